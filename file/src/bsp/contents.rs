@@ -0,0 +1,72 @@
+use super::{
+    lumps::{Leaf, Node, Plane, Vec3},
+    vis::find_leaf,
+};
+
+/// What a leaf of hull 0 is filled with, the values are `CONTENTS_*` of GoldSrc.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Contents {
+    Empty = -1,
+    /// Also the outside of the world.
+    Solid = -2,
+    Water = -3,
+    Slime = -4,
+    Lava = -5,
+    Sky = -6,
+    /// Brushes of the origin, clip and current textures are compiled away, so these are only
+    /// found in maps of broken compilers.
+    Origin = -7,
+    Clip = -8,
+    Current0 = -9,
+    Current90 = -10,
+    Current180 = -11,
+    Current270 = -12,
+    CurrentUp = -13,
+    CurrentDown = -14,
+    Translucent = -15,
+}
+
+impl Contents {
+    /// Contents of the `CONTENTS_*` value, other values are taken as solid.
+    pub const fn from_value(value: i32) -> Self {
+        match value {
+            -1 => Self::Empty,
+            -3 => Self::Water,
+            -4 => Self::Slime,
+            -5 => Self::Lava,
+            -6 => Self::Sky,
+            -7 => Self::Origin,
+            -8 => Self::Clip,
+            -9 => Self::Current0,
+            -10 => Self::Current90,
+            -11 => Self::Current180,
+            -12 => Self::Current270,
+            -13 => Self::CurrentUp,
+            -14 => Self::CurrentDown,
+            -15 => Self::Translucent,
+            _ => Self::Solid,
+        }
+    }
+
+    /// Water, slime or lava, which can be swum in.
+    pub const fn is_liquid(self) -> bool {
+        matches!(self, Self::Water | Self::Slime | Self::Lava)
+    }
+}
+
+/// Contents of the leaf containing `point`, found like by `find_leaf`. Points of missing
+/// leaves are solid.
+pub fn point_contents(
+    nodes: &[Node],
+    planes: &[Plane],
+    leaves: &[Leaf],
+    head_node: usize,
+    point: Vec3,
+) -> Contents {
+    if nodes.is_empty() {
+        return Contents::Solid;
+    }
+    leaves
+        .get(find_leaf(nodes, planes, head_node, point))
+        .map_or(Contents::Solid, |leaf| Contents::from_value(leaf.contents))
+}
@@ -0,0 +1,168 @@
+//! Faces of all brush models with their textures and coordinates resolved, the stable way to
+//! get geometry of a map without walking its lumps.
+
+use super::{
+    face_normal,
+    lightstyle::MAX_FACE_STYLES,
+    lumps::{
+        parse_edges, parse_faces, parse_models, parse_planes, parse_surfedges, parse_texinfos,
+        parse_textures, parse_vertices, Face, Model, Plane, TexInfo, Vec3,
+    },
+    uv::{face_uv, luxel_extents, texel_uv},
+    BspError, LumpType, RawMap,
+};
+
+/// Lighting lump offset of faces without a lightmap.
+const NO_LIGHTMAP: usize = u32::MAX as usize;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FaceVertex {
+    pub position: Vec3,
+    /// Texture coordinates normalized by the size of the texture, see `face_uv`.
+    pub uv: [f32; 2],
+    /// Position in luxels of the face's lightmap, a luxel is sampled at every whole one.
+    pub lightmap_uv: Option<[f32; 2]>,
+}
+
+/// Lightmaps of the face, one per style, each of `size` RGB luxels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FaceLightmap {
+    /// Offset of the first lightmap in the lighting lump, in bytes.
+    pub offset: usize,
+    pub size: [u32; 2],
+    /// Light styles of the lightmaps following each other, `NO_STYLE` ends them.
+    pub styles: [u8; MAX_FACE_STYLES],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapFace {
+    /// Index in the faces lump.
+    pub face_id: usize,
+    /// Brush model the face belongs to, 0 is the world. Faces of other models are positioned
+    /// like in the editor, entities of the models move them.
+    pub model_id: usize,
+    pub texture: String,
+    /// Size of the texture in texels.
+    pub texture_size: [u32; 2],
+    /// Surface flags of the texinfo, see `TEX_SPECIAL`.
+    pub flags: u32,
+    /// Normal of the face's plane, pointing to the side the face is seen from.
+    pub normal: Vec3,
+    pub lightmap: Option<FaceLightmap>,
+    /// Triangles of the polygon as a fan from its first vertex, wound like it.
+    pub triangles: Vec<[FaceVertex; 3]>,
+}
+
+/// Iterator over faces of a map, model by model in the order of the models lump. Faces which
+/// reference missing planes, texinfos, textures, edges or vertices, or have less than three
+/// vertices are skipped.
+pub struct Faces {
+    vertices: Vec<Vec3>,
+    edges: Vec<(u16, u16)>,
+    surfedges: Vec<i32>,
+    planes: Vec<Plane>,
+    texinfos: Vec<TexInfo>,
+    /// Names and sizes of textures.
+    textures: Vec<(String, [u32; 2])>,
+    faces: Vec<Face>,
+    models: Vec<Model>,
+    model_id: usize,
+    face_id: usize,
+}
+
+impl Faces {
+    pub(super) fn new(map: &RawMap) -> Result<Self, BspError> {
+        let malformed = |_| BspError::Malformed;
+        let models = parse_models(map.lump_data(LumpType::Models)).map_err(malformed)?;
+        Ok(Self {
+            vertices: parse_vertices(map.lump_data(LumpType::Vertices)).map_err(malformed)?,
+            edges: parse_edges(map.lump_data(LumpType::Edges)).map_err(malformed)?,
+            surfedges: parse_surfedges(map.lump_data(LumpType::Surfegdes)).map_err(malformed)?,
+            planes: parse_planes(map.lump_data(LumpType::Planes)).map_err(malformed)?,
+            texinfos: parse_texinfos(map.lump_data(LumpType::TexInfo)).map_err(malformed)?,
+            textures: parse_textures(map.lump_data(LumpType::Textures))
+                .map_err(malformed)?
+                .iter()
+                .map(|t| (t.name().to_string(), [t.main_width(), t.main_height()]))
+                .collect(),
+            faces: parse_faces(map.lump_data(LumpType::Faces)).map_err(malformed)?,
+            face_id: models.first().map_or(0, |model| model.face_id),
+            models,
+            model_id: 0,
+        })
+    }
+
+    fn resolve(&self, face_id: usize) -> Option<MapFace> {
+        let face = self.faces.get(face_id)?;
+        let texinfo = self.texinfos.get(face.texinfo_id)?;
+        let (texture, [width, height]) = self.textures.get(texinfo.texture_id)?;
+        let plane = self.planes.get(face.plane_id)?;
+        let positions = self
+            .surfedges
+            .get(face.surfedge_id..face.surfedge_id.checked_add(face.surfedge_num)?)?
+            .iter()
+            .map(|&s| {
+                let edge = self.edges.get(s.unsigned_abs() as usize)?;
+                let i = if s < 0 { edge.1 } else { edge.0 };
+                self.vertices.get(i as usize).copied()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if positions.len() < 3 {
+            return None;
+        }
+
+        let tex_coords: Vec<_> = positions.iter().map(|&v| texel_uv(v, texinfo)).collect();
+        let (first_luxel, size) = luxel_extents(&tex_coords);
+        let lightmap = (face.lightmap != NO_LIGHTMAP).then_some(FaceLightmap {
+            offset: face.lightmap,
+            size,
+            styles: face.styles,
+        });
+        let vertices: Vec<_> = positions
+            .iter()
+            .zip(&tex_coords)
+            .map(|(&position, &[s, t])| FaceVertex {
+                position,
+                uv: face_uv(position, texinfo, *width, *height),
+                lightmap_uv: lightmap
+                    .map(|_| [s / 16.0 - first_luxel[0], t / 16.0 - first_luxel[1]]),
+            })
+            .collect();
+        let triangles = (1..vertices.len() - 1)
+            .map(|i| [vertices[0], vertices[i], vertices[i + 1]])
+            .collect();
+
+        let normal = face_normal(plane, face.side);
+        Some(MapFace {
+            face_id,
+            model_id: self.model_id,
+            texture: texture.clone(),
+            texture_size: [*width, *height],
+            flags: texinfo.flags,
+            normal,
+            lightmap,
+            triangles,
+        })
+    }
+}
+
+impl Iterator for Faces {
+    type Item = MapFace;
+
+    fn next(&mut self) -> Option<MapFace> {
+        loop {
+            let model = self.models.get(self.model_id)?;
+            let end = model.face_id.saturating_add(model.face_num);
+            if self.face_id >= end.min(self.faces.len()) {
+                self.model_id += 1;
+                self.face_id = self.models.get(self.model_id).map_or(0, |m| m.face_id);
+                continue;
+            }
+            let face_id = self.face_id;
+            self.face_id += 1;
+            if let Some(face) = self.resolve(face_id) {
+                return Some(face);
+            }
+        }
+    }
+}
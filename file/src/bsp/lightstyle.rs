@@ -0,0 +1,57 @@
+//! Light styles of faces, brightness of lightmaps changing over time like flickering lights.
+
+/// Faces have up to that many lightmaps, one per style.
+pub const MAX_FACE_STYLES: usize = 4;
+/// Style of unused lightmap slots of faces.
+pub const NO_STYLE: u8 = 255;
+/// Styles are numbered below that.
+pub const STYLES_NUM: usize = 64;
+/// Letters of patterns advance that many times per second.
+pub const STYLE_FPS: f32 = 10.0;
+/// Styles from this one on are switched by lights with names, they're taken as on.
+const SWITCHABLE_STYLES: usize = 32;
+/// Brightness of styles which aren't in `PATTERNS`, the normal one.
+const NORMAL: &str = "m";
+
+/// Patterns of the game's styles, `a` is dark and `z` is double bright.
+const PATTERNS: [&str; 13] = [
+    NORMAL,
+    "mmnmmommommnonmmonqnmmo",
+    "abcdefghijklmnopqrstuvwxyzyxwvutsrqponmlkjihgfedcba",
+    "mmmmmaaaaammmmmaaaaaabcdefgabcdefg",
+    "mamamamamama",
+    "jklmnopqrstuvwxyzyxwvutsrqponmlkj",
+    "nmonqnmomnmomomno",
+    "mmmaaaabcdefgmmmmaaaammmaamm",
+    "mmmaaammmaaammmabcdefaaaammmmabcdefmmmaaaa",
+    "aaaaaaaazzzzzzzz",
+    "mmamammmmammamamaaamammma",
+    "abcdefghijklmnopqrrqponmlkjihgfedcba",
+    "mmnnmmnnnmmnn",
+];
+/// Unlit style the game uses for testing.
+const TEST_STYLE: usize = 63;
+
+/// Brightness the pattern has `time` seconds from its start, `m` is 1.
+pub fn pattern_brightness(pattern: &str, time: f32) -> f32 {
+    let pattern = pattern.as_bytes();
+    if pattern.is_empty() {
+        return 1.0;
+    }
+    let frame = (time.max(0.0) * STYLE_FPS) as usize % pattern.len();
+    f32::from(pattern[frame].saturating_sub(b'a')) / f32::from(b'm' - b'a')
+}
+
+/// Brightness of all styles `time` seconds from the start.
+pub fn style_brightness(time: f32) -> [f32; STYLES_NUM] {
+    let mut brightness = [1.0; STYLES_NUM];
+    for (style, value) in brightness.iter_mut().enumerate() {
+        let pattern = match style {
+            TEST_STYLE => "a",
+            style if style < SWITCHABLE_STYLES => PATTERNS.get(style).copied().unwrap_or(NORMAL),
+            _ => NORMAL,
+        };
+        *value = pattern_brightness(pattern, time);
+    }
+    brightness
+}
@@ -1,11 +1,12 @@
 // TODO : Reformat to human-read structures
 
+use super::lightstyle::MAX_FACE_STYLES;
 use crate::miptex::MipTexture;
 use nom::{
     bytes::complete::{take, take_until},
     combinator::{map, map_res},
     multi::{count, many0},
-    number::complete::{le_f32, le_i32, le_u16, le_u32, le_u8},
+    number::complete::{le_f32, le_i16, le_i32, le_u16, le_u32, le_u8},
     sequence::tuple,
 };
 
@@ -15,69 +16,116 @@ type ParseResult<'a, O> = nom::IResult<Input<'a>, O, ParseError<'a>>;
 type OnlyResult<'a, O> = Result<O, nom::Err<ParseError<'a>>>;
 type ParseError<'a> = nom::error::VerboseError<Input<'a>>;
 
+#[derive(Clone)]
 pub struct TexInfo {
     pub vs: Vec3,
     pub ss: f32,
     pub vt: Vec3,
     pub st: f32,
     pub texture_id: usize,
+    /// Surface flags, see `TEX_SPECIAL`.
+    pub flags: u32,
 }
 
+/// Flag of texinfos of unlit surfaces without lightmaps, like sky and liquids.
+pub const TEX_SPECIAL: u32 = 1;
+
 pub struct Face {
     pub plane_id: usize,
     pub side: bool,
     pub surfedge_id: usize,
     pub surfedge_num: usize,
     pub texinfo_id: usize,
+    /// Light styles of the face's lightmaps, which follow each other, `NO_STYLE` ends them.
+    pub styles: [u8; MAX_FACE_STYLES],
     pub lightmap: usize,
 }
 
 pub struct Model {
     pub origin: Vec3,
+    /// Roots of the model's trees: the nodes lump one for hull 0, then the clipnodes lump
+    /// ones for hulls 1 to 3 (standing, crouching player and big monsters).
+    pub head_nodes: [usize; 4],
+    /// Number of leaves covered by visibility data, not counting the outside leaf 0.
+    pub visleafs: usize,
     pub face_id: usize,
     pub face_num: usize,
 }
 
-pub fn parse_entities_str(i: &[u8]) -> OnlyResult<&str> {
+#[derive(Clone, Copy)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub dist: f32,
+}
+
+/// Child of a node, negative ones number leaves as `-(leaf + 1)`.
+pub type NodeChild = i16;
+
+pub struct Node {
+    pub plane_id: usize,
+    /// Front and back children.
+    pub children: [NodeChild; 2],
+    pub mins: [i16; 3],
+    pub maxs: [i16; 3],
+}
+
+/// Node of a collision hull, negative children are contents, e.g. `CONTENTS_SOLID`.
+pub struct ClipNode {
+    pub plane_id: usize,
+    /// Front and back children.
+    pub children: [NodeChild; 2],
+}
+
+pub struct Leaf {
+    pub contents: i32,
+    /// Offset to the compressed PVS row in the visibility lump, negative if there's none.
+    pub vis_offset: i32,
+    pub mins: [i16; 3],
+    pub maxs: [i16; 3],
+    pub marksurface_id: usize,
+    pub marksurface_num: usize,
+}
+
+pub fn parse_entities_str(i: &[u8]) -> OnlyResult<'_, &str> {
     let (_, s) = map_res(take_until("\0"), std::str::from_utf8)(i)?;
     Ok(s)
 }
 
-fn parse_vec3(i: &[u8]) -> ParseResult<Vec3> {
+fn parse_vec3(i: &[u8]) -> ParseResult<'_, Vec3> {
     tuple((le_f32, le_f32, le_f32))(i)
 }
 
-pub fn parse_vertices(i: &[u8]) -> OnlyResult<Vec<Vec3>> {
+pub fn parse_vertices(i: &[u8]) -> OnlyResult<'_, Vec<Vec3>> {
     let (_, vertices) = many0(parse_vec3)(i)?;
     Ok(vertices)
 }
 
-fn parse_edge(i: &[u8]) -> ParseResult<(u16, u16)> {
+fn parse_edge(i: &[u8]) -> ParseResult<'_, (u16, u16)> {
     tuple((le_u16, le_u16))(i)
 }
 
-pub fn parse_edges(i: &[u8]) -> OnlyResult<Vec<(u16, u16)>> {
+pub fn parse_edges(i: &[u8]) -> OnlyResult<'_, Vec<(u16, u16)>> {
     let (_, edges) = many0(parse_edge)(i)?;
     Ok(edges)
 }
 
-pub fn parse_surfedges(i: &[u8]) -> OnlyResult<Vec<i32>> {
+pub fn parse_surfedges(i: &[u8]) -> OnlyResult<'_, Vec<i32>> {
     let (_, surfedges) = many0(le_i32)(i)?;
     Ok(surfedges)
 }
 
-fn parse_normal_from_plane(i: &[u8]) -> ParseResult<Vec3> {
+fn parse_normal_from_plane(i: &[u8]) -> ParseResult<'_, Vec3> {
     let (i, (normal, _, _)) = tuple((parse_vec3, le_f32, le_u32))(i)?;
     Ok((i, normal))
 }
 
-pub fn parse_normals_from_planes(i: &[u8]) -> OnlyResult<Vec<Vec3>> {
+pub fn parse_normals_from_planes(i: &[u8]) -> OnlyResult<'_, Vec<Vec3>> {
     let (_, normals) = many0(parse_normal_from_plane)(i)?;
     Ok(normals)
 }
 
-fn parse_texinfo(i: &[u8]) -> ParseResult<TexInfo> {
-    let (i, (vs, ss, vt, st, texture_id, _)) = tuple((
+fn parse_texinfo(i: &[u8]) -> ParseResult<'_, TexInfo> {
+    let (i, (vs, ss, vt, st, texture_id, flags)) = tuple((
         parse_vec3,
         le_f32,
         parse_vec3,
@@ -93,29 +141,26 @@ fn parse_texinfo(i: &[u8]) -> ParseResult<TexInfo> {
             vt,
             st,
             texture_id,
+            flags,
         },
     ))
 }
 
-pub fn parse_texinfos(i: &[u8]) -> OnlyResult<Vec<TexInfo>> {
+pub fn parse_texinfos(i: &[u8]) -> OnlyResult<'_, Vec<TexInfo>> {
     let (_, texinfos) = many0(parse_texinfo)(i)?;
     Ok(texinfos)
 }
 
-fn parse_face(i: &[u8]) -> ParseResult<Face> {
-    let (i, (plane_id, side, surfedge_id, surfedge_num, texinfo_id, _, _, _, _, lightmap)) =
-        tuple((
-            map(le_u16, |x| x as usize),
-            map(le_u16, |x| x != 0),
-            map(le_u32, |x| x as usize),
-            map(le_u16, |x| x as usize),
-            map(le_u16, |x| x as usize),
-            le_u8,
-            le_u8,
-            le_u8,
-            le_u8,
-            map(le_u32, |x| x as usize),
-        ))(i)?;
+fn parse_face(i: &[u8]) -> ParseResult<'_, Face> {
+    let (i, (plane_id, side, surfedge_id, surfedge_num, texinfo_id, styles, lightmap)) = tuple((
+        map(le_u16, |x| x as usize),
+        map(le_u16, |x| x != 0),
+        map(le_u32, |x| x as usize),
+        map(le_u16, |x| x as usize),
+        map(le_u16, |x| x as usize),
+        map(count(le_u8, MAX_FACE_STYLES), |x| [x[0], x[1], x[2], x[3]]),
+        map(le_u32, |x| x as usize),
+    ))(i)?;
     Ok((
         i,
         Face {
@@ -124,25 +169,25 @@ fn parse_face(i: &[u8]) -> ParseResult<Face> {
             surfedge_id,
             surfedge_num,
             texinfo_id,
+            styles,
             lightmap,
         },
     ))
 }
 
-pub fn parse_faces(i: &[u8]) -> OnlyResult<Vec<Face>> {
+pub fn parse_faces(i: &[u8]) -> OnlyResult<'_, Vec<Face>> {
     let (_, faces) = many0(parse_face)(i)?;
     Ok(faces)
 }
 
-fn parse_model(i: &[u8]) -> ParseResult<Model> {
-    let (i, (_, origin, _, _, _, _, _, face_id, face_num)) = tuple((
+fn parse_model(i: &[u8]) -> ParseResult<'_, Model> {
+    let (i, (_, origin, head_nodes, visleafs, face_id, face_num)) = tuple((
         tuple((parse_vec3, parse_vec3)),
         parse_vec3,
-        le_u32,
-        le_u32,
-        le_u32,
-        le_u32,
-        le_u32,
+        map(count(le_u32, 4), |x| {
+            [x[0] as usize, x[1] as usize, x[2] as usize, x[3] as usize]
+        }),
+        map(le_u32, |x| x as usize),
         map(le_u32, |x| x as usize),
         map(le_u32, |x| x as usize),
     ))(i)?;
@@ -150,18 +195,110 @@ fn parse_model(i: &[u8]) -> ParseResult<Model> {
         i,
         Model {
             origin,
+            head_nodes,
+            visleafs,
             face_id,
             face_num,
         },
     ))
 }
 
-pub fn parse_models(i: &[u8]) -> OnlyResult<Vec<Model>> {
+pub fn parse_models(i: &[u8]) -> OnlyResult<'_, Vec<Model>> {
     let (_, models) = many0(parse_model)(i)?;
     Ok(models)
 }
 
-pub fn parse_textures(lump: &[u8]) -> OnlyResult<Vec<MipTexture>> {
+fn parse_plane(i: &[u8]) -> ParseResult<'_, Plane> {
+    let (i, (normal, dist, _)) = tuple((parse_vec3, le_f32, le_u32))(i)?;
+    Ok((i, Plane { normal, dist }))
+}
+
+pub fn parse_planes(i: &[u8]) -> OnlyResult<'_, Vec<Plane>> {
+    let (_, planes) = many0(parse_plane)(i)?;
+    Ok(planes)
+}
+
+fn parse_bounds(i: &[u8]) -> ParseResult<'_, ([i16; 3], [i16; 3])> {
+    let (i, (min_x, min_y, min_z, max_x, max_y, max_z)) =
+        tuple((le_i16, le_i16, le_i16, le_i16, le_i16, le_i16))(i)?;
+    Ok((i, ([min_x, min_y, min_z], [max_x, max_y, max_z])))
+}
+
+fn parse_node(i: &[u8]) -> ParseResult<'_, Node> {
+    let (i, (plane_id, front, back, (mins, maxs), _, _)) = tuple((
+        map(le_u32, |x| x as usize),
+        le_i16,
+        le_i16,
+        parse_bounds,
+        le_u16,
+        le_u16,
+    ))(i)?;
+    Ok((
+        i,
+        Node {
+            plane_id,
+            children: [front, back],
+            mins,
+            maxs,
+        },
+    ))
+}
+
+pub fn parse_nodes(i: &[u8]) -> OnlyResult<'_, Vec<Node>> {
+    let (_, nodes) = many0(parse_node)(i)?;
+    Ok(nodes)
+}
+
+fn parse_clipnode(i: &[u8]) -> ParseResult<'_, ClipNode> {
+    let (i, (plane_id, front, back)) = tuple((map(le_u32, |x| x as usize), le_i16, le_i16))(i)?;
+    Ok((
+        i,
+        ClipNode {
+            plane_id,
+            children: [front, back],
+        },
+    ))
+}
+
+pub fn parse_clipnodes(i: &[u8]) -> OnlyResult<'_, Vec<ClipNode>> {
+    let (_, clipnodes) = many0(parse_clipnode)(i)?;
+    Ok(clipnodes)
+}
+
+fn parse_leaf(i: &[u8]) -> ParseResult<'_, Leaf> {
+    let (i, (contents, vis_offset, (mins, maxs), marksurface_id, marksurface_num, _)) = tuple((
+        le_i32,
+        le_i32,
+        parse_bounds,
+        map(le_u16, |x| x as usize),
+        map(le_u16, |x| x as usize),
+        le_u32,
+    ))(i)?;
+    Ok((
+        i,
+        Leaf {
+            contents,
+            vis_offset,
+            mins,
+            maxs,
+            marksurface_id,
+            marksurface_num,
+        },
+    ))
+}
+
+pub fn parse_leaves(i: &[u8]) -> OnlyResult<'_, Vec<Leaf>> {
+    let (_, leaves) = many0(parse_leaf)(i)?;
+    Ok(leaves)
+}
+
+/// Indices of faces referenced by leaves.
+pub fn parse_marksurfaces(i: &[u8]) -> OnlyResult<'_, Vec<usize>> {
+    let (_, marksurfaces) = many0(map(le_u16, |x| x as usize))(i)?;
+    Ok(marksurfaces)
+}
+
+pub fn parse_textures(lump: &[u8]) -> OnlyResult<'_, Vec<MipTexture<'_>>> {
     let (i, offsets_num) = map(le_u32, |x| x as usize)(lump)?;
     let (_, offsets) = count(le_u32, offsets_num)(i)?;
     offsets
@@ -0,0 +1,150 @@
+//! Simplification of BSP face polygons: merging adjacent coplanar faces
+//! and dropping the collinear vertices that BSP splitting leaves behind.
+
+use super::lumps::Vec3;
+
+const EPSILON: f32 = 0.01;
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+fn same_point(a: Vec3, b: Vec3) -> bool {
+    let d = sub(a, b);
+    dot(d, d) < EPSILON * EPSILON
+}
+
+/// Newell's normal, its direction follows the winding of the polygon.
+fn winding_normal(polygon: &[Vec3]) -> Vec3 {
+    let n = polygon.len();
+    (0..n).fold((0.0, 0.0, 0.0), |acc, i| {
+        let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+        (
+            acc.0 + (a.1 - b.1) * (a.2 + b.2),
+            acc.1 + (a.2 - b.2) * (a.0 + b.0),
+            acc.2 + (a.0 - b.0) * (a.1 + b.1),
+        )
+    })
+}
+
+/// Area of a planar polygon, 0 for ones of less than 3 vertices.
+pub fn polygon_area(polygon: &[Vec3]) -> f32 {
+    let normal = winding_normal(polygon);
+    dot(normal, normal).sqrt() / 2.0
+}
+
+/// Removes vertices lying on the straight line between their neighbours.
+pub fn remove_collinear(polygon: &[Vec3]) -> Vec<Vec3> {
+    let mut result = polygon.to_vec();
+    let mut i = 0;
+    while result.len() > 3 && i < result.len() {
+        let n = result.len();
+        let (a, b, c) = (result[(i + n - 1) % n], result[i], result[(i + 1) % n]);
+        let (ab, bc) = (sub(b, a), sub(c, b));
+        let turn = cross(ab, bc);
+        let scale = dot(ab, ab).sqrt() * dot(bc, bc).sqrt();
+        if dot(turn, turn).sqrt() <= EPSILON * scale && dot(ab, bc) > 0.0 {
+            result.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    result
+}
+
+/// Removes repeated points and the zero-width spikes left by joining
+/// polygons sharing more than one consecutive edge.
+fn remove_spikes(polygon: &mut Vec<Vec3>) {
+    let mut i = 0;
+    while polygon.len() >= 3 && i < polygon.len() {
+        let n = polygon.len();
+        let (prev, cur, next) = (polygon[(i + n - 1) % n], polygon[i], polygon[(i + 1) % n]);
+        if same_point(cur, next) {
+            polygon.remove(i);
+        } else if same_point(prev, next) {
+            polygon.remove(i);
+            let next = i % polygon.len();
+            polygon.remove(next);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn is_convex(polygon: &[Vec3]) -> bool {
+    let normal = winding_normal(polygon);
+    let n = polygon.len();
+    (0..n).all(|i| {
+        let (a, b, c) = (polygon[i], polygon[(i + 1) % n], polygon[(i + 2) % n]);
+        dot(cross(sub(b, a), sub(c, b)), normal) >= -EPSILON
+    })
+}
+
+/// Joins two polygons along a shared edge, given they have the same winding.
+fn join(a: &[Vec3], b: &[Vec3]) -> Option<Vec<Vec3>> {
+    let (na, nb) = (a.len(), b.len());
+    for i in 0..na {
+        let (p, q) = (a[i], a[(i + 1) % na]);
+        // Same winding means the shared edge goes the opposite way in `b`
+        let j = (0..nb).find(|&j| same_point(b[j], q) && same_point(b[(j + 1) % nb], p));
+        if let Some(j) = j {
+            let mut joined: Vec<_> = (0..na)
+                .map(|k| a[(i + 1 + k) % na])
+                .chain((0..nb - 2).map(|k| b[(j + 2 + k) % nb]))
+                .collect();
+            remove_spikes(&mut joined);
+            return if joined.len() >= 3 && is_convex(&joined) {
+                Some(joined)
+            } else {
+                None
+            };
+        }
+    }
+    None
+}
+
+/// Greedily merges convex polygons lying on one plane with the same winding.
+/// Returns resulting polygons together with indices of the source polygons they cover.
+/// Callers should only pass faces sharing plane and texinfo, so that planar texture
+/// mapping stays continuous across the merged polygon.
+pub fn merge_coplanar(polygons: &[Vec<Vec3>]) -> Vec<(Vec<Vec3>, Vec<usize>)> {
+    // Collinear vertices are kept until the end, they may be needed to find shared edges
+    let mut merged: Vec<_> = polygons
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.clone(), vec![i]))
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        'outer: for i in 0..merged.len() {
+            for j in i + 1..merged.len() {
+                if let Some(joined) = join(&merged[i].0, &merged[j].0) {
+                    let (_, sources) = merged.swap_remove(j);
+                    merged[i].0 = joined;
+                    merged[i].1.extend(sources);
+                    changed = true;
+                    break 'outer;
+                }
+            }
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(polygon, sources)| (remove_collinear(&polygon), sources))
+        .collect()
+}
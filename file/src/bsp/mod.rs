@@ -1,16 +1,23 @@
+pub mod contents;
+pub mod faces;
+pub mod lightstyle;
 pub mod lumps; // TODO
+pub mod merge;
+pub mod trace;
+pub mod uv;
+pub mod vis;
 
-use nom::{
-    bytes::complete::take,
-    combinator::{map, verify},
-    multi::count,
-    number::complete::le_u32,
-    sequence::tuple,
-};
+use crate::map::{Entities, Entity};
+use contents::{point_contents, Contents};
+use faces::Faces;
+use lumps::{parse_leaves, parse_models, parse_nodes, parse_planes, Leaf, Node, Plane, Vec3};
+use nom::{combinator::map, multi::count, number::complete::le_u32, sequence::tuple};
+use std::fmt;
 
 const LUMPS_NUM: usize = 15;
 const HLBSP_VERSION: u32 = 30;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LumpType {
     Entities,
     Planes,
@@ -32,37 +39,189 @@ pub enum LumpType {
 type Input<'a> = &'a [u8];
 type ParseError<'a> = nom::error::VerboseError<Input<'a>>;
 type ParseResult<'a, O> = nom::IResult<Input<'a>, O, ParseError<'a>>;
-type OnlyResult<'a, O> = Result<O, nom::Err<ParseError<'a>>>;
+
+/// Names of lumps in the order of `LumpType`, for errors.
+const LUMP_NAMES: [&str; LUMPS_NUM] = [
+    "entities",
+    "planes",
+    "textures",
+    "vertices",
+    "visibility",
+    "nodes",
+    "texinfo",
+    "faces",
+    "lighting",
+    "clipnodes",
+    "leaves",
+    "marksurfaces",
+    "edges",
+    "surfedges",
+    "models",
+];
 
 pub struct Lump<'a> {
     data: &'a [u8],
 }
 
 impl<'a> Lump<'a> {
-    fn parse(i: &'a [u8], file: &'a [u8]) -> ParseResult<'a, Self> {
-        let (i, (offset, size)) =
-            tuple((map(le_u32, |x| x as usize), map(le_u32, |x| x as usize)))(i)?;
-        let (lump_i, _) = take(offset)(file)?;
-        let (_, data) = take(size)(lump_i)?;
+    /// Offset and length of the lump from its entry of the header.
+    fn parse_entry(i: &'a [u8]) -> ParseResult<'a, (usize, usize)> {
+        tuple((map(le_u32, |x| x as usize), map(le_u32, |x| x as usize)))(i)
+    }
+
+    /// Data of the entry, which must lie within the file.
+    fn new(
+        file: &'a [u8],
+        lump: &'static str,
+        (offset, len): (usize, usize),
+    ) -> Result<Self, BspError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| file.get(offset..end))
+            .map(|data| Self { data })
+            .ok_or(BspError::TruncatedLump { lump, offset, len })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BspError {
+    /// Version of other games, e.g. 29 of Quake or 19 to 21 of Source.
+    UnsupportedVersion(u32),
+    /// Header or lumps which can't be parsed.
+    Malformed,
+    /// Lump of the header going past the end of the file.
+    TruncatedLump {
+        lump: &'static str,
+        offset: usize,
+        len: usize,
+    },
+}
 
-        Ok((i, Self { data }))
+impl fmt::Display for BspError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported bsp version {}, only version {} of GoldSrc is supported",
+                version, HLBSP_VERSION
+            ),
+            Self::Malformed => write!(f, "malformed bsp header or entities"),
+            Self::TruncatedLump { lump, offset, len } => write!(
+                f,
+                "{} lump of {} bytes at offset {} goes past the end of the file",
+                lump, len, offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BspError {}
+
+/// Normal of the side of the plane a face is on, faces on the back side face against the
+/// plane's normal.
+pub fn face_normal(plane: &Plane, side: bool) -> Vec3 {
+    let (x, y, z) = plane.normal;
+    if side {
+        (-x, -y, -z)
+    } else {
+        (x, y, z)
     }
 }
 
 pub struct RawMap<'a> {
     lumps: Vec<Lump<'a>>,
+    entities: Entities<'a>,
+    /// `None` if lumps of the tree are malformed.
+    world_tree: Option<WorldTree>,
+}
+
+/// Hull 0 of the world model, which `RawMap::point_contents` walks.
+struct WorldTree {
+    nodes: Vec<Node>,
+    planes: Vec<Plane>,
+    leaves: Vec<Leaf>,
+    head_node: usize,
+}
+
+impl WorldTree {
+    fn parse(lumps: &[Lump]) -> Option<Self> {
+        let lump_data = |lump_type| lumps[lump_type as usize].data;
+        let models = parse_models(lump_data(LumpType::Models)).ok()?;
+        Some(Self {
+            nodes: parse_nodes(lump_data(LumpType::Nodes)).ok()?,
+            planes: parse_planes(lump_data(LumpType::Planes)).ok()?,
+            leaves: parse_leaves(lump_data(LumpType::Leaves)).ok()?,
+            head_node: models.first()?.head_nodes[0],
+        })
+    }
 }
 
 impl<'a> RawMap<'a> {
-    pub fn parse(file: &'a [u8]) -> OnlyResult<Self> {
-        let (_, (_, lumps)) = tuple((
-            verify(le_u32, |&x| x == HLBSP_VERSION),
-            count(|i| Lump::parse(i, file), LUMPS_NUM),
-        ))(file)?;
-        Ok(RawMap { lumps })
+    pub fn parse(file: &'a [u8]) -> Result<Self, BspError> {
+        let (i, version) = le_u32::<_, ParseError>(file).map_err(|_| BspError::Malformed)?;
+        if version != HLBSP_VERSION {
+            return Err(BspError::UnsupportedVersion(version));
+        }
+        let (_, entries) =
+            count(Lump::parse_entry, LUMPS_NUM)(i).map_err(|_| BspError::Malformed)?;
+        let lumps = entries
+            .into_iter()
+            .zip(LUMP_NAMES.iter())
+            .map(|(entry, &lump)| Lump::new(file, lump, entry))
+            .collect::<Result<Vec<_>, _>>()?;
+        let entities_str = lumps::parse_entities_str(lumps[LumpType::Entities as usize].data)
+            .map_err(|_| BspError::Malformed)?;
+        let entities = Entities::parse(entities_str).map_err(|_| BspError::Malformed)?;
+        let world_tree = WorldTree::parse(&lumps);
+        Ok(RawMap {
+            lumps,
+            entities,
+            world_tree,
+        })
     }
 
     pub fn lump_data(&self, lump_type: LumpType) -> &[u8] {
         self.lumps[lump_type as usize].data
     }
+
+    pub const fn entities(&self) -> &Entities<'a> {
+        &self.entities
+    }
+
+    /// All entities matching the predicate, in the order they appear in the entities lump.
+    pub fn entities_where<P: Fn(&Entity<'a>) -> bool>(&self, pred: P) -> Vec<&Entity<'a>> {
+        self.entities
+            .entities()
+            .iter()
+            .filter(|e| pred(e))
+            .collect()
+    }
+
+    /// Faces of all brush models with resolved textures and coordinates, see `Faces`. Fails if
+    /// lumps of the geometry can't be parsed.
+    pub fn faces(&self) -> Result<Faces, BspError> {
+        Faces::new(self)
+    }
+
+    /// Contents at the point of the world, walking hull 0 of its nodes and leaves. Maps with
+    /// malformed ones are solid everywhere.
+    pub fn point_contents(&self, point: Vec3) -> Contents {
+        self.world_tree.as_ref().map_or(Contents::Solid, |tree| {
+            point_contents(
+                &tree.nodes,
+                &tree.planes,
+                &tree.leaves,
+                tree.head_node,
+                point,
+            )
+        })
+    }
+
+    /// First entity with the given classname, e.g. `worldspawn` or `info_player_start`.
+    pub fn first_entity(&self, classname: &str) -> Option<&Entity<'a>> {
+        self.entities
+            .entities()
+            .iter()
+            .find(|e| e.classname() == Some(classname))
+    }
 }
@@ -0,0 +1,230 @@
+use super::lumps::{ClipNode, Leaf, Node, NodeChild, Plane, Vec3};
+use std::convert::TryFrom;
+
+pub const CONTENTS_EMPTY: i32 = -1;
+pub const CONTENTS_SOLID: i32 = -2;
+
+/// Hit planes are moved that far towards the start, so the end point doesn't get stuck in them.
+const DIST_EPSILON: f32 = 0.03125;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraceResult {
+    /// Part of the way from the start to the end which is passed before hitting anything.
+    pub fraction: f32,
+    pub end: Vec3,
+    /// Normal of the hit plane facing towards the start, `None` if nothing was hit.
+    pub normal: Option<Vec3>,
+    pub start_solid: bool,
+    /// The whole way is inside of solid contents.
+    pub all_solid: bool,
+}
+
+/// Collision tree of a model for a box of the hull's size, so its center point can be traced
+/// instead of the box.
+pub struct Hull {
+    clipnodes: Vec<ClipNode>,
+    planes: Vec<Plane>,
+    /// Index of the head clipnode, or `CONTENTS_SOLID` if it's missing.
+    head: NodeChild,
+}
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+fn lerp(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    (
+        a.0 + (b.0 - a.0) * t,
+        a.1 + (b.1 - a.1) * t,
+        a.2 + (b.2 - a.2) * t,
+    )
+}
+
+/// Replaces children which are missing clipnodes, ones with missing planes or ones closing a
+/// cycle with `CONTENTS_SOLID`, so walking the tree from the returned head never fails.
+fn check_tree(clipnodes: &mut [ClipNode], planes: &[Plane], head_node: usize) -> NodeChild {
+    #[derive(Copy, Clone, PartialEq)]
+    enum Mark {
+        New,
+        /// On the path from the head to the current node.
+        Open,
+        Done,
+    }
+
+    let usable = clipnodes
+        .iter()
+        .map(|node| node.plane_id < planes.len())
+        .collect::<Vec<_>>();
+    let head = match NodeChild::try_from(head_node) {
+        Ok(head) if usable.get(head_node) == Some(&true) => head,
+        _ => return CONTENTS_SOLID as NodeChild,
+    };
+    let mut marks = vec![Mark::New; clipnodes.len()];
+    marks[head_node] = Mark::Open;
+    // Depth-first with nodes and their next children to visit
+    let mut stack = vec![(head_node, 0)];
+    while let Some((node, side)) = stack.pop() {
+        if side == 2 {
+            marks[node] = Mark::Done;
+            continue;
+        }
+        stack.push((node, side + 1));
+        let child = clipnodes[node].children[side];
+        if child < 0 {
+            continue;
+        }
+        let i = child as usize;
+        if usable.get(i) != Some(&true) || marks[i] == Mark::Open {
+            clipnodes[node].children[side] = CONTENTS_SOLID as NodeChild;
+        } else if marks[i] == Mark::New {
+            marks[i] = Mark::Open;
+            stack.push((i, 0));
+        }
+    }
+    head
+}
+
+impl Hull {
+    /// `head_node` is one of `Model::head_nodes` for hulls 1 to 3. Broken references of the
+    /// tree are solid.
+    pub fn new(mut clipnodes: Vec<ClipNode>, planes: Vec<Plane>, head_node: usize) -> Self {
+        let head = check_tree(&mut clipnodes, &planes, head_node);
+        Self {
+            clipnodes,
+            planes,
+            head,
+        }
+    }
+
+    /// Point hull 0, which has no clipnodes and is made of the BSP nodes, whose leaves become
+    /// their contents. `head_node` is `Model::head_nodes[0]`.
+    pub fn from_nodes(
+        nodes: &[Node],
+        leaves: &[Leaf],
+        planes: Vec<Plane>,
+        head_node: usize,
+    ) -> Self {
+        let contents = |child: NodeChild| {
+            if child >= 0 {
+                return child;
+            }
+            leaves
+                .get((-1 - i32::from(child)) as usize)
+                .map_or(CONTENTS_SOLID, |leaf| leaf.contents) as NodeChild
+        };
+        let clipnodes = nodes
+            .iter()
+            .map(|node| ClipNode {
+                plane_id: node.plane_id,
+                children: [contents(node.children[0]), contents(node.children[1])],
+            })
+            .collect();
+        Self::new(clipnodes, planes, head_node)
+    }
+
+    fn distance(&self, node: &ClipNode, point: Vec3) -> f32 {
+        let plane = &self.planes[node.plane_id];
+        dot(plane.normal, point) - plane.dist
+    }
+
+    fn child_contents(&self, mut child: NodeChild, point: Vec3) -> i32 {
+        while child >= 0 {
+            let node = &self.clipnodes[child as usize];
+            child = node.children[if self.distance(node, point) >= 0.0 {
+                0
+            } else {
+                1
+            }];
+        }
+        child.into()
+    }
+
+    /// Contents at the point, e.g. `CONTENTS_EMPTY` or `CONTENTS_SOLID`.
+    pub fn contents(&self, point: Vec3) -> i32 {
+        if self.clipnodes.is_empty() {
+            return CONTENTS_EMPTY;
+        }
+        self.child_contents(self.head, point)
+    }
+
+    /// Moves the point from `start` to `end` until it hits solid contents.
+    pub fn trace(&self, start: Vec3, end: Vec3) -> TraceResult {
+        let mut result = TraceResult {
+            fraction: 1.0,
+            end,
+            normal: None,
+            start_solid: false,
+            all_solid: true,
+        };
+        if self.clipnodes.is_empty() {
+            result.all_solid = false;
+            return result;
+        }
+        self.trace_child(self.head, (0.0, 1.0), (start, end), &mut result);
+        result
+    }
+
+    /// Traces the segment through the subtree of `child`, returns `false` when it hit something.
+    fn trace_child(
+        &self,
+        child: NodeChild,
+        (f1, f2): (f32, f32),
+        (p1, p2): (Vec3, Vec3),
+        result: &mut TraceResult,
+    ) -> bool {
+        if child < 0 {
+            if i32::from(child) == CONTENTS_SOLID {
+                result.start_solid = true;
+            } else {
+                result.all_solid = false;
+            }
+            return true;
+        }
+
+        let node = &self.clipnodes[child as usize];
+        let (t1, t2) = (self.distance(node, p1), self.distance(node, p2));
+        if t1 >= 0.0 && t2 >= 0.0 {
+            return self.trace_child(node.children[0], (f1, f2), (p1, p2), result);
+        }
+        if t1 < 0.0 && t2 < 0.0 {
+            return self.trace_child(node.children[1], (f1, f2), (p1, p2), result);
+        }
+
+        // The segment crosses the plane, so split it a bit on the start's side
+        let side = if t1 < 0.0 { 1 } else { 0 };
+        let mut frac = if t1 < 0.0 {
+            (t1 + DIST_EPSILON) / (t1 - t2)
+        } else {
+            (t1 - DIST_EPSILON) / (t1 - t2)
+        }
+        .clamp(0.0, 1.0);
+        let mut mid_f = f1 + (f2 - f1) * frac;
+        let mut mid = lerp(p1, p2, frac);
+
+        if !self.trace_child(node.children[side], (f1, mid_f), (p1, mid), result) {
+            return false;
+        }
+        if self.child_contents(node.children[side ^ 1], mid) != CONTENTS_SOLID {
+            return self.trace_child(node.children[side ^ 1], (mid_f, f2), (mid, p2), result);
+        }
+        if result.all_solid {
+            // Never got out of the solid
+            return false;
+        }
+
+        let n = self.planes[node.plane_id].normal;
+        result.normal = Some(if side == 0 { n } else { (-n.0, -n.1, -n.2) });
+        // Float errors may still leave the point inside, so step back until it's out
+        while self.contents(mid) == CONTENTS_SOLID {
+            frac -= 0.1;
+            if frac < 0.0 {
+                break;
+            }
+            mid_f = f1 + (f2 - f1) * frac;
+            mid = lerp(p1, p2, frac);
+        }
+        result.fraction = mid_f;
+        result.end = mid;
+        false
+    }
+}
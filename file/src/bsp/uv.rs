@@ -0,0 +1,46 @@
+use super::lumps::{TexInfo, Vec3};
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+/// Texture coordinates of a vertex in texels: its projections onto the S and T axes of the
+/// texinfo plus their offsets. The axes already carry the scale of the texture, so a texel
+/// is `1 / |axis|` units long.
+pub fn texel_uv(vertex: Vec3, texinfo: &TexInfo) -> [f32; 2] {
+    [
+        dot(vertex, texinfo.vs) + texinfo.ss,
+        dot(vertex, texinfo.vt) + texinfo.st,
+    ]
+}
+
+/// Texture coordinates of a vertex normalized by the size of the texture, so the texture
+/// repeats every 1. V goes down the image, like its rows. Empty textures count as 1x1.
+pub fn face_uv(vertex: Vec3, texinfo: &TexInfo, tex_w: u32, tex_h: u32) -> [f32; 2] {
+    let [s, t] = texel_uv(vertex, texinfo);
+    [s / tex_w.max(1) as f32, t / tex_h.max(1) as f32]
+}
+
+/// First luxel and size in luxels of the lightmap of a face with texture coordinates in texels,
+/// see `texel_uv`. Luxels are spaced 16 texels apart starting at the extents rounded down to a
+/// multiple of 16, so a vertex is at `s / 16 - first` luxels of the lightmap.
+pub fn luxel_extents(tex_coords: &[[f32; 2]]) -> ([f32; 2], [u32; 2]) {
+    if tex_coords.is_empty() {
+        return ([0.0; 2], [0; 2]);
+    }
+    let (min, max) = tex_coords.iter().fold(
+        ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+        |(min, max), &[u, v]| {
+            (
+                [min[0].min(u), min[1].min(v)],
+                [max[0].max(u), max[1].max(v)],
+            )
+        },
+    );
+    let first = [(min[0] / 16.0).floor(), (min[1] / 16.0).floor()];
+    let size = [
+        ((max[0] / 16.0).ceil() - first[0] + 1.0) as u32,
+        ((max[1] / 16.0).ceil() - first[1] + 1.0) as u32,
+    ];
+    (first, size)
+}
@@ -0,0 +1,51 @@
+use super::lumps::{Leaf, Node, Plane, Vec3};
+
+/// Leaf containing `point`, found by walking the tree from `head_node`. Leaf 0 is the
+/// solid outside of the world, it's also the result for malformed trees: ones with indices
+/// of missing nodes or planes, or with cycles.
+pub fn find_leaf(nodes: &[Node], planes: &[Plane], head_node: usize, point: Vec3) -> usize {
+    let mut child = head_node as i32;
+    // No path down a tree is longer than the number of its nodes
+    for _ in 0..=nodes.len() {
+        if child < 0 {
+            return (-child - 1) as usize;
+        }
+        let node = match nodes.get(child as usize) {
+            Some(node) => node,
+            None => return 0,
+        };
+        let plane = match planes.get(node.plane_id) {
+            Some(plane) => plane,
+            None => return 0,
+        };
+        let n = plane.normal;
+        let dist = n.0 * point.0 + n.1 * point.1 + n.2 * point.2 - plane.dist;
+        child = node.children[if dist >= 0.0 { 0 } else { 1 }].into();
+    }
+    0
+}
+
+/// Decompresses the run-length encoded PVS row of `leaf`. The result tells for every leaf of
+/// `leaves_num` (`Model::visleafs`) whether it's visible, starting from leaf 1. Leaves without
+/// visibility data see everything.
+pub fn decompress_vis(vis: &[u8], leaf: &Leaf, leaves_num: usize) -> Vec<bool> {
+    if leaf.vis_offset < 0 {
+        return vec![true; leaves_num];
+    }
+
+    let mut visible = Vec::with_capacity(leaves_num + 7);
+    let mut i = vis.iter().skip(leaf.vis_offset as usize);
+    while visible.len() < leaves_num {
+        match i.next() {
+            // Zero bytes are followed by the number of repeated zero bytes
+            Some(0) => {
+                let zeros = i.next().copied().unwrap_or(0) as usize;
+                visible.resize(visible.len() + zeros * 8, false);
+            }
+            Some(&bits) => visible.extend((0..8).map(|bit| bits & (1 << bit) != 0)),
+            None => break,
+        }
+    }
+    visible.resize(leaves_num, false);
+    visible
+}
@@ -0,0 +1,92 @@
+use crate::pak::Pak;
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+use zip::ZipArchive;
+
+const ZIP_SEPARATOR: &str = ".zip:";
+const PAK_SEPARATOR: &str = ".pak:";
+
+fn split_path<P: AsRef<Path>>(path: P, separator: &str) -> Option<(PathBuf, String)> {
+    let path = path.as_ref().to_string_lossy();
+    let split = path.to_ascii_lowercase().find(separator)? + separator.len();
+    let (archive, inner) = path.split_at(split);
+    Some((
+        PathBuf::from(&archive[..archive.len() - 1]),
+        inner.to_string(),
+    ))
+}
+
+/// Splits `pack.zip:maps/foo.bsp` into the archive's path and the inner one.
+pub fn split_zip_path<P: AsRef<Path>>(path: P) -> Option<(PathBuf, String)> {
+    split_path(path, ZIP_SEPARATOR)
+}
+
+/// Splits `pak0.pak:maps/foo.bsp` into the archive's path and the inner one.
+pub fn split_pak_path<P: AsRef<Path>>(path: P) -> Option<(PathBuf, String)> {
+    split_path(path, PAK_SEPARATOR)
+}
+
+/// Whether the path points inside a zip or pak archive.
+pub fn is_archived<P: AsRef<Path>>(path: P) -> bool {
+    split_zip_path(&path).is_some() || split_pak_path(&path).is_some()
+}
+
+/// Reads the file at `path`, which either is a plain one or points inside a zip or pak archive
+/// as `pack.zip:maps/foo.bsp` or `pak0.pak:maps/foo.bsp`. Inner paths are matched ignoring
+/// case and kind of slashes.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    if let Some((archive, inner)) = split_zip_path(&path) {
+        read_from_zip(archive, &inner)
+    } else if let Some((archive, inner)) = split_pak_path(&path) {
+        read_from_pak(archive, &inner)
+    } else {
+        std::fs::read(path)
+    }
+}
+
+fn not_found<P: AsRef<Path>>(archive_path: P, inner: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "no `{}` in {}",
+            inner,
+            archive_path.as_ref().to_string_lossy()
+        ),
+    )
+}
+
+fn read_from_pak<P: AsRef<Path>>(archive_path: P, inner: &str) -> io::Result<Vec<u8>> {
+    let file = std::fs::read(&archive_path)?;
+    let pak = Pak::parse(&file).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "malformed pak header or directory in {}",
+                archive_path.as_ref().to_string_lossy()
+            ),
+        )
+    })?;
+    pak.read(inner)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| not_found(&archive_path, inner))
+}
+
+fn read_from_zip<P: AsRef<Path>>(archive_path: P, inner: &str) -> io::Result<Vec<u8>> {
+    let normalize = |s: &str| s.replace('\\', "/").trim_start_matches('/').to_lowercase();
+
+    let mut archive = ZipArchive::new(File::open(&archive_path)?)?;
+    let inner_normalized = normalize(inner);
+    let name = archive
+        .file_names()
+        .find(|name| normalize(name) == inner_normalized)
+        .map(str::to_string)
+        .ok_or_else(|| not_found(&archive_path, inner))?;
+
+    let mut entry = archive.by_name(&name)?;
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
@@ -1,12 +1,69 @@
 use arraylib::Array;
-use image::{ImageError, ImageResult};
+use image::{imageops, ImageError, ImageResult, RgbaImage};
 use std::{
     io::{Error as IOError, ErrorKind},
-    path::Path,
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
-const EXTENSION: &str = "tga";
-const SIDES: [&str; 6] = ["rt", "lf", "up", "dn", "bk", "ft"];
+/// Face files are looked up with these extensions in order.
+const EXTENSIONS: [&str; 2] = ["tga", "bmp"];
+/// Suffixes of GoldSrc sky faces in the order of cubemap layers: +X, -X, +Y, -Y, +Z, -Z of the
+/// view space, which is the map's space turned Y up, so `ft` looks along the map's -Y.
+const SIDES: [&str; 6] = ["ft", "bk", "up", "dn", "lf", "rt"];
+/// Turns the faces as the game draws them into the layout of cubemap layers, whose texels go
+/// mirrored when looked at from inside.
+const ORIENTATIONS: [FaceTransform; 6] = [
+    FaceTransform::FlipHorizontal,
+    FaceTransform::FlipHorizontal,
+    FaceTransform::FlipVertical,
+    FaceTransform::FlipVertical,
+    FaceTransform::FlipHorizontal,
+    FaceTransform::FlipHorizontal,
+];
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum FaceTransform {
+    #[default]
+    None,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl FaceTransform {
+    fn apply(self, image: RgbaImage) -> RgbaImage {
+        match self {
+            Self::None => image,
+            Self::FlipHorizontal => imageops::flip_horizontal(&image),
+            Self::FlipVertical => imageops::flip_vertical(&image),
+            Self::Rotate90 => imageops::rotate90(&image),
+            Self::Rotate180 => imageops::rotate180(&image),
+            Self::Rotate270 => imageops::rotate270(&image),
+        }
+    }
+}
+
+impl FromStr for FaceTransform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "flipx" => Ok(Self::FlipHorizontal),
+            "flipy" => Ok(Self::FlipVertical),
+            "rot90" => Ok(Self::Rotate90),
+            "rot180" => Ok(Self::Rotate180),
+            "rot270" => Ok(Self::Rotate270),
+            _ => Err(format!(
+                "Unknown face transform `{}`, expected one of: none, flipx, flipy, rot90, rot180, rot270",
+                s
+            )),
+        }
+    }
+}
 
 pub struct Cubemap {
     dimension: u32,
@@ -15,12 +72,21 @@ pub struct Cubemap {
 
 impl Cubemap {
     pub fn read<S: AsRef<str>, P: AsRef<Path>>(name: S, path: P) -> ImageResult<Self> {
+        Self::read_with_transforms(name, path, [FaceTransform::None; 6])
+    }
+
+    /// Same as `read`, but applies a transform to each face after loading, for skies made
+    /// for other layouts. Transforms are in the order of `Cubemap::side_names`.
+    pub fn read_with_transforms<S: AsRef<str>, P: AsRef<Path>>(
+        name: S,
+        path: P,
+        transforms: [FaceTransform; 6],
+    ) -> ImageResult<Self> {
         let mut dimension = 0;
-        let sides: Vec<_> = SIDES
+        let sides: Vec<_> = Self::face_paths(name, path)
             .iter()
-            .map(|postfix| {
-                let file_name = format!("{}{}.{}", name.as_ref(), postfix, EXTENSION);
-                let file_path = path.as_ref().join(file_name);
+            .zip(ORIENTATIONS.iter().zip(transforms.iter()))
+            .map(|(file_path, (orientation, transform))| {
                 let image = image::open(file_path)?.to_rgba8();
                 if dimension == 0 {
                     dimension = image.width();
@@ -43,18 +109,48 @@ impl Cubemap {
                         ),
                     )));
                 }
-                Ok(image.into_raw())
+                Ok(transform.apply(orientation.apply(image)).into_raw())
             })
             .collect::<ImageResult<_>>()?;
-        let sides = <[Vec<u8>; 6]>::from_iter(sides.into_iter());
+        let sides = <[Vec<u8>; 6]>::from_iter(sides);
 
         Ok(Self { dimension, sides })
     }
 
+    /// Paths of the face files, in the order of `Cubemap::side_names`. Faces missing with all
+    /// of the extensions get the path with the first one.
+    pub fn face_paths<S: AsRef<str>, P: AsRef<Path>>(name: S, path: P) -> [PathBuf; 6] {
+        SIDES.map(|postfix| {
+            let with_extension = |extension: &str| {
+                let file_name = format!("{}{}.{}", name.as_ref(), postfix, extension);
+                path.as_ref().join(file_name)
+            };
+            EXTENSIONS
+                .iter()
+                .map(|&extension| with_extension(extension))
+                .find(|path| path.is_file())
+                .unwrap_or_else(|| with_extension(EXTENSIONS[0]))
+        })
+    }
+
+    /// Paths of the face files which don't exist, e.g. to tell why `read` failed.
+    pub fn missing_faces<S: AsRef<str>, P: AsRef<Path>>(name: S, path: P) -> Vec<PathBuf> {
+        Self::face_paths(name, path)
+            .iter()
+            .filter(|path| !path.is_file())
+            .cloned()
+            .collect()
+    }
+
+    pub const fn side_names() -> [&'static str; 6] {
+        SIDES
+    }
+
     pub const fn dimension(&self) -> u32 {
         self.dimension
     }
 
+    /// RGBA pixels of the faces in the order of `Cubemap::side_names`, laid out for cubemap layers.
     pub const fn sides(&self) -> &[Vec<u8>] {
         &self.sides
     }
@@ -0,0 +1,114 @@
+use crate::bsp::{
+    lumps::{
+        parse_edges, parse_faces, parse_models, parse_surfedges, parse_texinfos, parse_textures,
+        parse_vertices,
+    },
+    uv::face_uv,
+    LumpType, RawMap,
+};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+/// Faces of these textures aren't drawn by the game, so they're left out like in the renderer.
+const HIDDEN_TEXTURES: [&str; 2] = ["sky", "aaatrigger"];
+
+fn malformed(lump: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed {} lump", lump),
+    )
+}
+
+/// Writes the world geometry as a Wavefront OBJ to `obj`, and its materials, one per texture,
+/// to `mtl`. The OBJ references the materials as `mtl_name`, each of them expects the texture
+/// image to be in `<texture name>.png` next to it.
+///
+/// Positions are turned Y up and faces are triangulated as fans.
+pub fn write_obj<O: Write, M: Write>(
+    map: &RawMap,
+    mtl_name: &str,
+    mut obj: O,
+    mut mtl: M,
+) -> io::Result<()> {
+    let vertices =
+        parse_vertices(map.lump_data(LumpType::Vertices)).map_err(|_| malformed("vertices"))?;
+    let edges = parse_edges(map.lump_data(LumpType::Edges)).map_err(|_| malformed("edges"))?;
+    let surfedges =
+        parse_surfedges(map.lump_data(LumpType::Surfegdes)).map_err(|_| malformed("surfedges"))?;
+    let texinfos =
+        parse_texinfos(map.lump_data(LumpType::TexInfo)).map_err(|_| malformed("texinfo"))?;
+    let textures =
+        parse_textures(map.lump_data(LumpType::Textures)).map_err(|_| malformed("textures"))?;
+    let faces = parse_faces(map.lump_data(LumpType::Faces)).map_err(|_| malformed("faces"))?;
+    let models = parse_models(map.lump_data(LumpType::Models)).map_err(|_| malformed("models"))?;
+    let world = models.first().ok_or_else(|| malformed("models"))?;
+
+    // Faces grouped by texture, so each material is used once
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for face_id in world.face_id..world.face_id + world.face_num {
+        let face = faces.get(face_id).ok_or_else(|| malformed("faces"))?;
+        let texinfo = texinfos
+            .get(face.texinfo_id)
+            .ok_or_else(|| malformed("texinfo"))?;
+        let texture = textures
+            .get(texinfo.texture_id)
+            .ok_or_else(|| malformed("textures"))?;
+        if !HIDDEN_TEXTURES
+            .iter()
+            .any(|name| texture.name().eq_ignore_ascii_case(name))
+        {
+            groups.entry(texinfo.texture_id).or_default().push(face_id);
+        }
+    }
+
+    writeln!(obj, "mtllib {}", mtl_name)?;
+    writeln!(obj, "o world")?;
+    let mut written = 0;
+    for (&texture_id, face_ids) in &groups {
+        let texture = &textures[texture_id];
+        let (width, height) = (texture.main_width(), texture.main_height());
+        writeln!(mtl, "newmtl {}", texture.name())?;
+        writeln!(mtl, "Kd 1.0 1.0 1.0")?;
+        writeln!(mtl, "map_Kd {}.png", texture.name())?;
+
+        writeln!(obj, "usemtl {}", texture.name())?;
+        for &face_id in face_ids {
+            let face = &faces[face_id];
+            let texinfo = &texinfos[face.texinfo_id];
+            let positions = surfedges
+                .get(face.surfedge_id..face.surfedge_id + face.surfedge_num)
+                .ok_or_else(|| malformed("surfedges"))?
+                .iter()
+                .map(|&s| {
+                    let edge = edges
+                        .get(s.unsigned_abs() as usize)
+                        .ok_or_else(|| malformed("edges"))?;
+                    let i = if s < 0 { edge.1 } else { edge.0 };
+                    vertices
+                        .get(i as usize)
+                        .copied()
+                        .ok_or_else(|| malformed("vertices"))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            if positions.len() < 3 {
+                continue;
+            }
+
+            for &v in &positions {
+                let [u, v_down] = face_uv(v, texinfo, width, height);
+                writeln!(obj, "v {} {} {}", v.0, v.2, -v.1)?;
+                // Image rows go down, while OBJ's V goes up
+                writeln!(obj, "vt {} {}", u, -v_down)?;
+            }
+            let first = written + 1;
+            for i in 1..positions.len() - 1 {
+                let (b, c) = (first + i, first + i + 1);
+                writeln!(obj, "f {0}/{0} {1}/{1} {2}/{2}", first, b, c)?;
+            }
+            written += positions.len();
+        }
+    }
+    Ok(())
+}
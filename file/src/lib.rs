@@ -1,5 +1,13 @@
 pub mod bsp;
+pub mod container;
 pub mod cubemap;
+pub mod export;
+pub mod load;
 pub mod map;
+pub mod mdl;
 pub mod miptex;
+pub mod pak;
+pub mod rad;
+pub mod replacement;
+pub mod spr;
 pub mod wad;
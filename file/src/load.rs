@@ -0,0 +1,115 @@
+use crate::{
+    bsp::{lumps::parse_textures, BspError, LumpType, RawMap},
+    miptex::MipTexture,
+    wad::Archive,
+};
+use std::fmt;
+
+/// Texture of a map decoded to RGBA.
+pub struct DecodedTexture {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Pixels of all mip levels, from the full size one, see `MipTexture::pixels`.
+    pub mips: Vec<Vec<u8>>,
+}
+
+impl DecodedTexture {
+    fn decode(miptex: &MipTexture) -> Option<Self> {
+        let mips = (0..MipTexture::layers())
+            .map(|level| miptex.pixels(level))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            name: miptex.name().to_string(),
+            width: miptex.main_width(),
+            height: miptex.main_height(),
+            mips,
+        })
+    }
+}
+
+/// Map and wads parsed from bytes, with textures left in them undecoded.
+pub struct ParsedMap<'a> {
+    pub map: RawMap<'a>,
+    /// Parsed wads in the given order, `None` for ones which can't be parsed and were skipped.
+    pub wads: Vec<Option<Archive<'a>>>,
+}
+
+/// Parses the map and the wads, for users which read textures by themselves. Broken wads
+/// don't fail the map.
+pub fn parse_map<'a>(bsp: &'a [u8], wads: &[&'a [u8]]) -> Result<ParsedMap<'a>, BspError> {
+    let map = RawMap::parse(bsp)?;
+    let wads = wads.iter().map(|wad| Archive::parse(wad).ok()).collect();
+    Ok(ParsedMap { map, wads })
+}
+
+/// Map parsed from bytes with its textures, no matter where the files came from.
+pub struct LoadedMap<'a> {
+    pub map: RawMap<'a>,
+    /// Textures embedded into the map or found in the wads, at indices of the textures lump
+    /// which `TexInfo::texture_id` refers to. `None` for ones in `missing_textures`.
+    pub textures: Vec<Option<DecodedTexture>>,
+    /// Names of textures neither embedded nor found in any of the wads.
+    pub missing_textures: Vec<String>,
+    /// Parsed wads in the given order, `None` for ones which can't be parsed and were skipped.
+    pub wads: Vec<Option<Archive<'a>>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadMapError {
+    Bsp(BspError),
+    /// Textures lump which can't be parsed.
+    Textures,
+}
+
+impl fmt::Display for LoadMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bsp(e) => e.fmt(f),
+            Self::Textures => write!(f, "malformed textures lump"),
+        }
+    }
+}
+
+impl std::error::Error for LoadMapError {}
+
+/// Parses the map and decodes its textures, taking ones which aren't embedded from the first
+/// of `wads` having them. Names are matched ignoring case. Broken wads don't fail the map,
+/// their textures may still be found in the rest of them.
+pub fn load_map<'a>(bsp: &'a [u8], wads: &[&'a [u8]]) -> Result<LoadedMap<'a>, LoadMapError> {
+    let ParsedMap { map, wads } = parse_map(bsp, wads).map_err(LoadMapError::Bsp)?;
+    let miptexs =
+        parse_textures(map.lump_data(LumpType::Textures)).map_err(|_| LoadMapError::Textures)?;
+
+    let (mut textures, mut missing_textures) = (Vec::new(), Vec::new());
+    for miptex in &miptexs {
+        let name = miptex.name();
+        let texture = if miptex.is_empty() {
+            wads.iter()
+                .flatten()
+                .find_map(|wad| {
+                    wad.get_by_name(name.to_ascii_uppercase())
+                        .or_else(|| wad.get_by_name(name.to_ascii_lowercase()))
+                        .or_else(|| wad.get_by_name(name))
+                })
+                .and_then(|entry| MipTexture::parse(entry.data()).ok())
+                .and_then(|miptex| DecodedTexture::decode(&miptex))
+        } else {
+            DecodedTexture::decode(miptex)
+        };
+        if texture.is_none() {
+            missing_textures.push(name.to_string());
+        }
+        // Wads may name it in another case
+        textures.push(texture.map(|texture| DecodedTexture {
+            name: name.to_string(),
+            ..texture
+        }));
+    }
+    Ok(LoadedMap {
+        map,
+        textures,
+        missing_textures,
+        wads,
+    })
+}
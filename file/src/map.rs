@@ -17,7 +17,7 @@ pub struct Entity<'a> {
 }
 
 impl<'a> Entity<'a> {
-    fn entry(i: &str) -> ParseResult<(&str, &str)> {
+    fn entry(i: &str) -> ParseResult<'_, (&str, &str)> {
         separated_pair(
             delimited(character('"'), take_until("\""), character('"')),
             multispace0,
@@ -25,7 +25,7 @@ impl<'a> Entity<'a> {
         )(i)
     }
 
-    fn entries(i: &str) -> ParseResult<Vec<(&str, &str)>> {
+    fn entries(i: &str) -> ParseResult<'_, Vec<(&str, &str)>> {
         many0(delimited(multispace0, Self::entry, multispace0))(i)
     }
 
@@ -39,21 +39,32 @@ impl<'a> Entity<'a> {
         Ok((i, Self { properties }))
     }
 
+    /// All key/value pairs of the entity. Keys are unique, the last occurrence wins.
     pub fn properties(&self) -> &HashMap<&str, &str> {
         &self.properties
     }
+
+    /// Raw value of `key`, e.g. `origin` or `skyname`.
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.properties.get(key).copied()
+    }
+
+    /// Value of the `classname` key, which every valid entity has.
+    pub fn classname(&self) -> Option<&'a str> {
+        self.get("classname")
+    }
 }
 
 #[derive(Debug)]
 pub struct Entities<'a>(Vec<Entity<'a>>);
 
 impl<'a> Entities<'a> {
-    pub fn parse(i: &'a str) -> OnlyResult<Self> {
+    pub fn parse(i: &'a str) -> OnlyResult<'a, Self> {
         let (_, ents) = many0(delimited(multispace0, Entity::parse, multispace0))(i)?;
         Ok(Self(ents))
     }
 
-    pub fn entities(&self) -> &[Entity] {
+    pub fn entities(&self) -> &[Entity<'a>] {
         &self.0
     }
 }
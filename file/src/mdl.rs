@@ -0,0 +1,455 @@
+//! Studio models of Half-Life, version 10 `.mdl` files.
+
+use nom::{
+    bytes::complete::{tag, take, take_until},
+    combinator::{map, map_res, verify},
+    multi::count,
+    number::complete::{le_f32, le_i16, le_i32, le_u16, le_u32, le_u8},
+    sequence::tuple,
+};
+use std::convert::TryFrom;
+
+const MAGIC: &[u8] = b"IDST";
+const VERSION: u32 = 10;
+const NAME_LEN: usize = 64;
+const BONE_NAME_LEN: usize = 32;
+const COLOR_TABLE_SIZE: usize = 256 * 3;
+/// Offset of `numbones` in the header, past the name, sizes and bounding boxes.
+const BONES_OFFSET: usize = 140;
+/// Offset of `numtextures` in the header.
+const TEXTURES_OFFSET: usize = 180;
+/// Offset of `animindex` in a sequence description.
+const SEQUENCE_ANIM_OFFSET: usize = 124;
+/// Offset of `seqgroup` in a sequence description.
+const SEQUENCE_GROUP_OFFSET: usize = 156;
+/// Offset of `nummesh` in a model of a body part.
+const MODEL_MESHES_OFFSET: usize = 72;
+/// Sizes of the structures the header points to.
+const BONE_SIZE: usize = 112;
+const TEXTURE_SIZE: usize = 80;
+const BODY_PART_SIZE: usize = 76;
+const MESH_SIZE: usize = 20;
+/// Values of a bone: position, then rotation in radians around X, Y and Z.
+const BONE_VALUES: usize = 6;
+/// Last palette index of masked textures is a transparent cutout, as of masked miptextures.
+const MASK_INDEX: usize = 255;
+/// Flag of textures with cutouts.
+pub const MASKED_FLAG: u32 = 0x40;
+
+type Input<'a> = &'a [u8];
+type ParseResult<'a, O> = nom::IResult<Input<'a>, O, ParseError<'a>>;
+type OnlyResult<'a, O> = Result<O, nom::Err<ParseError<'a>>>;
+type ParseError<'a> = nom::error::VerboseError<Input<'a>>;
+
+fn take_cstr(i: &[u8], size: usize) -> ParseResult<'_, &str> {
+    let (i, cstr) = take(size)(i)?;
+    let (_, cstr) = map_res(take_until("\0"), std::str::from_utf8)(cstr)?;
+    Ok((i, cstr))
+}
+
+/// Part of the file starting at `offset`.
+fn at(file: &[u8], offset: usize) -> OnlyResult<'_, &[u8]> {
+    let (i, _) = take(offset)(file)?;
+    Ok(i)
+}
+
+fn usize_u32(i: &[u8]) -> ParseResult<'_, usize> {
+    map(le_u32, |x| x as usize)(i)
+}
+
+/// Number and offset of an array of structures.
+fn parse_array(i: &[u8]) -> ParseResult<'_, (usize, usize)> {
+    tuple((usize_u32, usize_u32))(i)
+}
+
+fn parse_vec3(i: &[u8]) -> ParseResult<'_, [f32; 3]> {
+    map(tuple((le_f32, le_f32, le_f32)), |(x, y, z)| [x, y, z])(i)
+}
+
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// Of the reference pose, relative to the parent.
+    pub position: [f32; 3],
+    /// Angles in radians around X, Y and Z of the reference pose, relative to the parent.
+    pub rotation: [f32; 3],
+}
+
+/// Skin texture, `pixels` are RGBA rows.
+pub struct Texture {
+    pub name: String,
+    pub flags: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Texture {
+    pub fn is_masked(&self) -> bool {
+        self.flags & MASKED_FLAG != 0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vertex {
+    /// In the space of `bone`, see `StudioModel::pose`.
+    pub position: [f32; 3],
+    pub bone: usize,
+    /// In the space of `normal_bone`.
+    pub normal: [f32; 3],
+    pub normal_bone: usize,
+    /// In texels of the mesh's texture.
+    pub tex_coords: [f32; 2],
+}
+
+/// Triangles of a texture.
+pub struct Mesh {
+    /// Index of the texture in the skin, see `StudioModel::skin_texture`.
+    pub skin_ref: usize,
+    /// Three per triangle.
+    pub vertices: Vec<Vertex>,
+}
+
+/// Transform of a bone to the model space, rows of a 3x4 matrix.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoneTransform(pub [[f32; 4]; 3]);
+
+impl BoneTransform {
+    const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ]);
+
+    /// Rotation by angles around X, Y and Z, applied in this order, then the translation.
+    fn new(position: [f32; 3], [x, y, z]: [f32; 3]) -> Self {
+        let (sr, cr) = x.sin_cos();
+        let (sp, cp) = y.sin_cos();
+        let (sy, cy) = z.sin_cos();
+        Self([
+            [
+                cp * cy,
+                sr * sp * cy - cr * sy,
+                cr * sp * cy + sr * sy,
+                position[0],
+            ],
+            [
+                cp * sy,
+                sr * sp * sy + cr * cy,
+                cr * sp * sy - sr * cy,
+                position[1],
+            ],
+            [-sp, sr * cp, cr * cp, position[2]],
+        ])
+    }
+
+    /// `self` applied after `inner`.
+    fn then(&self, inner: &Self) -> Self {
+        let (a, b) = (&self.0, &inner.0);
+        let mut out = [[0.0; 4]; 3];
+        for (row, out_row) in out.iter_mut().enumerate() {
+            for (column, value) in out_row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+            }
+            out_row[3] += a[row][3];
+        }
+        Self(out)
+    }
+
+    pub fn vector(&self, v: [f32; 3]) -> [f32; 3] {
+        let row = |r: &[f32; 4]| r[0] * v[0] + r[1] * v[1] + r[2] * v[2];
+        [row(&self.0[0]), row(&self.0[1]), row(&self.0[2])]
+    }
+
+    pub fn point(&self, p: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = self.vector(p);
+        [x + self.0[0][3], y + self.0[1][3], z + self.0[2][3]]
+    }
+}
+
+/// Model with bones in the reference pose, all textures, the first skin family and the first
+/// model of each body part. Controllers, attachments and hitboxes aren't read.
+pub struct StudioModel {
+    pub name: String,
+    pub bones: Vec<Bone>,
+    /// Empty if they're in a separate `*t.mdl` file, see `StudioModel::parse_textures`.
+    pub textures: Vec<Texture>,
+    /// Indices of `textures` by skin references of meshes.
+    pub skin: Vec<usize>,
+    pub meshes: Vec<Mesh>,
+}
+
+/// Header fields pointing to textures and skins.
+struct TextureHeader {
+    textures: (usize, usize),
+    skin_refs: usize,
+    skin_families: usize,
+    skin_offset: usize,
+}
+
+impl StudioModel {
+    pub fn parse(file: &[u8]) -> OnlyResult<'_, Self> {
+        let (_, name) = parse_header(file)?;
+        let (_, (bones, _, _, sequences, seq_groups)) = tuple((
+            parse_array,
+            parse_array,
+            parse_array,
+            parse_array,
+            parse_array,
+        ))(at(file, BONES_OFFSET)?)?;
+        let (textures, skin) = parse_textures(file)?;
+        let (_, (_, body_parts)) = tuple((
+            count(le_u32, 6), // textures, skins
+            parse_array,
+        ))(at(file, TEXTURES_OFFSET)?)?;
+
+        let mut bones = (0..bones.0)
+            .map(|i| parse_bone(at(file, bones.1 + i * BONE_SIZE)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        // The first sequence is found in the file itself only if it's in the first group
+        if sequences.0 > 0 && seq_groups.0 > 0 {
+            let (_, group) = le_u32(at(file, sequences.1 + SEQUENCE_GROUP_OFFSET)?)?;
+            if group == 0 {
+                let (_, anims) = usize_u32(at(file, sequences.1 + SEQUENCE_ANIM_OFFSET)?)?;
+                for (i, (bone, scales)) in bones.iter_mut().enumerate() {
+                    let anim_offset = anims + i * BONE_VALUES * 2;
+                    let (_, offsets) = count(le_u16, BONE_VALUES)(at(file, anim_offset)?)?;
+                    for (j, &offset) in offsets.iter().enumerate().filter(|(_, &o)| o != 0) {
+                        let value = first_frame_value(at(file, anim_offset + offset as usize)?)?;
+                        let value = f32::from(value) * scales[j];
+                        if j < 3 {
+                            bone.position[j] += value;
+                        } else {
+                            bone.rotation[j - 3] += value;
+                        }
+                    }
+                }
+            }
+        }
+        let bones = bones.into_iter().map(|(bone, _)| bone).collect();
+
+        let mut meshes = vec![];
+        for i in 0..body_parts.0 {
+            let (_, (_, models, _, model_offset)) =
+                tuple((take(NAME_LEN), le_u32, le_u32, usize_u32))(at(
+                    file,
+                    body_parts.1 + i * BODY_PART_SIZE,
+                )?)?;
+            if models > 0 {
+                meshes.extend(parse_model(file, model_offset)?);
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            bones,
+            textures,
+            skin,
+            meshes,
+        })
+    }
+
+    /// Textures and the first skin family of a model, e.g. of `scientistt.mdl` for models
+    /// which keep their textures in another file.
+    pub fn parse_textures(file: &[u8]) -> OnlyResult<'_, (Vec<Texture>, Vec<usize>)> {
+        parse_header(file)?;
+        parse_textures(file)
+    }
+
+    /// Index of the texture of a mesh.
+    pub fn skin_texture(&self, skin_ref: usize) -> Option<usize> {
+        self.skin
+            .get(skin_ref)
+            .copied()
+            .filter(|&i| i < self.textures.len())
+    }
+
+    /// Transforms of bones to the model space in the reference pose. Parents come before their
+    /// children in the file, broken references are taken as roots.
+    pub fn pose(&self) -> Vec<BoneTransform> {
+        let mut transforms: Vec<BoneTransform> = Vec::with_capacity(self.bones.len());
+        for bone in &self.bones {
+            let local = BoneTransform::new(bone.position, bone.rotation);
+            let transform = match bone.parent.and_then(|parent| transforms.get(parent)) {
+                Some(parent) => parent.then(&local),
+                None => local,
+            };
+            transforms.push(transform);
+        }
+        transforms
+    }
+
+    /// Position and normal of a vertex in the model space, `pose` is of `StudioModel::pose`.
+    pub fn posed(pose: &[BoneTransform], vertex: &Vertex) -> ([f32; 3], [f32; 3]) {
+        let transform = |bone: usize| pose.get(bone).unwrap_or(&BoneTransform::IDENTITY);
+        (
+            transform(vertex.bone).point(vertex.position),
+            transform(vertex.normal_bone).vector(vertex.normal),
+        )
+    }
+}
+
+/// Name of the model after checking the magic and version.
+fn parse_header(file: &[u8]) -> ParseResult<'_, &str> {
+    let (i, (_, _, name)) = tuple((
+        tag(MAGIC),
+        verify(le_u32, |&version| version == VERSION),
+        |i| take_cstr(i, NAME_LEN),
+    ))(file)?;
+    Ok((i, name))
+}
+
+fn parse_textures(file: &[u8]) -> OnlyResult<'_, (Vec<Texture>, Vec<usize>)> {
+    let (_, header) = map(
+        tuple((parse_array, le_u32, usize_u32, usize_u32, usize_u32)),
+        |(textures, _, skin_refs, skin_families, skin_offset)| TextureHeader {
+            textures,
+            skin_refs,
+            skin_families,
+            skin_offset,
+        },
+    )(at(file, TEXTURES_OFFSET)?)?;
+
+    let textures = (0..header.textures.0)
+        .map(|i| parse_texture(file, header.textures.1 + i * TEXTURE_SIZE))
+        .collect::<Result<_, _>>()?;
+    let skin = if header.skin_families > 0 {
+        let (_, skin) =
+            count(map(le_u16, usize::from), header.skin_refs)(at(file, header.skin_offset)?)?;
+        skin
+    } else {
+        (0..header.textures.0).collect()
+    };
+    Ok((textures, skin))
+}
+
+fn parse_texture(file: &[u8], offset: usize) -> OnlyResult<'_, Texture> {
+    let (_, (name, flags, width, height, pixels_offset)) = tuple((
+        |i| take_cstr(i, NAME_LEN),
+        le_u32,
+        le_u32,
+        le_u32,
+        usize_u32,
+    ))(at(file, offset)?)?;
+    let size = width as usize * height as usize;
+    let (_, (indices, color_table)) =
+        tuple((take(size), take(COLOR_TABLE_SIZE)))(at(file, pixels_offset)?)?;
+    let masked = flags & MASKED_FLAG != 0;
+    let pixels = indices
+        .iter()
+        .map(|&i| i as usize)
+        .flat_map(|i| {
+            if masked && i == MASK_INDEX {
+                return [0; 4];
+            }
+            [
+                color_table[3 * i],
+                color_table[3 * i + 1],
+                color_table[3 * i + 2],
+                255,
+            ]
+        })
+        .collect();
+    Ok(Texture {
+        name: name.to_string(),
+        flags,
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Bone with its default values and the scales of animation values.
+fn parse_bone(i: &[u8]) -> OnlyResult<'_, (Bone, [f32; BONE_VALUES])> {
+    let (_, (name, parent, _, _, values, scales)) = tuple((
+        |i| take_cstr(i, BONE_NAME_LEN),
+        le_i32,
+        le_u32,
+        count(le_i32, BONE_VALUES),
+        count(le_f32, BONE_VALUES),
+        count(le_f32, BONE_VALUES),
+    ))(i)?;
+    let mut scale = [0.0; BONE_VALUES];
+    scale.copy_from_slice(&scales);
+    Ok((
+        Bone {
+            name: name.to_string(),
+            parent: usize::try_from(parent).ok(),
+            position: [values[0], values[1], values[2]],
+            rotation: [values[3], values[4], values[5]],
+        },
+        scale,
+    ))
+}
+
+/// Value of the first frame of run-length encoded animation values: they start with counts of
+/// the stored and the total frames of a run, followed by the stored values.
+fn first_frame_value(i: &[u8]) -> OnlyResult<'_, i16> {
+    let (values, (valid, _)) = tuple((le_u8, le_u8))(i)?;
+    // Runs without stored values repeat the one in place of their counts, as the game does
+    let (_, value) = le_i16(if valid == 0 { i } else { values })?;
+    Ok(value)
+}
+
+/// Meshes of a model of a body part with their triangle commands unrolled.
+fn parse_model(file: &[u8], offset: usize) -> OnlyResult<'_, Vec<Mesh>> {
+    let (_, (meshes, (verts, vert_info, vert_offset), (norms, norm_info, norm_offset))) =
+        tuple((
+            parse_array,
+            tuple((usize_u32, usize_u32, usize_u32)),
+            tuple((usize_u32, usize_u32, usize_u32)),
+        ))(at(file, offset + MODEL_MESHES_OFFSET)?)?;
+    let (_, vert_bones) = take(verts)(at(file, vert_info)?)?;
+    let (_, positions) = count(parse_vec3, verts)(at(file, vert_offset)?)?;
+    let (_, norm_bones) = take(norms)(at(file, norm_info)?)?;
+    let (_, normals) = count(parse_vec3, norms)(at(file, norm_offset)?)?;
+
+    let vertex = |[vert, norm, s, t]: [i16; 4]| {
+        let (vert, norm) = (vert as usize, norm as usize);
+        Some(Vertex {
+            position: *positions.get(vert)?,
+            bone: *vert_bones.get(vert)? as usize,
+            normal: *normals.get(norm)?,
+            normal_bone: *norm_bones.get(norm)? as usize,
+            tex_coords: [f32::from(s), f32::from(t)],
+        })
+    };
+    (0..meshes.0)
+        .map(|i| {
+            let (_, (_, tri_offset, skin_ref)) =
+                tuple((le_u32, usize_u32, usize_u32))(at(file, meshes.1 + i * MESH_SIZE)?)?;
+            let mut vertices = vec![];
+            let mut i = at(file, tri_offset)?;
+            loop {
+                let (rest, length) = le_i16(i)?;
+                if length == 0 {
+                    break;
+                }
+                let (rest, commands) = count(
+                    map(count(le_i16, 4), |c| [c[0], c[1], c[2], c[3]]),
+                    length.unsigned_abs() as usize,
+                )(rest)?;
+                i = rest;
+                let commands: Option<Vec<_>> = commands.into_iter().map(vertex).collect();
+                // Commands referencing missing vertices are dropped whole
+                let commands = match commands {
+                    Some(commands) => commands,
+                    None => continue,
+                };
+                for j in 2..commands.len() {
+                    let triangle = if length < 0 {
+                        // Fan around the first vertex
+                        [0, j - 1, j]
+                    } else if j % 2 == 0 {
+                        [j - 2, j - 1, j]
+                    } else {
+                        // Every other triangle of a strip is reversed to keep the winding
+                        [j - 1, j - 2, j]
+                    };
+                    vertices.extend(triangle.iter().map(|&k| commands[k]));
+                }
+            }
+            Ok(Mesh { skin_ref, vertices })
+        })
+        .collect()
+}
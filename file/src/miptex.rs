@@ -5,17 +5,22 @@ use nom::{
     number::complete::le_u32,
     sequence::tuple,
 };
-use std::iter::once;
+use std::collections::HashMap;
 
 const MIP_NUM: usize = 4;
 const NAME_LEN: usize = 16;
 const COLOR_TABLE_SIZE: usize = 256 * 3;
+/// Animated textures have at most 10 frames, `+0` to `+9` and `+a` to `+j`.
+const ANIMATION_FRAMES: usize = 10;
+/// Textures named like `{fence` are masked: their last palette index is a transparent cutout.
+pub const MASKED_PREFIX: char = '{';
+const MASK_INDEX: usize = 255;
 
 type Input<'a> = &'a [u8];
 type ParseResult<'a, O> = nom::IResult<Input<'a>, O, ParseError<'a>>;
 type ParseError<'a> = nom::error::VerboseError<Input<'a>>;
 
-fn take_cstr(i: &[u8], size: usize) -> ParseResult<&str> {
+fn take_cstr(i: &[u8], size: usize) -> ParseResult<'_, &str> {
     let (i, cstr) = take(size)(i)?;
     let (_, cstr) = map_res(take_until("\0"), std::str::from_utf8)(cstr)?;
     Ok((i, cstr))
@@ -38,7 +43,7 @@ impl<'a> MipTexture<'a> {
             count(map(le_u32, |x| x as usize), MIP_NUM),
         ))(file)?;
 
-        let (color_indices, color_table) = if offsets.iter().any(|&x| x == 0) {
+        let (color_indices, color_table) = if offsets.contains(&0) {
             (None, None)
         } else {
             let mut color_indices: [&[u8]; MIP_NUM] = [&[]; MIP_NUM];
@@ -87,20 +92,115 @@ impl<'a> MipTexture<'a> {
         self.color_table.is_none() || self.color_indices.is_none()
     }
 
+    pub fn is_masked(&self) -> bool {
+        self.name.starts_with(MASKED_PREFIX)
+    }
+
+    /// RGBA pixels of the mip level. Cutout texels of masked textures are transparent black, so
+    /// filtering doesn't bleed the key color into their edges.
     pub fn pixels(&self, mip_level: usize) -> Option<Vec<u8>> {
         let color_table = self.color_table?;
+        let masked = self.is_masked();
         Some(
             self.color_indices?[mip_level]
                 .iter()
                 .map(|&i| i as usize)
                 .flat_map(|i| {
-                    let r = color_table[3 * i];
-                    let g = color_table[3 * i + 1];
-                    let b = color_table[3 * i + 2];
-                    let a = if r < 30 && g < 30 && b > 125 { 0 } else { 255 };
-                    once(r).chain(once(g)).chain(once(b)).chain(once(a))
+                    if masked && i == MASK_INDEX {
+                        return [0; 4];
+                    }
+                    [
+                        color_table[3 * i],
+                        color_table[3 * i + 1],
+                        color_table[3 * i + 2],
+                        255,
+                    ]
                 })
                 .collect(),
         )
     }
+
+    /// RGBA pixels of the mip level for a decal of decals.wad: all texels have the color of the
+    /// last palette entry and their indices are the opacity, so 0 is fully transparent.
+    pub fn decal_pixels(&self, mip_level: usize) -> Option<Vec<u8>> {
+        let color_table = self.color_table?;
+        let color = &color_table[3 * MASK_INDEX..3 * MASK_INDEX + 3];
+        Some(
+            self.color_indices?[mip_level]
+                .iter()
+                .flat_map(|&i| [color[0], color[1], color[2], i])
+                .collect(),
+        )
+    }
+}
+
+/// Frame of an animated texture named like `+0lava`, or `+alava` for the alternate sequence
+/// which the game shows when the entity is toggled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AnimationFrame<'a> {
+    pub base: &'a str,
+    pub alternate: bool,
+    pub index: usize,
+}
+
+impl<'a> AnimationFrame<'a> {
+    pub fn parse(name: &'a str) -> Option<Self> {
+        let mut chars = name.chars();
+        if chars.next()? != '+' {
+            return None;
+        }
+        let (alternate, index) = match chars.next()?.to_ascii_lowercase() {
+            c @ '0'..='9' => (false, c as usize - '0' as usize),
+            c @ 'a'..='j' => (true, c as usize - 'a' as usize),
+            _ => return None,
+        };
+        Some(Self {
+            base: &name[2..],
+            alternate,
+            index,
+        })
+    }
+
+    /// Names of all frames the sequence may have, e.g. `+0lava` to `+9lava`.
+    pub fn sequence_names(base: &str, alternate: bool) -> impl Iterator<Item = String> + '_ {
+        let first = if alternate { b'a' } else { b'0' };
+        (0..ANIMATION_FRAMES as u8).map(move |i| format!("+{}{}", (first + i) as char, base))
+    }
+}
+
+/// Frame sequences of an animated texture, both are empty or start with frame 0.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AnimationChain {
+    pub frames: Vec<String>,
+    pub alternate: Vec<String>,
+}
+
+/// Groups names of animated textures to chains by their lowercase base names. Sequences end
+/// at the first missing frame, as in the game.
+pub fn animation_chains<'a, I: IntoIterator<Item = &'a str>>(
+    names: I,
+) -> HashMap<String, AnimationChain> {
+    let mut sequences: HashMap<(String, bool), [Option<&str>; ANIMATION_FRAMES]> = HashMap::new();
+    for name in names {
+        if let Some(frame) = AnimationFrame::parse(name) {
+            let key = (frame.base.to_ascii_lowercase(), frame.alternate);
+            sequences.entry(key).or_default()[frame.index] = Some(name);
+        }
+    }
+
+    let mut chains: HashMap<String, AnimationChain> = HashMap::new();
+    for ((base, alternate), frames) in sequences {
+        let frames = frames
+            .iter()
+            .map_while(|frame| frame.map(str::to_string))
+            .collect();
+        let chain = chains.entry(base).or_default();
+        if alternate {
+            chain.alternate = frames;
+        } else {
+            chain.frames = frames;
+        }
+    }
+    chains.retain(|_, chain| !chain.frames.is_empty() || !chain.alternate.is_empty());
+    chains
 }
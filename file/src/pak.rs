@@ -0,0 +1,75 @@
+use nom::{
+    bytes::complete::{tag, take, take_until},
+    combinator::{map, map_res},
+    multi::count,
+    number::complete::le_u32,
+    sequence::tuple,
+};
+
+const PAK_MAGIC: &[u8] = b"PACK";
+const NAME_LEN: usize = 56;
+const DIR_ENTRY_SIZE: usize = 64;
+
+type Input<'a> = &'a [u8];
+type ParseError<'a> = nom::error::VerboseError<Input<'a>>;
+type ParseResult<'a, O> = nom::IResult<Input<'a>, O, ParseError<'a>>;
+type OnlyResult<'a, O> = Result<O, nom::Err<ParseError<'a>>>;
+
+fn take_cstr(i: &[u8], size: usize) -> ParseResult<'_, &str> {
+    let (i, cstr) = take(size)(i)?;
+    let (_, cstr) = map_res(take_until("\0"), std::str::from_utf8)(cstr)?;
+    Ok((i, cstr))
+}
+
+fn parse_entry<'a>(i: &'a [u8], file: &'a [u8]) -> ParseResult<'a, (&'a str, &'a [u8])> {
+    let (i, (name, offset, size)) = tuple((
+        |i| take_cstr(i, NAME_LEN),
+        map(le_u32, |x| x as usize),
+        map(le_u32, |x| x as usize),
+    ))(i)?;
+
+    let (data_i, _) = take(offset)(file)?;
+    let (_, data) = take(size)(data_i)?;
+    Ok((i, (name, data)))
+}
+
+/// Normalizes paths of entries, which are matched ignoring case and kind of slashes.
+fn normalize(name: &str) -> String {
+    name.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_ascii_lowercase()
+}
+
+/// Quake's PAK archive, mods of the game ship their content as `pak0.pak`.
+pub struct Pak<'a> {
+    entries: Vec<(&'a str, &'a [u8])>,
+}
+
+impl<'a> Pak<'a> {
+    pub fn parse(file: &'a [u8]) -> OnlyResult<'a, Self> {
+        let (_, (_, dir_offset, dir_size)) = tuple((
+            tag(PAK_MAGIC),
+            map(le_u32, |x| x as usize),
+            map(le_u32, |x| x as usize),
+        ))(file)?;
+
+        let (dir_i, _) = take(dir_offset)(file)?;
+        let (_, entries) = count(|i| parse_entry(i, file), dir_size / DIR_ENTRY_SIZE)(dir_i)?;
+        Ok(Self { entries })
+    }
+
+    /// Names and data of the entries in the order of the directory.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.entries.iter().copied()
+    }
+
+    /// Data of the entry at the path like `maps/foo.bsp`, matched ignoring case and kind of
+    /// slashes.
+    pub fn read<S: AsRef<str>>(&self, name: S) -> Option<&'a [u8]> {
+        let name = normalize(name.as_ref());
+        self.entries
+            .iter()
+            .find(|(entry, _)| normalize(entry) == name)
+            .map(|&(_, data)| data)
+    }
+}
@@ -0,0 +1,32 @@
+const COMMENT: &str = "//";
+
+/// Single entry of a `lights.rad` file: a texture name followed by its
+/// emitted color and intensity, e.g. `+0~LIGHT3A 255 255 255 1000`.
+#[derive(Debug)]
+pub struct RadLight<'a> {
+    pub name: &'a str,
+    pub color: (f32, f32, f32),
+    pub intensity: f32,
+}
+
+/// Parses `lights.rad`, skipping blank, commented and malformed lines.
+/// Intensity is optional and defaults to 1.
+pub fn parse_lights_rad(i: &str) -> Vec<RadLight<'_>> {
+    i.lines()
+        .map(|line| line.split(COMMENT).next().unwrap_or_default())
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let mut numbers = parts.map(|x| x.parse::<f32>());
+            let r = numbers.next()?.ok()?;
+            let g = numbers.next()?.ok()?;
+            let b = numbers.next()?.ok()?;
+            let intensity = numbers.next().unwrap_or(Ok(1.0)).ok()?;
+            Some(RadLight {
+                name,
+                color: (r, g, b),
+                intensity,
+            })
+        })
+        .collect()
+}
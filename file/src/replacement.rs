@@ -0,0 +1,83 @@
+use image::ImageResult;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of replacement images in the order of preference.
+const EXTENSIONS: [&str; 3] = ["png", "tga", "bmp"];
+
+/// RGBA image with rows going from the top.
+pub struct RgbaTexture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Directory of images replacing miptextures of the same names, e.g. of an HD texture pack.
+/// Names are matched ignoring case.
+pub struct ReplacementDir {
+    files: HashMap<String, PathBuf>,
+}
+
+impl ReplacementDir {
+    pub fn scan<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut files: HashMap<String, (usize, PathBuf)> = HashMap::new();
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            let preference = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .and_then(|extension| {
+                    EXTENSIONS
+                        .iter()
+                        .position(|x| x.eq_ignore_ascii_case(extension))
+                });
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(str::to_ascii_lowercase);
+            if let (Some(preference), Some(name)) = (preference, name) {
+                let file = files.entry(name).or_insert((preference, path.clone()));
+                if preference < file.0 {
+                    *file = (preference, path);
+                }
+            }
+        }
+        Ok(Self {
+            files: files
+                .into_iter()
+                .map(|(name, (_, path))| (name, path))
+                .collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Path of the image replacing the texture, if there's one.
+    pub fn find(&self, name: &str) -> Option<&Path> {
+        self.files
+            .get(&name.to_ascii_lowercase())
+            .map(PathBuf::as_path)
+    }
+
+    /// Reads the image replacing the texture, `None` if there's none.
+    pub fn read(&self, name: &str) -> Option<ImageResult<RgbaTexture>> {
+        let path = self.find(name)?;
+        Some(image::open(path).map(|image| {
+            let image = image.to_rgba8();
+            RgbaTexture {
+                width: image.width(),
+                height: image.height(),
+                pixels: image.into_raw(),
+            }
+        }))
+    }
+}
@@ -0,0 +1,152 @@
+//! Sprites of Half-Life, version 2 `.spr` files.
+
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::{map, verify},
+    multi::count,
+    number::complete::{le_f32, le_i32, le_u16, le_u32},
+    sequence::tuple,
+};
+
+const MAGIC: &[u8] = b"IDSP";
+const VERSION: u32 = 2;
+/// Last palette index is transparent in alpha tested sprites and the color of index-alpha ones.
+const LAST_INDEX: usize = 255;
+
+type Input<'a> = &'a [u8];
+type ParseResult<'a, O> = nom::IResult<Input<'a>, O, ParseError<'a>>;
+type OnlyResult<'a, O> = Result<O, nom::Err<ParseError<'a>>>;
+type ParseError<'a> = nom::error::VerboseError<Input<'a>>;
+
+/// How the sprite is turned to the viewer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Orientation {
+    ParallelUpright,
+    FacingUpright,
+    Parallel,
+    Oriented,
+    ParallelOriented,
+}
+
+/// How texels are blended with what's behind the sprite.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextureFormat {
+    Normal,
+    Additive,
+    /// Texels have the color of the last palette entry, their indices are the opacity.
+    IndexAlpha,
+    /// Texels of the last palette index are transparent.
+    AlphaTest,
+}
+
+/// Frame with RGBA `pixels`, `origin` is the offset of its top left corner from the sprite's
+/// origin, with Y going up.
+pub struct Frame {
+    pub origin: [i32; 2],
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+pub struct Sprite {
+    pub orientation: Orientation,
+    pub format: TextureFormat,
+    /// Size of the largest frame.
+    pub width: u32,
+    pub height: u32,
+    /// Frames of groups follow each other, intervals of the groups aren't kept.
+    pub frames: Vec<Frame>,
+}
+
+impl Sprite {
+    pub fn parse(file: &[u8]) -> OnlyResult<'_, Self> {
+        let (i, (_, _, orientation, format, _, width, height, frames, _, _)) = tuple((
+            tag(MAGIC),
+            verify(le_u32, |&version| version == VERSION),
+            map(le_u32, |x| match x {
+                0 => Orientation::ParallelUpright,
+                1 => Orientation::FacingUpright,
+                3 => Orientation::Oriented,
+                4 => Orientation::ParallelOriented,
+                _ => Orientation::Parallel,
+            }),
+            map(le_u32, |x| match x {
+                1 => TextureFormat::Additive,
+                2 => TextureFormat::IndexAlpha,
+                3 => TextureFormat::AlphaTest,
+                _ => TextureFormat::Normal,
+            }),
+            le_f32,
+            le_u32,
+            le_u32,
+            map(le_u32, |x| x as usize),
+            le_f32,
+            le_u32,
+        ))(file)?;
+        let (i, colors) = le_u16(i)?;
+        let (mut i, palette) = take(colors as usize * 3)(i)?;
+
+        let mut parsed = Vec::with_capacity(frames);
+        for _ in 0..frames {
+            let (rest, group) = le_u32(i)?;
+            i = rest;
+            let group_frames = if group == 0 {
+                1
+            } else {
+                let (rest, group_frames) = map(le_u32, |x| x as usize)(i)?;
+                let (rest, _) = count(le_f32, group_frames)(rest)?;
+                i = rest;
+                group_frames
+            };
+            for _ in 0..group_frames {
+                let (rest, frame) = parse_frame(i, palette, format)?;
+                i = rest;
+                parsed.push(frame);
+            }
+        }
+
+        Ok(Self {
+            orientation,
+            format,
+            width,
+            height,
+            frames: parsed,
+        })
+    }
+}
+
+fn parse_frame<'a>(i: &'a [u8], palette: &[u8], format: TextureFormat) -> ParseResult<'a, Frame> {
+    let (i, (x, y, width, height)) = tuple((le_i32, le_i32, le_u32, le_u32))(i)?;
+    let (i, indices) = take(width as usize * height as usize)(i)?;
+    let color = |index: usize| {
+        palette
+            .get(3 * index..3 * index + 3)
+            .map_or([0; 3], |c| [c[0], c[1], c[2]])
+    };
+    let pixels = indices
+        .iter()
+        .flat_map(|&index| {
+            let index = index as usize;
+            match format {
+                TextureFormat::IndexAlpha => {
+                    let [r, g, b] = color(LAST_INDEX);
+                    [r, g, b, index as u8]
+                }
+                TextureFormat::AlphaTest if index == LAST_INDEX => [0; 4],
+                _ => {
+                    let [r, g, b] = color(index);
+                    [r, g, b, 255]
+                }
+            }
+        })
+        .collect();
+    Ok((
+        i,
+        Frame {
+            origin: [x, y],
+            width,
+            height,
+            pixels,
+        },
+    ))
+}
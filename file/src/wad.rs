@@ -5,31 +5,51 @@ use nom::{
     number::complete::{le_u16, le_u32, le_u8},
     sequence::tuple,
 };
-use std::{collections::HashMap, iter::Iterator};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    iter::Iterator,
+    path::Path,
+};
 
 const WAD3_MAGIC: &[u8] = b"WAD3";
 const NAME_LEN: usize = 16;
+const HEADER_SIZE: usize = 12;
+const DIR_ENTRY_SIZE: usize = 32;
 
 type Input<'a> = &'a [u8];
 type ParseError<'a> = nom::error::VerboseError<Input<'a>>;
 type ParseResult<'a, O> = nom::IResult<Input<'a>, O, ParseError<'a>>;
 type OnlyResult<'a, O> = Result<O, nom::Err<ParseError<'a>>>;
 
-fn take_cstr(i: &[u8], size: usize) -> ParseResult<&str> {
+fn take_cstr(i: &[u8], size: usize) -> ParseResult<'_, &str> {
     let (i, cstr) = take(size)(i)?;
     let (_, cstr) = map_res(take_until("\0"), std::str::from_utf8)(cstr)?;
     Ok((i, cstr))
 }
 
-pub struct Entry<'a> {
+fn parse_header(i: &[u8]) -> ParseResult<'_, (usize, usize)> {
+    let (i, (_, dir_num, dir_offset)) = tuple((
+        tag(WAD3_MAGIC),
+        map(le_u32, |x| x as usize),
+        map(le_u32, |x| x as usize),
+    ))(i)?;
+    Ok((i, (dir_num, dir_offset)))
+}
+
+/// Location of an entry's data inside the wad file.
+#[derive(Debug, Copy, Clone)]
+pub struct EntryInfo {
+    offset: usize,
+    size: usize,
     etype: u8,
-    data: &'a [u8],
 }
 
-impl<'a> Entry<'a> {
-    fn parse(i: &'a [u8], file: &'a [u8]) -> ParseResult<'a, (&'a str, Self)> {
+impl EntryInfo {
+    fn parse(i: &[u8]) -> ParseResult<'_, (&str, Self)> {
         // There's no compression, because I don't find any wad using compression (seems it's LZSS)
-        let (i, (offset, disk_size, _, etype, _, _)) = tuple((
+        let (i, (offset, size, _, etype, _, _)) = tuple((
             map(le_u32, |x| x as usize),
             map(le_u32, |x| x as usize),
             le_u32,
@@ -39,10 +59,54 @@ impl<'a> Entry<'a> {
         ))(i)?;
         let (i, name) = take_cstr(i, NAME_LEN)?;
 
-        let (data_i, _) = take(offset)(file)?;
-        let (_, data) = take(disk_size)(data_i)?;
+        Ok((
+            i,
+            (
+                name,
+                Self {
+                    offset,
+                    size,
+                    etype,
+                },
+            ),
+        ))
+    }
+
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    pub const fn etype(&self) -> u8 {
+        self.etype
+    }
+}
+
+pub struct Entry<'a> {
+    etype: u8,
+    data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    fn parse(i: &'a [u8], file: &'a [u8]) -> ParseResult<'a, (&'a str, Self)> {
+        let (i, (name, info)) = EntryInfo::parse(i)?;
 
-        Ok((i, (name, Self { etype, data })))
+        let (data_i, _) = take(info.offset)(file)?;
+        let (_, data) = take(info.size)(data_i)?;
+
+        Ok((
+            i,
+            (
+                name,
+                Self {
+                    etype: info.etype,
+                    data,
+                },
+            ),
+        ))
     }
 
     pub const fn etype(&self) -> u8 {
@@ -59,12 +123,8 @@ pub struct Archive<'a> {
 }
 
 impl<'a> Archive<'a> {
-    pub fn parse(file: &'a [u8]) -> OnlyResult<Self> {
-        let (_, (_, dir_num, dir_offset)) = tuple((
-            tag(WAD3_MAGIC),
-            map(le_u32, |x| x as usize),
-            map(le_u32, |x| x as usize),
-        ))(file)?;
+    pub fn parse(file: &'a [u8]) -> OnlyResult<'a, Self> {
+        let (_, (dir_num, dir_offset)) = parse_header(file)?;
 
         let (dir_i, _) = take(dir_offset)(file)?;
         let (_, entries) = map(count(|i| Entry::parse(i, file), dir_num), |x| {
@@ -73,11 +133,123 @@ impl<'a> Archive<'a> {
         Ok(Self { entries })
     }
 
-    pub fn entries(&self) -> impl Iterator<Item = (&str, &Entry)> {
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Entry<'_>)> {
         self.entries.iter().map(|(&name, entry)| (name, entry))
     }
 
-    pub fn get_by_name<S: AsRef<str>>(&self, name: S) -> Option<&Entry> {
+    pub fn get_by_name<S: AsRef<str>>(&self, name: S) -> Option<&Entry<'_>> {
         self.entries.get(name.as_ref())
     }
 }
+
+/// Wad archive which keeps only the directory in memory and reads entries
+/// from the open file on demand. Suits huge wads which are mostly unused.
+pub struct StreamedArchive {
+    file: File,
+    entries: HashMap<String, EntryInfo>,
+}
+
+impl StreamedArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        let (_, (dir_num, dir_offset)) = parse_header(&header).map_err(invalid_data)?;
+
+        // The header may claim more entries than the file has, they aren't allocated then
+        let file_size = file.metadata()?.len();
+        let directory_size = dir_num
+            .checked_mul(DIR_ENTRY_SIZE)
+            .filter(|&size| {
+                (dir_offset as u64)
+                    .checked_add(size as u64)
+                    .is_some_and(|end| end <= file_size)
+            })
+            .ok_or_else(malformed)?;
+        let mut directory = vec![0; directory_size];
+        file.seek(SeekFrom::Start(dir_offset as u64))?;
+        file.read_exact(&mut directory)?;
+        let (_, entries) = count(EntryInfo::parse, dir_num)(&directory).map_err(invalid_data)?;
+        let entries = entries
+            .into_iter()
+            .map(|(name, info)| (name.to_string(), info))
+            .collect();
+
+        Ok(Self { file, entries })
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &EntryInfo)> {
+        self.entries
+            .iter()
+            .map(|(name, info)| (name.as_str(), info))
+    }
+
+    pub fn get_by_name<S: AsRef<str>>(&self, name: S) -> Option<&EntryInfo> {
+        self.entries.get(name.as_ref())
+    }
+
+    /// Reads data of the named entry, seeking directly to its offset.
+    pub fn read<S: AsRef<str>>(&self, name: S) -> io::Result<Option<Vec<u8>>> {
+        let info = match self.get_by_name(name) {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+        let mut file = &self.file;
+        let mut data = vec![0; info.size];
+        file.seek(SeekFrom::Start(info.offset as u64))?;
+        file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+}
+
+/// Precedence of entries in several wads: the first wad containing a name provides it, as the
+/// game searches wads in the order of worldspawn's `wad` key. Names are matched ignoring case.
+#[derive(Debug, Default)]
+pub struct WadOrder {
+    providers: HashMap<String, usize>,
+    wads_num: usize,
+}
+
+impl WadOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds entry names of the next wad. Returns the ones shadowed by earlier wads, along with
+    /// indices of the wads providing them.
+    pub fn push<'n, I: IntoIterator<Item = &'n str>>(&mut self, names: I) -> Vec<(String, usize)> {
+        let wad = self.wads_num;
+        self.wads_num += 1;
+        let mut shadowed = names
+            .into_iter()
+            .filter_map(|name| {
+                let provider = *self
+                    .providers
+                    .entry(name.to_ascii_lowercase())
+                    .or_insert(wad);
+                Some((name.to_string(), provider)).filter(|_| provider != wad)
+            })
+            .collect::<Vec<_>>();
+        shadowed.sort();
+        shadowed
+    }
+
+    /// Index of the wad providing the entry, in the order of `push` calls.
+    pub fn provider<S: AsRef<str>>(&self, name: S) -> Option<usize> {
+        self.providers
+            .get(&name.as_ref().to_ascii_lowercase())
+            .copied()
+    }
+}
+
+fn malformed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "malformed wad header or directory",
+    )
+}
+
+fn invalid_data(_: nom::Err<ParseError>) -> io::Error {
+    malformed()
+}
@@ -0,0 +1,35 @@
+use file::miptex::{animation_chains, AnimationFrame};
+
+#[test]
+fn parse_frame_names() {
+    let frame = AnimationFrame::parse("+3LAVA").unwrap();
+    assert_eq!(
+        (frame.base, frame.alternate, frame.index),
+        ("LAVA", false, 3)
+    );
+    let frame = AnimationFrame::parse("+bbutton").unwrap();
+    assert_eq!(
+        (frame.base, frame.alternate, frame.index),
+        ("button", true, 1)
+    );
+    assert!(AnimationFrame::parse("lava").is_none());
+    assert!(AnimationFrame::parse("+zlava").is_none());
+    assert!(AnimationFrame::parse("+").is_none());
+}
+
+#[test]
+fn group_names_into_chains() {
+    let names = [
+        "+1lava", "+0LAVA", "+2lava", "+4lava", "wall", "+abutton", "+0button", "+bbutton", "+1fan",
+    ];
+    let chains = animation_chains(names.iter().copied());
+
+    // `+4lava` is cut off by the missing `+3lava`
+    assert_eq!(chains["lava"].frames, vec!["+0LAVA", "+1lava", "+2lava"]);
+    assert!(chains["lava"].alternate.is_empty());
+    assert_eq!(chains["button"].frames, vec!["+0button"]);
+    assert_eq!(chains["button"].alternate, vec!["+abutton", "+bbutton"]);
+    // Sequences without the first frame aren't animated
+    assert!(!chains.contains_key("fan"));
+    assert_eq!(chains.len(), 2);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{bsp_with, WORLDSPAWN};
+use file::bsp::{BspError, LumpType, RawMap};
+
+#[test]
+fn reject_other_versions() {
+    let mut quake = 29u32.to_le_bytes().to_vec();
+    quake.extend(vec![0; 15 * 8]);
+    assert_eq!(
+        RawMap::parse(&quake).err(),
+        Some(BspError::UnsupportedVersion(29))
+    );
+    assert!(BspError::UnsupportedVersion(29).to_string().contains("29"));
+
+    assert_eq!(RawMap::parse(b"VB").err(), Some(BspError::Malformed));
+    assert_eq!(
+        RawMap::parse(&30u32.to_le_bytes()).err(),
+        Some(BspError::Malformed)
+    );
+}
+
+/// Bsp with only the worldspawn in its entities lump, the other lumps are empty.
+fn bsp() -> Vec<u8> {
+    bsp_with(&[(LumpType::Entities, WORLDSPAWN)])
+}
+
+#[test]
+fn reject_truncated_files() {
+    let file = bsp();
+    assert!(RawMap::parse(&file).is_ok());
+    for len in 0..file.len() {
+        assert!(RawMap::parse(&file[..len]).is_err(), "{} bytes", len);
+    }
+
+    let mut file = bsp();
+    let entry = 4 + 7 * 8;
+    file[entry..entry + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    file[entry + 4..entry + 8].copy_from_slice(&16u32.to_le_bytes());
+    let error = RawMap::parse(&file).err();
+    assert_eq!(
+        error,
+        Some(BspError::TruncatedLump {
+            lump: "faces",
+            offset: u32::MAX as usize,
+            len: 16,
+        })
+    );
+    assert!(error.unwrap().to_string().contains("faces"));
+}
+
+#[test]
+fn garbage_headers_dont_panic() {
+    let mut seed = 0x2545_f491_u32;
+    let mut next = || {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        seed
+    };
+    let valid = bsp();
+    for _ in 0..1000 {
+        let mut file = valid.clone();
+        for byte in &mut file[4..4 + 15 * 8] {
+            if next() % 4 == 0 {
+                *byte = next() as u8;
+            }
+        }
+        let _ = RawMap::parse(&file);
+    }
+}
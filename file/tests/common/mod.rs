@@ -0,0 +1,70 @@
+//! Builders of synthetic bsp and wad files for the tests.
+#![allow(dead_code)]
+
+use file::bsp::LumpType;
+
+pub const WORLDSPAWN: &[u8] = b"{\n\"classname\" \"worldspawn\"\n}\n\0";
+
+pub fn floats(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+/// Bsp of the given lumps following the header in the order of `LumpType`, the rest are empty.
+pub fn bsp_with(lumps: &[(LumpType, &[u8])]) -> Vec<u8> {
+    let mut data = [&[][..]; 15];
+    lumps
+        .iter()
+        .for_each(|&(lump, bytes)| data[lump as usize] = bytes);
+
+    let mut file = 30u32.to_le_bytes().to_vec();
+    let mut offset = 4 + data.len() * 8;
+    for lump in &data {
+        file.extend((offset as u32).to_le_bytes());
+        file.extend((lump.len() as u32).to_le_bytes());
+        offset += lump.len();
+    }
+    data.iter().for_each(|lump| file.extend(*lump));
+    file
+}
+
+/// Textures lump of the miptexs.
+pub fn textures_lump(miptexs: &[Vec<u8>]) -> Vec<u8> {
+    let mut textures = (miptexs.len() as u32).to_le_bytes().to_vec();
+    let mut offset = 4 + 4 * miptexs.len();
+    for miptex in miptexs {
+        textures.extend((offset as u32).to_le_bytes());
+        offset += miptex.len();
+    }
+    miptexs.iter().for_each(|miptex| textures.extend(miptex));
+    textures
+}
+
+/// Header of a 64x32 miptex which isn't embedded.
+pub fn miptex_header(name: &str) -> Vec<u8> {
+    let mut header = [0; 40];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[16..20].copy_from_slice(&64u32.to_le_bytes());
+    header[20..24].copy_from_slice(&32u32.to_le_bytes());
+    header.to_vec()
+}
+
+/// Wad of miptex entries of the given names and data.
+pub fn wad_with(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let data_len: usize = entries.iter().map(|(_, data)| data.len()).sum();
+    let mut file = b"WAD3".to_vec();
+    file.extend((entries.len() as u32).to_le_bytes());
+    file.extend(((12 + data_len) as u32).to_le_bytes());
+    entries.iter().for_each(|(_, data)| file.extend(*data));
+    let mut offset = 12;
+    for (name, data) in entries {
+        file.extend((offset as u32).to_le_bytes());
+        file.extend((data.len() as u32).to_le_bytes());
+        file.extend((data.len() as u32).to_le_bytes());
+        file.extend([0x43, 0, 0, 0]);
+        let mut name_bytes = [0; 16];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        file.extend(name_bytes);
+        offset += data.len();
+    }
+    file
+}
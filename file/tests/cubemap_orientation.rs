@@ -0,0 +1,29 @@
+#[test]
+fn synthetic_faces_orientation() {
+    use file::cubemap::Cubemap;
+    use image::{Rgb, RgbImage};
+
+    let dir = std::env::temp_dir().join(format!("hlbsp-cubemap-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Corners of each face are numbered from the top left one clockwise, 2x2 faces are all
+    // corners
+    let corner = |face: usize, corner: usize| Rgb([face as u8 * 40, corner as u8 * 60, 255]);
+    for (face, side) in Cubemap::side_names().iter().enumerate() {
+        let mut image = RgbImage::new(2, 2);
+        for (corner_id, &(x, y)) in [(0, 0), (1, 0), (1, 1), (0, 1)].iter().enumerate() {
+            image.put_pixel(x, y, corner(face, corner_id));
+        }
+        image.save(dir.join(format!("synth{}.bmp", side))).unwrap();
+    }
+
+    let cubemap = Cubemap::read("synth", &dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(cubemap.dimension(), 2);
+    // Top left texel of each layer: side faces are mirrored horizontally, up and down ones
+    // vertically
+    let expected_corners = [1, 1, 3, 3, 1, 1];
+    for (face, (pixels, &expected)) in cubemap.sides().iter().zip(&expected_corners).enumerate() {
+        let Rgb([r, g, b]) = corner(face, expected);
+        assert_eq!(&pixels[..4], &[r, g, b, 255], "face {}", face);
+    }
+}
@@ -0,0 +1,16 @@
+use file::map::Entities;
+
+const ENTITIES: &str = "{\r\n\"classname\" \"worldspawn\"\r\n\"skyname\" \"night\"\r\n}\r\n\
+{\n\"model\" \"*1\"\n\"classname\" \"func_door\"\n}\n\
+{ \"classname\" \"info_player_start\" \"origin\" \"0 0 36\" }\n\0";
+
+#[test]
+fn parse_goldsrc_entities() {
+    let entities = Entities::parse(ENTITIES).unwrap();
+    let entities = entities.entities();
+    assert_eq!(entities.len(), 3);
+    assert_eq!(entities[0].classname(), Some("worldspawn"));
+    assert_eq!(entities[0].get("skyname"), Some("night"));
+    assert_eq!(entities[1].get("skyname"), None);
+    assert_eq!(entities[2].get("origin"), Some("0 0 36"));
+}
@@ -0,0 +1,61 @@
+use file::bsp::{
+    lumps::TexInfo,
+    uv::{face_uv, texel_uv},
+};
+
+/// Texinfo of a floor facing up with the texture aligned to the world axes.
+fn floor(scale: f32, shift: (f32, f32)) -> TexInfo {
+    TexInfo {
+        vs: (1.0 / scale, 0.0, 0.0),
+        ss: shift.0,
+        vt: (0.0, -1.0 / scale, 0.0),
+        st: shift.1,
+        texture_id: 0,
+        flags: 0,
+    }
+}
+
+#[test]
+fn unit_scale_repeats_every_texture_size() {
+    let texinfo = floor(1.0, (0.0, 0.0));
+    assert_eq!(texel_uv((32.0, -16.0, 0.0), &texinfo), [32.0, 16.0]);
+    assert_eq!(face_uv((0.0, 0.0, 64.0), &texinfo, 64, 64), [0.0, 0.0]);
+    assert_eq!(face_uv((64.0, -32.0, 0.0), &texinfo, 64, 64), [1.0, 0.5]);
+    assert_eq!(face_uv((128.0, 0.0, 0.0), &texinfo, 64, 32), [2.0, 0.0]);
+}
+
+#[test]
+fn scale_stretches_texture() {
+    // Scale 2 makes texels two units long, so the texture repeats every 128 units
+    let texinfo = floor(2.0, (0.0, 0.0));
+    assert_eq!(face_uv((128.0, -64.0, 0.0), &texinfo, 64, 64), [1.0, 0.5]);
+    let texinfo = floor(0.5, (0.0, 0.0));
+    assert_eq!(face_uv((32.0, 0.0, 0.0), &texinfo, 64, 64), [1.0, 0.0]);
+}
+
+#[test]
+fn shift_is_in_texels() {
+    let texinfo = floor(2.0, (16.0, -8.0));
+    assert_eq!(texel_uv((0.0, 0.0, 0.0), &texinfo), [16.0, -8.0]);
+    assert_eq!(face_uv((32.0, 0.0, 0.0), &texinfo, 64, 16), [0.5, -0.5]);
+}
+
+#[test]
+fn rotated_axes() {
+    // A wall facing -Y with the texture rotated by 90 degrees
+    let texinfo = TexInfo {
+        vs: (0.0, 0.0, 1.0),
+        ss: 0.0,
+        vt: (1.0, 0.0, 0.0),
+        st: 0.0,
+        texture_id: 0,
+        flags: 0,
+    };
+    assert_eq!(face_uv((16.0, 100.0, 32.0), &texinfo, 64, 64), [0.5, 0.25]);
+}
+
+#[test]
+fn empty_texture_counts_as_one_texel() {
+    let texinfo = floor(1.0, (0.0, 0.0));
+    assert_eq!(face_uv((3.0, 0.0, 0.0), &texinfo, 0, 0), [3.0, 0.0]);
+}
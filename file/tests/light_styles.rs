@@ -0,0 +1,20 @@
+use file::bsp::lightstyle::{pattern_brightness, style_brightness};
+
+#[test]
+fn patterns_advance_at_ten_fps() {
+    assert_eq!(pattern_brightness("m", 12.3), 1.0);
+    assert_eq!(pattern_brightness("az", 0.05), 0.0);
+    assert_eq!(pattern_brightness("az", 0.15), 25.0 / 12.0);
+    assert_eq!(pattern_brightness("az", 0.25), 0.0);
+}
+
+#[test]
+fn brightness_of_styles() {
+    let brightness = style_brightness(0.55);
+    assert_eq!(brightness[0], 1.0);
+    // Fast strobe `mamama...`
+    assert_eq!(brightness[4], 0.0);
+    assert_eq!(brightness[20], 1.0);
+    assert_eq!(brightness[40], 1.0);
+    assert_eq!(brightness[63], 0.0);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{bsp_with, textures_lump, wad_with, WORLDSPAWN};
+use file::{
+    bsp::LumpType,
+    load::{load_map, parse_map, LoadMapError},
+};
+
+/// Miptex of the name, all texels of the first palette color if it's embedded.
+fn miptex(name: &str, embedded: bool) -> Vec<u8> {
+    let mut name_bytes = [0; 16];
+    name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+    let mut file = name_bytes.to_vec();
+    file.extend(16u32.to_le_bytes());
+    file.extend(8u32.to_le_bytes());
+    if !embedded {
+        file.extend([0; 16]);
+        return file;
+    }
+    for offset in [40u32, 168, 200, 208] {
+        file.extend(offset.to_le_bytes());
+    }
+    file.extend(vec![0; 128 + 32 + 8 + 2]);
+    file.extend([0, 1]);
+    file.extend((0..=255).flat_map(|_| [10, 20, 30]));
+    file
+}
+
+/// Map with only entities and textures, `stone` and `grass` aren't embedded.
+fn bsp() -> Vec<u8> {
+    let textures = textures_lump(&[
+        miptex("brick", true),
+        miptex("grass", false),
+        miptex("stone", false),
+    ]);
+    bsp_with(&[
+        (LumpType::Entities, WORLDSPAWN),
+        (LumpType::Textures, &textures),
+    ])
+}
+
+/// Wad of embedded miptexs of the names.
+fn wad(names: &[&str]) -> Vec<u8> {
+    let miptexs: Vec<_> = names.iter().map(|name| miptex(name, true)).collect();
+    let entries: Vec<_> = names
+        .iter()
+        .zip(&miptexs)
+        .map(|(&name, miptex)| (name, miptex.as_slice()))
+        .collect();
+    wad_with(&entries)
+}
+
+#[test]
+fn textures_from_map_and_wads() {
+    let (bsp, wad) = (bsp(), wad(&["GRASS"]));
+    let loaded = load_map(&bsp, &[&wad]).unwrap();
+    assert!(loaded.map.first_entity("worldspawn").is_some());
+
+    let names: Vec<_> = loaded
+        .textures
+        .iter()
+        .map(|t| t.as_ref().map(|t| t.name.as_str()))
+        .collect();
+    assert_eq!(names, [Some("brick"), Some("grass"), None]);
+    assert_eq!(loaded.missing_textures, ["stone"]);
+    let grass = loaded.textures[1].as_ref().unwrap();
+    assert_eq!((grass.width, grass.height), (16, 8));
+    assert_eq!(grass.mips.len(), 4);
+    assert_eq!(grass.mips[0].len(), 16 * 8 * 4);
+    assert_eq!(&grass.mips[3][..4], &[10, 20, 30, 255]);
+}
+
+#[test]
+fn broken_inputs() {
+    let (bsp, wad) = (bsp(), wad(&["GRASS"]));
+    let loaded = load_map(&bsp, &[b"WAD3", &wad]).unwrap();
+    assert!(loaded.wads[0].is_none() && loaded.wads[1].is_some());
+    assert!(loaded.textures[1].is_some());
+    assert_eq!(loaded.missing_textures, ["stone"]);
+    assert!(matches!(
+        load_map(b"VBSP", &[]).err(),
+        Some(LoadMapError::Bsp(_))
+    ));
+}
+
+#[test]
+fn parse_without_decoding() {
+    let (bsp, wad) = (bsp(), wad(&["GRASS"]));
+    let parsed = parse_map(&bsp, &[b"WAD3", &wad]).unwrap();
+    assert!(parsed.wads[0].is_none() && parsed.wads[1].is_some());
+    assert!(parsed.map.first_entity("worldspawn").is_some());
+    assert!(parse_map(b"VBSP", &[]).is_err());
+}
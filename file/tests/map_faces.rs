@@ -0,0 +1,125 @@
+mod common;
+
+use common::{bsp_with, floats, miptex_header, textures_lump, WORLDSPAWN};
+use file::bsp::{
+    faces::{FaceLightmap, MapFace},
+    lumps::TEX_SPECIAL,
+    LumpType, RawMap,
+};
+
+fn model(face_id: u32, face_num: u32) -> Vec<u8> {
+    let mut model = floats(&[0.0; 9]);
+    model.extend([0u32; 5].iter().flat_map(|x| x.to_le_bytes()));
+    model.extend(face_id.to_le_bytes());
+    model.extend(face_num.to_le_bytes());
+    model
+}
+
+/// Map whose world is a lit 64x64 floor quad of `wall`, a brush model has the same quad of
+/// unlit `sky` above it facing down, and a face referencing a missing texinfo.
+fn floor_bsp() -> Vec<u8> {
+    let planes = floats(&[0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    let textures = textures_lump(&[miptex_header("wall"), miptex_header("sky")]);
+
+    let vertices = floats(&[
+        0.0, 0.0, 0.0, 64.0, 0.0, 0.0, 64.0, 64.0, 0.0, 0.0, 64.0, 0.0, //
+        0.0, 0.0, 64.0, 0.0, 64.0, 64.0, 64.0, 64.0, 64.0, 64.0, 0.0, 64.0,
+    ]);
+    let edges: Vec<u8> = [(0u16, 0u16), (0, 1), (1, 2), (2, 3), (3, 0)]
+        .iter()
+        .chain(&[(4, 5), (5, 6), (6, 7), (7, 4)])
+        .flat_map(|&(a, b)| [a.to_le_bytes(), b.to_le_bytes()].concat())
+        .collect();
+    let surfedges: Vec<u8> = (1i32..=8).flat_map(|s| s.to_le_bytes()).collect();
+
+    let mut texinfos = vec![];
+    for (texture_id, flags) in [(0u32, 0), (1, TEX_SPECIAL)] {
+        texinfos.extend(floats(&[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]));
+        texinfos.extend(texture_id.to_le_bytes());
+        texinfos.extend(flags.to_le_bytes());
+    }
+    let mut faces = vec![];
+    for (face_id, texinfo_id, lightmap) in [(0u16, 0u16, 0), (1, 1, u32::MAX), (1, 9, 0)] {
+        faces.extend(0u16.to_le_bytes());
+        faces.extend(face_id.to_le_bytes());
+        faces.extend((u32::from(face_id) * 4).to_le_bytes());
+        faces.extend(4u16.to_le_bytes());
+        faces.extend(texinfo_id.to_le_bytes());
+        faces.extend([0, 255, 255, 255]);
+        faces.extend(lightmap.to_le_bytes());
+    }
+    let mut models = model(0, 1);
+    models.extend(model(1, 2));
+
+    bsp_with(&[
+        (LumpType::Entities, WORLDSPAWN),
+        (LumpType::Planes, &planes),
+        (LumpType::Textures, &textures),
+        (LumpType::Vertices, &vertices),
+        (LumpType::TexInfo, &texinfos),
+        (LumpType::Faces, &faces),
+        (LumpType::Edges, &edges),
+        (LumpType::Surfegdes, &surfedges),
+        (LumpType::Models, &models),
+    ])
+}
+
+#[test]
+fn resolve_faces_of_models() {
+    let bsp = floor_bsp();
+    let map = RawMap::parse(&bsp).unwrap();
+    let faces: Vec<MapFace> = map.faces().unwrap().collect();
+    // The face of the missing texinfo is skipped
+    assert_eq!(faces.len(), 2);
+
+    let floor = &faces[0];
+    assert_eq!((floor.face_id, floor.model_id), (0, 0));
+    assert_eq!(floor.texture, "wall");
+    assert_eq!(floor.texture_size, [64, 32]);
+    assert_eq!((floor.flags, floor.normal), (0, (0.0, 0.0, 1.0)));
+    assert_eq!(
+        floor.lightmap,
+        Some(FaceLightmap {
+            offset: 0,
+            size: [5, 5],
+            styles: [0, 255, 255, 255],
+        })
+    );
+    let positions: Vec<_> = floor
+        .triangles
+        .iter()
+        .map(|t| t.map(|v| v.position))
+        .collect();
+    assert_eq!(
+        positions,
+        [
+            [(0.0, 0.0, 0.0), (64.0, 0.0, 0.0), (64.0, 64.0, 0.0)],
+            [(0.0, 0.0, 0.0), (64.0, 64.0, 0.0), (0.0, 64.0, 0.0)]
+        ]
+    );
+    let corner = floor.triangles[0][2];
+    assert_eq!(corner.uv, [1.0, 2.0]);
+    assert_eq!(corner.lightmap_uv, Some([4.0, 4.0]));
+
+    let sky = &faces[1];
+    assert_eq!((sky.face_id, sky.model_id), (1, 1));
+    assert_eq!(sky.texture, "sky");
+    assert_eq!((sky.flags, sky.normal), (TEX_SPECIAL, (0.0, 0.0, -1.0)));
+    assert_eq!(sky.lightmap, None);
+    assert!(sky
+        .triangles
+        .iter()
+        .flatten()
+        .all(|v| v.lightmap_uv.is_none() && v.position.2 == 64.0));
+}
+
+#[test]
+fn reject_malformed_geometry() {
+    let mut bsp = floor_bsp();
+    // Textures lump claims more textures than it has offsets of
+    let textures = u32::from_le_bytes([bsp[20], bsp[21], bsp[22], bsp[23]]) as usize;
+    bsp[textures..textures + 4].copy_from_slice(&9u32.to_le_bytes());
+    let map = RawMap::parse(&bsp).unwrap();
+    assert!(map.faces().is_err());
+}
@@ -0,0 +1,169 @@
+use file::mdl::StudioModel;
+use std::f32::consts::FRAC_PI_2;
+
+const HEADER_SIZE: usize = 244;
+
+fn put_u32(file: &mut [u8], at: usize, value: u32) {
+    file[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_f32(file: &mut [u8], at: usize, value: f32) {
+    file[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Appends zeroed space for a structure, returning its offset.
+fn alloc(file: &mut Vec<u8>, size: usize) -> usize {
+    let offset = file.len();
+    file.resize(offset + size, 0);
+    offset
+}
+
+fn vec3s(file: &mut Vec<u8>, vectors: &[[f32; 3]]) -> usize {
+    let offset = file.len();
+    vectors
+        .iter()
+        .flatten()
+        .for_each(|x| file.extend(x.to_le_bytes()));
+    offset
+}
+
+/// Two bones: the root moved 10 along X and its child moved 5 along Y, turned around Z by the
+/// first frame of the only sequence. A strip of two triangles with a 2x2 masked texture has
+/// vertices of both bones.
+fn model() -> Vec<u8> {
+    let mut file = vec![0; HEADER_SIZE];
+    file[..4].copy_from_slice(b"IDST");
+    put_u32(&mut file, 4, 10);
+    file[8..18].copy_from_slice(b"test/model");
+
+    let bones = alloc(&mut file, 2 * 112);
+    file[bones..bones + 4].copy_from_slice(b"root");
+    put_u32(&mut file, bones + 32, u32::MAX);
+    put_f32(&mut file, bones + 64, 10.0);
+    file[bones + 112..bones + 117].copy_from_slice(b"child");
+    put_f32(&mut file, bones + 112 + 68, 5.0);
+    put_f32(&mut file, bones + 112 + 88 + 20, FRAC_PI_2);
+    put_u32(&mut file, 140, 2);
+    put_u32(&mut file, 144, bones as u32);
+
+    let sequence = alloc(&mut file, 176);
+    let seq_group = alloc(&mut file, 104);
+    let anims = alloc(&mut file, 2 * 12);
+    // Rotation around Z of the child, 1 stored frame of 1 is 1 step of the scale
+    file[anims + 12 + 10..anims + 12 + 12].copy_from_slice(&12u16.to_le_bytes());
+    file.extend([1, 1, 1, 0]);
+    put_u32(&mut file, sequence + 124, anims as u32);
+    put_u32(&mut file, 164, 1);
+    put_u32(&mut file, 168, sequence as u32);
+    put_u32(&mut file, 172, 1);
+    put_u32(&mut file, 176, seq_group as u32);
+
+    let texture = alloc(&mut file, 80);
+    file[texture..texture + 4].copy_from_slice(b"skin");
+    put_u32(&mut file, texture + 64, 0x40);
+    put_u32(&mut file, texture + 68, 2);
+    put_u32(&mut file, texture + 72, 2);
+    let pixels = file.len();
+    put_u32(&mut file, texture + 76, pixels as u32);
+    file.extend([0, 1, 2, 255]);
+    file.extend((0..=255).flat_map(|i| [i, i, i]));
+    let skin = file.len();
+    file.extend(0u16.to_le_bytes());
+    put_u32(&mut file, 180, 1);
+    put_u32(&mut file, 184, texture as u32);
+    put_u32(&mut file, 192, 1);
+    put_u32(&mut file, 196, 1);
+    put_u32(&mut file, 200, skin as u32);
+
+    let body_part = alloc(&mut file, 76);
+    let model = alloc(&mut file, 112);
+    let mesh = alloc(&mut file, 20);
+    put_u32(&mut file, body_part + 64, 1);
+    put_u32(&mut file, body_part + 72, model as u32);
+    put_u32(&mut file, 204, 1);
+    put_u32(&mut file, 208, body_part as u32);
+
+    let vert_info = file.len();
+    file.extend([0, 1]);
+    let verts = vec3s(&mut file, &[[1.0, 0.0, 0.0], [1.0, 0.0, 0.0]]);
+    let norm_info = file.len();
+    file.push(1);
+    let norms = vec3s(&mut file, &[[0.0, 0.0, 1.0]]);
+    for (at, value) in [
+        (72, 1),
+        (76, mesh),
+        (80, 2),
+        (84, vert_info),
+        (88, verts),
+        (92, 1),
+        (96, norm_info),
+        (100, norms),
+    ] {
+        put_u32(&mut file, model + at, value as u32);
+    }
+
+    let tris = file.len();
+    let commands: [i16; 17] = [4, 0, 0, 0, 0, 1, 0, 2, 0, 0, 0, 0, 2, 1, 0, 2, 2];
+    commands.iter().for_each(|c| file.extend(c.to_le_bytes()));
+    file.extend(0i16.to_le_bytes());
+    put_u32(&mut file, mesh, 2);
+    put_u32(&mut file, mesh + 4, tris as u32);
+    file
+}
+
+fn assert_near(a: [f32; 3], b: [f32; 3]) {
+    assert!(
+        a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-4),
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+#[test]
+fn parse_studio_model() {
+    let file = model();
+    let model = StudioModel::parse(&file).unwrap();
+    assert_eq!(model.name, "test/model");
+    assert_eq!(model.bones.len(), 2);
+    assert_eq!(model.bones[0].parent, None);
+    assert_eq!(model.bones[1].parent, Some(0));
+    assert_eq!(model.skin_texture(0), Some(0));
+    assert_eq!(model.skin_texture(1), None);
+
+    let texture = &model.textures[0];
+    assert_eq!((texture.name.as_str(), texture.width), ("skin", 2));
+    assert!(texture.is_masked());
+    assert_eq!(&texture.pixels[4..8], &[1, 1, 1, 255]);
+    assert_eq!(&texture.pixels[12..], &[0; 4]);
+
+    // The strip's second triangle is reversed to keep the winding
+    let mesh = &model.meshes[0];
+    let tex_coords: Vec<_> = mesh.vertices.iter().map(|v| v.tex_coords).collect();
+    assert_eq!(
+        tex_coords,
+        [
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [0.0, 2.0],
+            [0.0, 2.0],
+            [2.0, 0.0],
+            [2.0, 2.0]
+        ]
+    );
+
+    let pose = model.pose();
+    let (root_vertex, _) = StudioModel::posed(&pose, &mesh.vertices[0]);
+    assert_near(root_vertex, [11.0, 0.0, 0.0]);
+    let (child_vertex, normal) = StudioModel::posed(&pose, &mesh.vertices[1]);
+    assert_near(child_vertex, [10.0, 6.0, 0.0]);
+    assert_near(normal, [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn reject_other_versions() {
+    let mut file = model();
+    put_u32(&mut file, 4, 6);
+    assert!(StudioModel::parse(&file).is_err());
+    assert!(StudioModel::parse(b"IDST").is_err());
+}
@@ -0,0 +1,97 @@
+use file::bsp::merge::{merge_coplanar, polygon_area, remove_collinear};
+
+#[test]
+fn drop_collinear_vertices() {
+    let polygon = [
+        (0.0, 0.0, 0.0),
+        (32.0, 0.0, 0.0),
+        (64.0, 0.0, 0.0),
+        (64.0, 64.0, 0.0),
+        (0.0, 64.0, 0.0),
+    ];
+    assert_eq!(remove_collinear(&polygon).len(), 4);
+}
+
+#[test]
+fn measure_polygon_area() {
+    let quad = [
+        (0.0, 0.0, 8.0),
+        (32.0, 0.0, 8.0),
+        (32.0, 0.0, 72.0),
+        (0.0, 0.0, 72.0),
+    ];
+    assert_eq!(polygon_area(&quad), 2048.0);
+    let line = [(0.0, 0.0, 0.0), (32.0, 0.0, 0.0), (64.0, 0.0, 0.0)];
+    assert_eq!(polygon_area(&line), 0.0);
+    assert_eq!(polygon_area(&quad[..2]), 0.0);
+}
+
+#[test]
+fn merge_split_quads() {
+    let left = vec![
+        (0.0, 0.0, 0.0),
+        (32.0, 0.0, 0.0),
+        (32.0, 64.0, 0.0),
+        (0.0, 64.0, 0.0),
+    ];
+    let right = vec![
+        (32.0, 0.0, 0.0),
+        (64.0, 0.0, 0.0),
+        (64.0, 64.0, 0.0),
+        (32.0, 64.0, 0.0),
+    ];
+    let merged = merge_coplanar(&[left, right]);
+    assert_eq!(merged.len(), 1);
+    let (polygon, sources) = &merged[0];
+    assert_eq!(polygon.len(), 4);
+    assert_eq!(sources.len(), 2);
+}
+
+#[test]
+fn keep_concave_union_apart() {
+    // L-shaped union of these two isn't convex
+    let bottom = vec![
+        (0.0, 0.0, 0.0),
+        (64.0, 0.0, 0.0),
+        (64.0, 32.0, 0.0),
+        (32.0, 32.0, 0.0),
+        (0.0, 32.0, 0.0),
+    ];
+    let top = vec![
+        (0.0, 32.0, 0.0),
+        (32.0, 32.0, 0.0),
+        (32.0, 64.0, 0.0),
+        (0.0, 64.0, 0.0),
+    ];
+    let merged = merge_coplanar(&[bottom, top.clone()]);
+    assert_eq!(merged.len(), 2);
+    assert!(merged.iter().any(|(polygon, _)| polygon == &top));
+}
+
+#[test]
+fn merge_along_split_edge() {
+    // Right half was split in two by the BSP, leaving a vertex on the shared edge
+    let left = vec![
+        (0.0, 0.0, 0.0),
+        (32.0, 0.0, 0.0),
+        (32.0, 32.0, 0.0),
+        (32.0, 64.0, 0.0),
+        (0.0, 64.0, 0.0),
+    ];
+    let right_bottom = vec![
+        (32.0, 0.0, 0.0),
+        (64.0, 0.0, 0.0),
+        (64.0, 32.0, 0.0),
+        (32.0, 32.0, 0.0),
+    ];
+    let right_top = vec![
+        (32.0, 32.0, 0.0),
+        (64.0, 32.0, 0.0),
+        (64.0, 64.0, 0.0),
+        (32.0, 64.0, 0.0),
+    ];
+    let merged = merge_coplanar(&[left, right_bottom, right_top]);
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].0.len(), 4);
+    assert_eq!(merged[0].1.len(), 3);
+}
@@ -0,0 +1,40 @@
+use file::miptex::MipTexture;
+
+/// 16x16 miptex of the name with all texels of the given palette index.
+fn miptex(name: &str, index: u8) -> Vec<u8> {
+    let mut name_bytes = [0; 16];
+    name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+    let mut file = name_bytes.to_vec();
+    file.extend(16u32.to_le_bytes());
+    file.extend(16u32.to_le_bytes());
+    for offset in [40u32, 296, 360, 376] {
+        file.extend(offset.to_le_bytes());
+    }
+    file.extend(vec![index; 256 + 64 + 16 + 4]);
+    file.extend([0, 1]);
+    file.extend((0..=255).flat_map(|_| [0, 0, 255]));
+    file
+}
+
+#[test]
+fn masked_texture_cutout() {
+    let masked = miptex("{fence", 255);
+    let masked = MipTexture::parse(&masked).unwrap();
+    assert!(masked.is_masked());
+    assert_eq!(&masked.pixels(0).unwrap()[..8], &[0; 8]);
+
+    // The same index is an ordinary color of other textures
+    let solid = miptex("fence", 255);
+    let solid = MipTexture::parse(&solid).unwrap();
+    assert!(!solid.is_masked());
+    assert_eq!(&solid.pixels(3).unwrap()[..4], &[0, 0, 255, 255]);
+}
+
+#[test]
+fn decal_opacity_gradient() {
+    let decal = miptex("{bigshot1", 128);
+    let decal = MipTexture::parse(&decal).unwrap();
+    // Colored by the last palette entry whatever the index is
+    assert_eq!(&decal.decal_pixels(0).unwrap()[..4], &[0, 0, 255, 128]);
+    assert_eq!(decal.decal_pixels(1).unwrap().len(), 64 * 4);
+}
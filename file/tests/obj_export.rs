@@ -0,0 +1,83 @@
+mod common;
+
+use common::{bsp_with, floats, miptex_header, textures_lump, WORLDSPAWN};
+use file::bsp::{LumpType, RawMap};
+
+/// Map whose world is a 64x64 floor quad of `wall` and the same quad of `sky` above it.
+fn floor_bsp() -> Vec<u8> {
+    let planes = floats(&[0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    let textures = textures_lump(&[miptex_header("wall"), miptex_header("sky")]);
+
+    let vertices = floats(&[
+        0.0, 0.0, 0.0, 64.0, 0.0, 0.0, 64.0, 64.0, 0.0, 0.0, 64.0, 0.0, //
+        0.0, 0.0, 64.0, 64.0, 0.0, 64.0, 64.0, 64.0, 64.0, 0.0, 64.0, 64.0,
+    ]);
+    let edges: Vec<u8> = [(0u16, 0u16), (0, 1), (1, 2), (2, 3), (3, 0)]
+        .iter()
+        .chain(&[(4, 5), (5, 6), (6, 7), (7, 4)])
+        .flat_map(|&(a, b)| [a.to_le_bytes(), b.to_le_bytes()].concat())
+        .collect();
+    let surfedges: Vec<u8> = (1i32..=8).flat_map(|s| s.to_le_bytes()).collect();
+
+    let mut texinfos = vec![];
+    for texture_id in 0u32..2 {
+        texinfos.extend(floats(&[1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]));
+        texinfos.extend(texture_id.to_le_bytes());
+        texinfos.extend(0u32.to_le_bytes());
+    }
+    let mut faces = vec![];
+    for face_id in 0u16..2 {
+        faces.extend(0u16.to_le_bytes());
+        faces.extend(0u16.to_le_bytes());
+        faces.extend((u32::from(face_id) * 4).to_le_bytes());
+        faces.extend(4u16.to_le_bytes());
+        faces.extend(face_id.to_le_bytes());
+        faces.extend([0, 255, 255, 255]);
+        faces.extend(u32::MAX.to_le_bytes());
+    }
+    let mut models = floats(&[0.0; 9]);
+    models.extend([0u32; 5].iter().flat_map(|x| x.to_le_bytes()));
+    models.extend(0u32.to_le_bytes());
+    models.extend(2u32.to_le_bytes());
+
+    bsp_with(&[
+        (LumpType::Entities, WORLDSPAWN),
+        (LumpType::Planes, &planes),
+        (LumpType::Textures, &textures),
+        (LumpType::Vertices, &vertices),
+        (LumpType::TexInfo, &texinfos),
+        (LumpType::Faces, &faces),
+        (LumpType::Edges, &edges),
+        (LumpType::Surfegdes, &surfedges),
+        (LumpType::Models, &models),
+    ])
+}
+
+#[test]
+fn export_floor_quad() {
+    let bsp = floor_bsp();
+    let map = RawMap::parse(&bsp).unwrap();
+    let (mut obj, mut mtl) = (vec![], vec![]);
+    file::export::write_obj(&map, "floor.mtl", &mut obj, &mut mtl).unwrap();
+    let (obj, mtl) = (
+        String::from_utf8(obj).unwrap(),
+        String::from_utf8(mtl).unwrap(),
+    );
+
+    assert!(obj.starts_with("mtllib floor.mtl\n"));
+    // The sky quad is left out
+    assert_eq!(obj.matches("usemtl ").count(), 1);
+    assert!(obj.contains("usemtl wall\n"));
+    assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 4);
+    assert_eq!(
+        obj.lines()
+            .filter(|l| l.starts_with("f "))
+            .collect::<Vec<_>>(),
+        vec!["f 1/1 2/2 3/3", "f 1/1 3/3 4/4"]
+    );
+    // Z up turns Y up, UVs are in texture sizes
+    assert!(obj.contains("v 64 0 -64\nvt 1 -2\n"));
+    assert!(mtl.contains("newmtl wall\n"));
+    assert!(!mtl.contains("sky"));
+}
@@ -0,0 +1,47 @@
+use file::pak::Pak;
+
+/// Pak with the entries' data right after the header and the directory at the end.
+fn pak_with(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let data_size: usize = entries.iter().map(|(_, data)| data.len()).sum();
+    let mut file = b"PACK".to_vec();
+    file.extend(((12 + data_size) as u32).to_le_bytes());
+    file.extend(((entries.len() * 64) as u32).to_le_bytes());
+    entries.iter().for_each(|(_, data)| file.extend(*data));
+
+    let mut offset = 12;
+    for (name, data) in entries {
+        let mut name_bytes = [0; 56];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        file.extend(name_bytes);
+        file.extend((offset as u32).to_le_bytes());
+        file.extend((data.len() as u32).to_le_bytes());
+        offset += data.len();
+    }
+    file
+}
+
+#[test]
+fn read_entries_ignoring_case() {
+    let file = pak_with(&[("maps/Foo.bsp", b"bsp data"), ("halflife.wad", b"wad")]);
+    let pak = Pak::parse(&file).unwrap();
+    assert_eq!(pak.entries().count(), 2);
+    assert_eq!(pak.read("MAPS\\foo.bsp"), Some(&b"bsp data"[..]));
+    assert_eq!(pak.read("halflife.wad"), Some(&b"wad"[..]));
+    assert_eq!(pak.read("maps/bar.bsp"), None);
+    assert!(Pak::parse(b"WAD3").is_err());
+}
+
+#[test]
+fn read_through_container_path() {
+    let path = std::env::temp_dir().join("hlbsp_pak_read_test.pak");
+    std::fs::write(&path, pak_with(&[("maps/foo.bsp", b"bsp data")])).unwrap();
+
+    let inner = format!("{}:maps/foo.bsp", path.display());
+    assert!(file::container::is_archived(&inner));
+    assert_eq!(file::container::read(&inner).unwrap(), b"bsp data");
+    let missing = format!("{}:maps/bar.bsp", path.display());
+    let err = file::container::read(&missing).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{bsp_with, WORLDSPAWN};
+use file::bsp::{
+    contents::{point_contents, Contents},
+    lumps::{Leaf, Node, Plane},
+    LumpType, RawMap,
+};
+
+fn leaf(contents: Contents) -> Leaf {
+    Leaf {
+        contents: contents as i32,
+        vis_offset: -1,
+        mins: [0; 3],
+        maxs: [0; 3],
+        marksurface_id: 0,
+        marksurface_num: 0,
+    }
+}
+
+/// Air above z = 0 and water below it down to z = -64, solid beneath.
+fn pool() -> (Vec<Node>, Vec<Plane>, Vec<Leaf>) {
+    let planes = vec![
+        Plane {
+            normal: (0.0, 0.0, 1.0),
+            dist: 0.0,
+        },
+        Plane {
+            normal: (0.0, 0.0, 1.0),
+            dist: -64.0,
+        },
+    ];
+    let node = |plane_id, children| Node {
+        plane_id,
+        children,
+        mins: [0; 3],
+        maxs: [0; 3],
+    };
+    let nodes = vec![node(0, [-2, 1]), node(1, [-3, -1])];
+    let leaves = vec![
+        leaf(Contents::Solid),
+        leaf(Contents::Empty),
+        leaf(Contents::Water),
+    ];
+    (nodes, planes, leaves)
+}
+
+#[test]
+fn classify_points_by_leaves() {
+    let (nodes, planes, leaves) = pool();
+    let contents = |z| point_contents(&nodes, &planes, &leaves, 0, (0.0, 0.0, z));
+    assert_eq!(contents(16.0), Contents::Empty);
+    assert_eq!(contents(-16.0), Contents::Water);
+    assert!(contents(-16.0).is_liquid());
+    assert_eq!(contents(-128.0), Contents::Solid);
+    assert_eq!(
+        point_contents(&[], &planes, &leaves, 0, (0.0, 0.0, 16.0)),
+        Contents::Solid
+    );
+}
+
+#[test]
+fn convert_values() {
+    assert_eq!(Contents::from_value(-5), Contents::Lava);
+    assert_eq!(Contents::from_value(-6), Contents::Sky);
+    assert_eq!(Contents::from_value(-15), Contents::Translucent);
+    assert_eq!(Contents::from_value(0), Contents::Solid);
+    assert!(!Contents::Sky.is_liquid());
+}
+
+/// Bsp of the pool's planes, nodes and leaves with a world model whose hull 0 starts at node 0.
+fn bsp() -> Vec<u8> {
+    let (nodes, planes, leaves) = pool();
+    let mut planes_lump = vec![];
+    for plane in &planes {
+        let (x, y, z) = plane.normal;
+        [x, y, z, plane.dist]
+            .iter()
+            .for_each(|f| planes_lump.extend(f.to_le_bytes()));
+        planes_lump.extend(2u32.to_le_bytes());
+    }
+    let mut nodes_lump = vec![];
+    for node in &nodes {
+        nodes_lump.extend((node.plane_id as u32).to_le_bytes());
+        node.children
+            .iter()
+            .for_each(|c| nodes_lump.extend(c.to_le_bytes()));
+        nodes_lump.extend([0; 16]);
+    }
+    let mut leaves_lump = vec![];
+    for leaf in &leaves {
+        leaves_lump.extend(leaf.contents.to_le_bytes());
+        leaves_lump.extend(leaf.vis_offset.to_le_bytes());
+        leaves_lump.extend([0; 20]);
+    }
+    bsp_with(&[
+        (LumpType::Entities, WORLDSPAWN),
+        (LumpType::Planes, &planes_lump),
+        (LumpType::Nodes, &nodes_lump),
+        (LumpType::Leaves, &leaves_lump),
+        (LumpType::Models, &[0; 64]),
+    ])
+}
+
+#[test]
+fn query_contents_of_map() {
+    let file = bsp();
+    let map = RawMap::parse(&file).unwrap();
+    assert_eq!(map.point_contents((0.0, 0.0, 16.0)), Contents::Empty);
+    assert_eq!(map.point_contents((32.0, 0.0, -32.0)), Contents::Water);
+    assert_eq!(map.point_contents((0.0, 0.0, -96.0)), Contents::Solid);
+}
+
+#[test]
+fn solid_in_malformed_trees() {
+    let (mut nodes, planes, leaves) = pool();
+    let contents = |nodes: &[Node], head_node| {
+        point_contents(nodes, &planes, &leaves, head_node, (0.0, 0.0, -16.0))
+    };
+    assert_eq!(contents(&nodes, 2), Contents::Solid);
+    nodes[0].children[1] = 0;
+    assert_eq!(contents(&nodes, 0), Contents::Solid);
+    nodes[0].children[1] = 1;
+    nodes[1].plane_id = 2;
+    assert_eq!(contents(&nodes, 0), Contents::Solid);
+}
@@ -0,0 +1,27 @@
+use file::replacement::ReplacementDir;
+use image::{Rgba, RgbaImage};
+
+#[test]
+fn replacements_by_name() {
+    let dir = std::env::temp_dir().join(format!("hlbsp-replacements-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let red = RgbaImage::from_pixel(64, 32, Rgba([255, 0, 0, 255]));
+    let blue = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 128]));
+    red.save(dir.join("CRATE1.png")).unwrap();
+    // Less preferred than the png of the same name
+    blue.save(dir.join("crate1.tga")).unwrap();
+    blue.save(dir.join("+0lava.tga")).unwrap();
+    std::fs::write(dir.join("readme.txt"), "not an image").unwrap();
+
+    let replacements = ReplacementDir::scan(&dir).unwrap();
+    let crate1 = replacements.read("Crate1").unwrap().unwrap();
+    let lava = replacements.read("+0LAVA").unwrap().unwrap();
+    let missing = replacements.read("readme").is_none() && replacements.read("wall").is_none();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(replacements.len(), 2);
+    assert_eq!((crate1.width, crate1.height), (64, 32));
+    assert_eq!(&crate1.pixels[..4], &[255, 0, 0, 255]);
+    assert_eq!(&lava.pixels[..4], &[0, 0, 255, 128]);
+    assert!(missing);
+}
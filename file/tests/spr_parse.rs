@@ -0,0 +1,57 @@
+use file::spr::{Orientation, Sprite, TextureFormat};
+
+/// Sprite of the format with a single 2x1 frame and a group of two 1x1 frames, the palette
+/// maps indices to their grays.
+fn sprite(format: u32) -> Vec<u8> {
+    let mut file = b"IDSP".to_vec();
+    for value in [2, 2, format] {
+        file.extend(value.to_le_bytes());
+    }
+    file.extend(1.0f32.to_le_bytes());
+    for value in [2u32, 1, 2] {
+        file.extend(value.to_le_bytes());
+    }
+    file.extend(0.0f32.to_le_bytes());
+    file.extend(0u32.to_le_bytes());
+    file.extend(256u16.to_le_bytes());
+    file.extend((0..=255).flat_map(|i| [i, i, i]));
+
+    file.extend(0u32.to_le_bytes());
+    for value in [-1i32, 1, 2, 1] {
+        file.extend(value.to_le_bytes());
+    }
+    file.extend([7, 255]);
+
+    file.extend([1u32, 2].iter().flat_map(|x| x.to_le_bytes()));
+    file.extend([0.1f32, 0.2].iter().flat_map(|x| x.to_le_bytes()));
+    for index in [3, 4] {
+        for value in [0i32, 0, 1, 1] {
+            file.extend(value.to_le_bytes());
+        }
+        file.push(index);
+    }
+    file
+}
+
+#[test]
+fn parse_sprite_frames() {
+    let file = sprite(0);
+    let sprite = Sprite::parse(&file).unwrap();
+    assert_eq!(sprite.orientation, Orientation::Parallel);
+    assert_eq!(sprite.format, TextureFormat::Normal);
+    assert_eq!((sprite.width, sprite.height), (2, 1));
+    assert_eq!(sprite.frames.len(), 3);
+    assert_eq!(sprite.frames[0].origin, [-1, 1]);
+    assert_eq!(sprite.frames[0].pixels, [7, 7, 7, 255, 255, 255, 255, 255]);
+    assert_eq!(sprite.frames[2].pixels, [4, 4, 4, 255]);
+}
+
+#[test]
+fn transparency_of_formats() {
+    let file = sprite(3);
+    let alpha_test = Sprite::parse(&file).unwrap();
+    assert_eq!(&alpha_test.frames[0].pixels[4..], &[0; 4]);
+    let file = sprite(2);
+    let index_alpha = Sprite::parse(&file).unwrap();
+    assert_eq!(&index_alpha.frames[0].pixels[..4], &[255, 255, 255, 7]);
+}
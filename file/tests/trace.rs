@@ -0,0 +1,91 @@
+use file::bsp::{
+    lumps::{ClipNode, Leaf, Node, Plane},
+    trace::{Hull, CONTENTS_EMPTY, CONTENTS_SOLID},
+};
+
+fn floor_hull() -> Hull {
+    let planes = vec![Plane {
+        normal: (0.0, 0.0, 1.0),
+        dist: 0.0,
+    }];
+    let clipnodes = vec![ClipNode {
+        plane_id: 0,
+        children: [CONTENTS_EMPTY as i16, CONTENTS_SOLID as i16],
+    }];
+    Hull::new(clipnodes, planes, 0)
+}
+
+#[test]
+fn trace_hits_floor() {
+    let hull = floor_hull();
+    assert_eq!(hull.contents((0.0, 0.0, 1.0)), CONTENTS_EMPTY);
+    assert_eq!(hull.contents((0.0, 0.0, -1.0)), CONTENTS_SOLID);
+
+    let hit = hull.trace((0.0, 0.0, 10.0), (0.0, 0.0, -10.0));
+    assert!((hit.fraction - 0.5).abs() < 0.01);
+    assert!(hit.end.2 > 0.0 && hit.end.2 < 0.1);
+    assert_eq!(hit.normal, Some((0.0, 0.0, 1.0)));
+    assert!(!hit.start_solid && !hit.all_solid);
+
+    let miss = hull.trace((0.0, 0.0, 10.0), (5.0, 0.0, 1.0));
+    assert_eq!(miss.fraction, 1.0);
+    assert_eq!(miss.normal, None);
+}
+
+#[test]
+fn point_hull_from_nodes() {
+    let planes = vec![Plane {
+        normal: (0.0, 0.0, 1.0),
+        dist: 0.0,
+    }];
+    let nodes = [Node {
+        plane_id: 0,
+        children: [-2, -1],
+        mins: [0; 3],
+        maxs: [0; 3],
+    }];
+    let leaf = |contents| Leaf {
+        contents,
+        vis_offset: -1,
+        mins: [0; 3],
+        maxs: [0; 3],
+        marksurface_id: 0,
+        marksurface_num: 0,
+    };
+    let hull = Hull::from_nodes(
+        &nodes,
+        &[leaf(CONTENTS_SOLID), leaf(CONTENTS_EMPTY)],
+        planes,
+        0,
+    );
+    assert_eq!(hull.contents((0.0, 0.0, 1.0)), CONTENTS_EMPTY);
+    assert_eq!(hull.contents((0.0, 0.0, -1.0)), CONTENTS_SOLID);
+    assert_eq!(
+        hull.trace((0.0, 0.0, 10.0), (0.0, 0.0, -10.0)).normal,
+        Some((0.0, 0.0, 1.0))
+    );
+}
+
+#[test]
+fn broken_references_are_solid() {
+    let planes = vec![Plane {
+        normal: (0.0, 0.0, 1.0),
+        dist: 0.0,
+    }];
+    let hull = |children, plane_id, head_node| {
+        Hull::new(
+            vec![ClipNode { plane_id, children }],
+            planes.clone(),
+            head_node,
+        )
+    };
+    let below = (0.0, 0.0, -1.0);
+    let empty = CONTENTS_EMPTY as i16;
+    assert_eq!(hull([empty, 1], 0, 0).contents(below), CONTENTS_SOLID);
+    assert_eq!(hull([empty, 0], 0, 0).contents(below), CONTENTS_SOLID);
+    assert_eq!(hull([empty, empty], 1, 0).contents(below), CONTENTS_SOLID);
+    assert_eq!(hull([empty, empty], 0, 1).contents(below), CONTENTS_SOLID);
+
+    let hit = hull([empty, 0], 0, 0).trace((0.0, 0.0, 10.0), (0.0, 0.0, -10.0));
+    assert_eq!(hit.normal, Some((0.0, 0.0, 1.0)));
+}
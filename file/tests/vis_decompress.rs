@@ -0,0 +1,62 @@
+use file::bsp::{
+    lumps::{Leaf, Node, Plane},
+    vis::{decompress_vis, find_leaf},
+};
+
+fn leaf(vis_offset: i32) -> Leaf {
+    Leaf {
+        contents: 0,
+        vis_offset,
+        mins: [0; 3],
+        maxs: [0; 3],
+        marksurface_id: 0,
+        marksurface_num: 0,
+    }
+}
+
+#[test]
+fn decompress_runs_of_zeros() {
+    // Leaves 1 and 3 are visible, then 2 bytes of invisible ones, then leaf 28
+    let vis = [0xFF, 0b101, 0, 2, 0b1000];
+    let visible = decompress_vis(&vis, &leaf(1), 28);
+    let expected = (1..=28)
+        .map(|i| i == 1 || i == 3 || i == 28)
+        .collect::<Vec<_>>();
+    assert_eq!(visible, expected);
+    assert_eq!(decompress_vis(&vis, &leaf(-1), 3), vec![true; 3]);
+}
+
+#[test]
+fn find_leaf_by_planes() {
+    let planes = vec![Plane {
+        normal: (1.0, 0.0, 0.0),
+        dist: 16.0,
+    }];
+    let nodes = vec![Node {
+        plane_id: 0,
+        children: [-2, -3],
+        mins: [0; 3],
+        maxs: [0; 3],
+    }];
+    assert_eq!(find_leaf(&nodes, &planes, 0, (32.0, 0.0, 0.0)), 1);
+    assert_eq!(find_leaf(&nodes, &planes, 0, (0.0, 0.0, 0.0)), 2);
+}
+
+#[test]
+fn find_leaf_of_malformed_trees() {
+    let planes = vec![Plane {
+        normal: (1.0, 0.0, 0.0),
+        dist: 16.0,
+    }];
+    let node = |plane_id, children| Node {
+        plane_id,
+        children,
+        mins: [0; 3],
+        maxs: [0; 3],
+    };
+    let point = (32.0, 0.0, 0.0);
+    assert_eq!(find_leaf(&[node(0, [-2, -3])], &planes, 1, point), 0);
+    assert_eq!(find_leaf(&[node(0, [1, -3])], &planes, 0, point), 0);
+    assert_eq!(find_leaf(&[node(1, [-2, -3])], &planes, 0, point), 0);
+    assert_eq!(find_leaf(&[node(0, [0, 0])], &planes, 0, point), 0);
+}
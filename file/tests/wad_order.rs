@@ -0,0 +1,21 @@
+mod common;
+
+use common::wad_with;
+
+#[test]
+fn first_wad_provides_shared_entries() {
+    let (first, second) = (
+        wad_with(&[("BRICK", &[]), ("SKY", &[])]),
+        wad_with(&[("brick", &[]), ("GRASS", &[])]),
+    );
+    let first = file::wad::Archive::parse(&first).unwrap();
+    let second = file::wad::Archive::parse(&second).unwrap();
+
+    let mut order = file::wad::WadOrder::new();
+    assert!(order.push(first.entries().map(|(name, _)| name)).is_empty());
+    let shadowed = order.push(second.entries().map(|(name, _)| name));
+    assert_eq!(shadowed, vec![("brick".to_string(), 0)]);
+    assert_eq!(order.provider("Brick"), Some(0));
+    assert_eq!(order.provider("grass"), Some(1));
+    assert_eq!(order.provider("stone"), None);
+}
@@ -5,3 +5,30 @@ fn print_entries_name_and_type() {
     wad.entries()
         .for_each(|(name, e)| println!("{} - {}", name, e.etype()));
 }
+
+#[test]
+fn streamed_entries_match_buffered() {
+    let file = std::fs::read(env!("WAD_TEST")).unwrap();
+    let wad = file::wad::Archive::parse(&file).unwrap();
+    let streamed = file::wad::StreamedArchive::open(env!("WAD_TEST")).unwrap();
+    assert_eq!(wad.entries().count(), streamed.entries().count());
+    wad.entries().for_each(|(name, e)| {
+        let info = streamed.get_by_name(name).unwrap();
+        assert_eq!(info.etype(), e.etype());
+        assert_eq!(streamed.read(name).unwrap().unwrap(), e.data());
+    });
+}
+
+#[test]
+fn streamed_directory_past_the_end() {
+    let path = std::env::temp_dir().join("hlbsp_wad_read_test.wad");
+    let mut wad = b"WAD3".to_vec();
+    wad.extend(u32::MAX.to_le_bytes());
+    wad.extend(12u32.to_le_bytes());
+    std::fs::write(&path, wad).unwrap();
+
+    let err = file::wad::StreamedArchive::open(&path).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,21 @@
+use std::io::Write;
+use zip::{write::FileOptions, ZipWriter};
+
+#[test]
+fn read_inner_path_ignoring_case() {
+    let path = std::env::temp_dir().join("hlbsp_zip_read_test.zip");
+    let mut zip = ZipWriter::new(std::fs::File::create(&path).unwrap());
+    zip.start_file("Maps/Foo.bsp", FileOptions::default())
+        .unwrap();
+    zip.write_all(b"bsp data").unwrap();
+    zip.finish().unwrap();
+
+    let inner = format!("{}:maps\\foo.BSP", path.display());
+    assert_eq!(file::container::read(&inner).unwrap(), b"bsp data");
+
+    let missing = format!("{}:maps/bar.bsp", path.display());
+    let err = file::container::read(&missing).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    std::fs::remove_file(path).unwrap();
+}
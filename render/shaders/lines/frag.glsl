@@ -0,0 +1,9 @@
+#version 140
+
+in vec3 o_color;
+
+out vec4 out_color;
+
+void main() {
+    out_color = vec4(o_color, 1.0);
+}
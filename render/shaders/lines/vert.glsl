@@ -0,0 +1,13 @@
+#version 140
+
+in vec3 position;
+in vec3 color;
+
+out vec3 o_color;
+
+uniform mat4 mvp;
+
+void main() {
+    o_color = color;
+    gl_Position = mvp * vec4(position, 1.0);
+}
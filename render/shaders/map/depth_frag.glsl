@@ -0,0 +1,6 @@
+#version 140
+
+// Only depth is written, color writes are masked out
+void main() {
+    gl_FragColor = vec4(0.0);
+}
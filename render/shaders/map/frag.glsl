@@ -5,24 +5,86 @@ in vec2 o_light_tex_coords;
 
 flat in uint o_lightmap_offset;
 flat in uvec2 o_lightmap_size;
+flat in uvec4 o_light_styles;
+flat in float o_texel_density;
+flat in uint o_leaf;
+in float o_view_depth;
 
 uniform sampler2D colormap;
+// Size texture coordinates are in, differs from the colormap's one for replaced textures
+uniform vec2 texture_size;
 uniform samplerBuffer lightmap;
+// Brightness of each light style, lightmaps of faces are scaled by their styles and summed
+uniform samplerBuffer light_styles;
+uniform float emissive;
+// Used instead of the shaded color when alpha isn't zero
+uniform vec4 overlay_color;
+uniform vec3 tint;
+uniform float opacity;
+// Sine warp of water surfaces and linear scroll of conveyors, both in texels
+uniform bool water_warp;
+uniform float warp_time;
+uniform vec2 uv_scroll;
+// Drop transparent pixels instead of blending them
+uniform bool alpha_test;
+// Lit colors are raised to 1 / gamma
+uniform float gamma;
+// Textures and lightmaps are sRGB, decode them before lighting and encode the lit color
+uniform bool srgb;
+// 0 is no fog, 1 is exponential with the density in params.x, 2 is linear from params.x to
+// params.y units away
+uniform int fog_mode;
+uniform vec3 fog_color;
+uniform vec2 fog_params;
+// Replaces texture colors by a ramp over texel density, interpolated in log scale
+uniform bool density_view;
+uniform vec2 density_range;
+uniform vec3 density_low_color;
+uniform vec3 density_high_color;
+// Replaces texture colors by hashed colors of leaves
+uniform bool leaf_colors;
 
 const bool BILINEAR = true;
+const uint NO_LIGHTMAP = 0xFFFFFFFFu;
+const uint NO_STYLE = 255u;
 
-vec4 sample_lightmap(in vec2 uv) {
-    int offset = int(o_lightmap_offset + floor(uv.y) * o_lightmap_size.x + floor(uv.x));
-    return texelFetch(lightmap, offset);
+vec3 srgb_to_linear(in vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
 }
 
-vec4 sample_bilinear_lightmap(in vec2 uv) {
-    vec2 uv2 = min(uv + 1, o_lightmap_size);
+vec3 linear_to_srgb(in vec3 c) {
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+// Stable color of a leaf, hashed so neighbouring leaves differ, gray for no leaf
+vec3 leaf_color(in uint leaf) {
+    if (leaf == 0u) {
+        return vec3(0.5);
+    }
+    uint h = leaf * 0x9E3779B1u;
+    h ^= h >> 15;
+    h *= 0x85EBCA77u;
+    h ^= h >> 13;
+    return 0.25 + 0.75 * vec3(uvec3(h, h >> 8, h >> 16) & 0xFFu) / 255.0;
+}
 
-    vec4 tl = sample_lightmap(uv);
-    vec4 tr = sample_lightmap(vec2(uv2.x, uv.y));
-    vec4 bl = sample_lightmap(vec2(uv.x, uv2.y));
-    vec4 br = sample_lightmap(uv2); 
+// Luxels are decoded one by one, so the bilinear interpolation is linear too
+vec4 sample_lightmap(in vec2 uv, in uint lightmap_offset) {
+    int offset = int(lightmap_offset + floor(uv.y) * o_lightmap_size.x + floor(uv.x));
+    vec4 luxel = texelFetch(lightmap, offset);
+    if (srgb) {
+        luxel.rgb = srgb_to_linear(luxel.rgb);
+    }
+    return luxel;
+}
+
+vec4 sample_bilinear_lightmap(in vec2 uv, in uint offset) {
+    vec2 uv2 = min(uv + 1.0, vec2(o_lightmap_size - 1u));
+
+    vec4 tl = sample_lightmap(uv, offset);
+    vec4 tr = sample_lightmap(vec2(uv2.x, uv.y), offset);
+    vec4 bl = sample_lightmap(vec2(uv.x, uv2.y), offset);
+    vec4 br = sample_lightmap(uv2, offset); 
 
     vec2 f = fract(uv);
     vec4 tA = mix(tl, tr, f.x);
@@ -31,12 +93,66 @@ vec4 sample_bilinear_lightmap(in vec2 uv) {
     return mix(tA, tB, f.y);
 }
 
+// Lightmaps of the face's styles follow each other
+vec4 sample_styled_lightmaps(in vec2 uv) {
+    vec3 light = vec3(0.0);
+    uint offset = o_lightmap_offset;
+    for (int i = 0; i < 4 && o_light_styles[i] != NO_STYLE; i++) {
+        float brightness = texelFetch(light_styles, int(o_light_styles[i])).r;
+        if (BILINEAR) {
+            light += brightness * sample_bilinear_lightmap(uv, offset).rgb;
+        } else {
+            light += brightness * sample_lightmap(uv, offset).rgb;
+        }
+        offset += o_lightmap_size.x * o_lightmap_size.y;
+    }
+    return vec4(light, 1.0);
+}
+
 void main() {
-    vec4 color = texture(colormap, o_tex_coords / textureSize(colormap, 0));
-    if (BILINEAR) {
-        color *= sample_bilinear_lightmap(o_light_tex_coords);
+    if (overlay_color.a > 0) {
+        gl_FragColor = overlay_color;
+        return;
+    }
+
+    vec2 uv = o_tex_coords + uv_scroll;
+    if (water_warp) {
+        uv += 8.0 * sin(uv.yx * 0.125 + warp_time);
+    }
+    vec4 color = texture(colormap, uv / texture_size);
+    if (density_view) {
+        vec2 range = log2(density_range);
+        float t = clamp((log2(o_texel_density) - range.x) / (range.y - range.x), 0.0, 1.0);
+        color.rgb = mix(density_low_color, density_high_color, t);
+    }
+    if (leaf_colors) {
+        color.rgb = leaf_color(o_leaf);
+    }
+    vec4 light;
+    if (o_lightmap_offset == NO_LIGHTMAP) {
+        light = vec4(1.0);
     } else {
-        color *= sample_lightmap(o_light_tex_coords);
+        light = sample_styled_lightmaps(o_light_tex_coords);
+    }
+    if (alpha_test && color.a < 0.5) {
+        discard;
+    }
+    if (srgb) {
+        color.rgb = srgb_to_linear(color.rgb);
+    }
+    color.rgb *= max(light.rgb, vec3(emissive)) * tint;
+    if (srgb) {
+        color.rgb = linear_to_srgb(color.rgb);
+    }
+    if (gamma != 1.0) {
+        color.rgb = pow(color.rgb, vec3(1.0 / gamma));
+    }
+    if (fog_mode == 1) {
+        color.rgb = mix(fog_color, color.rgb, exp(-fog_params.x * o_view_depth));
+    } else if (fog_mode == 2) {
+        float visibility = (fog_params.y - o_view_depth) / (fog_params.y - fog_params.x);
+        color.rgb = mix(fog_color, color.rgb, clamp(visibility, 0.0, 1.0));
     }
+    color.a *= opacity;
     gl_FragColor = color;
 }
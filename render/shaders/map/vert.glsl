@@ -6,16 +6,25 @@ in vec2 light_tex_coords;
 
 in uint lightmap_offset;
 in uvec2 lightmap_size;
+in uvec4 light_styles;
 
 in vec3 normal;
+in float texel_density;
+in uint leaf;
 
 out vec2 o_tex_coords;
 out vec2 o_light_tex_coords;
 
 flat out uint o_lightmap_offset;
 flat out uvec2 o_lightmap_size;
+flat out uvec4 o_light_styles;
+flat out float o_texel_density;
+flat out uint o_leaf;
+// Distance along the view direction, for fog
+out float o_view_depth;
 
 uniform mat4 mvp;
+uniform mat4 model;
 uniform vec3 origin;
 
 const mat4 ROTATE_MAT = mat4(
@@ -30,6 +39,11 @@ void main() {
     o_light_tex_coords = light_tex_coords;
     o_lightmap_offset = lightmap_offset;
     o_lightmap_size = lightmap_size;
+    o_light_styles = light_styles;
+    o_texel_density = texel_density;
+    o_leaf = leaf;
 
-    gl_Position = mvp * ROTATE_MAT * vec4(origin + position, 1.0);
+    gl_Position = mvp * ROTATE_MAT * model * vec4(origin + position, 1.0);
+    // W of a perspective projection is the view space depth
+    o_view_depth = gl_Position.w;
 }
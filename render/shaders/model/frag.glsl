@@ -0,0 +1,39 @@
+#version 140
+
+in vec2 o_tex_coords;
+in vec3 o_normal;
+in float o_view_depth;
+
+out vec4 out_color;
+
+uniform sampler2D colormap;
+// Drop transparent pixels of masked textures
+uniform bool alpha_test;
+// Same as of the map shader
+uniform float gamma;
+uniform int fog_mode;
+uniform vec3 fog_color;
+uniform vec2 fog_params;
+
+// Light falling from above and a bit aside, so vertical sides differ
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.3, 0.2, 1.0));
+const float AMBIENT = 0.5;
+
+void main() {
+    vec4 color = texture(colormap, o_tex_coords);
+    if (alpha_test && color.a < 0.5) {
+        discard;
+    }
+    float diffuse = max(dot(normalize(o_normal), LIGHT_DIRECTION), 0.0);
+    color.rgb *= AMBIENT + (1.0 - AMBIENT) * diffuse;
+    if (gamma != 1.0) {
+        color.rgb = pow(color.rgb, vec3(1.0 / gamma));
+    }
+    if (fog_mode == 1) {
+        color.rgb = mix(fog_color, color.rgb, exp(-fog_params.x * o_view_depth));
+    } else if (fog_mode == 2) {
+        float visibility = (fog_params.y - o_view_depth) / (fog_params.y - fog_params.x);
+        color.rgb = mix(fog_color, color.rgb, clamp(visibility, 0.0, 1.0));
+    }
+    out_color = color;
+}
@@ -0,0 +1,24 @@
+#version 140
+
+in vec3 position;
+in vec3 normal;
+in vec2 tex_coords;
+
+out vec2 o_tex_coords;
+// In map's coordinates, Z is up
+out vec3 o_normal;
+// Distance along the view direction, for fog
+out float o_view_depth;
+
+uniform mat4 mvp;
+// Placement of the model in the map, only rotates normals
+uniform mat4 model;
+
+void main() {
+    o_tex_coords = tex_coords;
+    o_normal = mat3(model) * normal;
+
+    gl_Position = mvp * vec4(position, 1.0);
+    // W of a perspective projection is the view space depth
+    o_view_depth = gl_Position.w;
+}
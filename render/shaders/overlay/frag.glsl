@@ -0,0 +1,9 @@
+#version 140
+
+out vec4 out_color;
+
+uniform vec4 color;
+
+void main() {
+    out_color = color;
+}
@@ -0,0 +1,11 @@
+#version 140
+
+in vec2 position;
+
+// Size of the target in pixels, positions go from its top left corner
+uniform vec2 screen_size;
+
+void main() {
+    vec2 ndc = position / screen_size * vec2(2.0, -2.0) + vec2(-1.0, 1.0);
+    gl_Position = vec4(ndc, 0.0, 1.0);
+}
@@ -5,8 +5,11 @@ in vec3 position;
 out vec3 reflect_dir;
 
 uniform mat4 mvp;
+// Depth of the far plane in NDC, -1 when reversed-Z is used
+uniform float far_depth;
 
 void main() {
     reflect_dir = position;
-    gl_Position = (mvp * vec4(position, 1.0)).xyww;
+    vec4 pos = mvp * vec4(position, 1.0);
+    gl_Position = vec4(pos.xy, far_depth * pos.w, pos.w);
 }
@@ -0,0 +1,24 @@
+#version 140
+
+in vec2 o_tex_coords;
+in float o_view_depth;
+
+out vec4 out_color;
+
+uniform sampler2D colormap;
+// `rendercolor` and `renderamt` of the entity
+uniform vec4 color;
+// Same as of the map shader, fog fades sprites out instead of tinting them, as additive ones
+// would only get brighter
+uniform int fog_mode;
+uniform vec2 fog_params;
+
+void main() {
+    vec4 texel = texture(colormap, o_tex_coords) * color;
+    if (fog_mode == 1) {
+        texel.a *= exp(-fog_params.x * o_view_depth);
+    } else if (fog_mode == 2) {
+        texel.a *= clamp((fog_params.y - o_view_depth) / (fog_params.y - fog_params.x), 0.0, 1.0);
+    }
+    out_color = texel;
+}
@@ -0,0 +1,23 @@
+#version 140
+
+in vec2 corner;
+
+out vec2 o_tex_coords;
+// Distance along the view direction, for fog
+out float o_view_depth;
+
+uniform mat4 projection;
+// Origin of the sprite and its axes, all in the view space
+uniform vec3 center;
+uniform vec3 right;
+uniform vec3 up;
+// Top left corner of the frame from the origin and its size, in units with Y going up
+uniform vec2 frame_origin;
+uniform vec2 frame_size;
+
+void main() {
+    o_tex_coords = corner;
+    vec2 offset = frame_origin + vec2(corner.x, -corner.y) * frame_size;
+    gl_Position = projection * vec4(center + right * offset.x + up * offset.y, 1.0);
+    o_view_depth = gl_Position.w;
+}
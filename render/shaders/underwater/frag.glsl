@@ -0,0 +1,18 @@
+#version 140
+
+in vec2 o_uv;
+
+out vec4 out_color;
+
+uniform sampler2D scene;
+uniform float time;
+// Part of the screen the image is shifted by at most
+uniform float warp_amount;
+uniform vec3 tint;
+uniform float tint_amount;
+
+void main() {
+    vec2 uv = o_uv + warp_amount * sin(o_uv.yx * 25.0 + time * 2.0);
+    vec3 color = texture(scene, uv).rgb;
+    out_color = vec4(mix(color, tint, tint_amount), 1.0);
+}
@@ -0,0 +1,10 @@
+#version 140
+
+in vec2 position;
+
+out vec2 o_uv;
+
+void main() {
+    o_uv = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 140
+
+out vec4 out_color;
+
+// Squares are of this many pixels, every other one is `bright`
+uniform float cell_size;
+uniform vec3 color;
+uniform vec3 bright;
+
+void main() {
+    ivec2 cell = ivec2(floor(gl_FragCoord.xy / cell_size));
+    out_color = vec4((cell.x + cell.y) % 2 == 0 ? color : bright, 1.0);
+}
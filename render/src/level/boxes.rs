@@ -0,0 +1,100 @@
+use super::entities::{get_point_entities, PointEntity};
+use file::bsp::RawMap;
+use glium::{
+    backend::Facade, implement_vertex, index::NoIndices, index::PrimitiveType, program, uniform,
+    Depth, DrawParameters, Program, Surface, VertexBuffer,
+};
+
+/// Half of the size of the boxes, entities have no sizes in the entities lump.
+const HALF_SIZE: f32 = 8.0;
+/// The darkest a channel of the color of a classname may be, so boxes stay visible.
+const MIN_CHANNEL: f32 = 0.25;
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+implement_vertex!(Vertex, position, color);
+
+/// Color of a classname, hashed with FNV-1a so it's the same every run.
+fn classname_color(classname: &str) -> [f32; 3] {
+    let hash = classname.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    let channel = |shift: u32| {
+        let value = ((hash >> shift) & 0xFF) as f32 / 255.0;
+        MIN_CHANNEL + (1.0 - MIN_CHANNEL) * value
+    };
+    [channel(0), channel(8), channel(16)]
+}
+
+/// Lines of the box's 12 edges.
+fn box_lines(entity: &PointEntity) -> impl Iterator<Item = Vertex> {
+    let (x, y, z) = entity.origin;
+    let color = classname_color(&entity.classname);
+    let corner = move |i: usize| Vertex {
+        position: [
+            x + if i & 1 == 0 { -HALF_SIZE } else { HALF_SIZE },
+            y + if i & 2 == 0 { -HALF_SIZE } else { HALF_SIZE },
+            z + if i & 4 == 0 { -HALF_SIZE } else { HALF_SIZE },
+        ],
+        color,
+    };
+    // Corners differing in one bit of their indices share an edge
+    (0..8)
+        .flat_map(|i| [1, 2, 4].iter().map(move |bit| (i, i | bit)))
+        .filter(|(a, b)| a != b)
+        .flat_map(move |(a, b)| [corner(a), corner(b)])
+}
+
+/// Wireframe boxes at origins of point entities colored by their classnames, to find ones
+/// which aren't drawn, like lights or sounds.
+pub struct EntityBoxes {
+    vbo: VertexBuffer<Vertex>,
+    program: Program,
+}
+
+impl EntityBoxes {
+    pub fn new<F: ?Sized + Facade>(facade: &F, map: &RawMap) -> Self {
+        let vertices: Vec<_> = get_point_entities(map).iter().flat_map(box_lines).collect();
+        let program = program!(facade,
+            140 => {
+                vertex: include_str!("../../shaders/lines/vert.glsl"),
+                fragment: include_str!("../../shaders/lines/frag.glsl"),
+            },
+        )
+        .unwrap();
+        Self {
+            vbo: VertexBuffer::new(facade, &vertices).unwrap(),
+            program,
+        }
+    }
+
+    /// `mvp` transforms coordinates of the map, see `Map::to_view_transform`. Boxes are
+    /// hidden behind walls like everything else, but don't hide anything themselves.
+    pub fn render<S: Surface>(
+        &self,
+        surface: &mut S,
+        mvp: [[f32; 4]; 4],
+        draw_params: &DrawParameters,
+    ) {
+        let draw_params = DrawParameters {
+            depth: Depth {
+                write: false,
+                ..draw_params.depth
+            },
+            ..draw_params.clone()
+        };
+        surface
+            .draw(
+                &self.vbo,
+                NoIndices(PrimitiveType::LinesList),
+                &self.program,
+                &uniform! { mvp: mvp },
+                &draw_params,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use cgmath::{InnerSpace, Vector3};
+
+/// Decals stick to faces at most that far from their origins, like after the short trace
+/// the game does from the entity.
+const MAX_DISTANCE: f32 = 8.0;
+/// Points this close outside of an edge still count as inside of the polygon.
+const EDGE_EPSILON: f32 = 0.01;
+
+type Vec3 = Vector3<f32>;
+
+/// Where a decal sits on a face, in the map space.
+pub struct DecalSpot {
+    /// Origin of the decal projected onto the face.
+    pub center: Vec3,
+    /// Unit texture axes of the face in its plane, the decal is laid along them.
+    pub s_axis: Vec3,
+    pub t_axis: Vec3,
+}
+
+/// Sum of cross products of the polygon's edges, goes along its normal when it's wound
+/// counter-clockwise looking from the front.
+fn winding(positions: &[Vec3]) -> Vec3 {
+    positions
+        .iter()
+        .zip(positions.iter().cycle().skip(1))
+        .map(|(a, b)| a.cross(*b))
+        .sum()
+}
+
+/// Signed distance of a point to the line through the edge `a` to `b` in the plane, positive
+/// on the left looking from the front.
+fn edge_distance(a: Vec3, b: Vec3, normal: Vec3, point: Vec3) -> f32 {
+    let edge = b - a;
+    edge.cross(point - a).dot(normal) / edge.magnitude().max(f32::EPSILON)
+}
+
+/// Signed distances of a point to the edges of a convex polygon in its plane, positive inside.
+fn edge_distances<'a>(
+    positions: &'a [Vec3],
+    normal: Vec3,
+    point: Vec3,
+) -> impl Iterator<Item = f32> + 'a {
+    let orientation = winding(positions).dot(normal).signum();
+    positions
+        .iter()
+        .zip(positions.iter().cycle().skip(1))
+        .map(move |(&a, &b)| orientation * edge_distance(a, b, normal, point))
+}
+
+/// Index of the face nearest to `origin` whose polygon contains the origin projected onto it.
+/// Faces are given by their front normals and positions. Faces farther than `MAX_DISTANCE`
+/// are never chosen.
+pub fn find_face<'a, I: IntoIterator<Item = (Vec3, &'a [Vec3])>>(
+    origin: Vec3,
+    faces: I,
+) -> Option<usize> {
+    faces
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, (normal, positions))| {
+            let distance = (origin - *positions.first()?).dot(normal);
+            let projected = origin - normal * distance;
+            let inside = edge_distances(positions, normal, projected).all(|d| d >= -EDGE_EPSILON);
+            Some((i, distance.abs())).filter(|_| inside && distance.abs() <= MAX_DISTANCE)
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+impl DecalSpot {
+    /// Projects `origin` onto the plane of a face with texture axes `s` and `t`.
+    pub fn new(origin: Vec3, face_point: Vec3, normal: Vec3, s: Vec3, t: Vec3) -> Self {
+        let in_plane = |v: Vec3| (v - normal * v.dot(normal)).normalize();
+        Self {
+            center: origin - normal * (origin - face_point).dot(normal),
+            s_axis: in_plane(s),
+            t_axis: in_plane(t),
+        }
+    }
+
+    /// Polygon of a decal of `width` x `height` texels, one per unit, centered on the spot and
+    /// clipped to the face. Points go with their texture coordinates in texels and are wound
+    /// like the face, so they're culled the same way.
+    pub fn clip_to_face(
+        &self,
+        positions: &[Vec3],
+        normal: Vec3,
+        width: f32,
+        height: f32,
+    ) -> Vec<(Vec3, [f32; 2])> {
+        let corner = self.center - self.s_axis * (width / 2.0) - self.t_axis * (height / 2.0);
+        let mut polygon = vec![
+            corner,
+            corner + self.s_axis * width,
+            corner + self.s_axis * width + self.t_axis * height,
+            corner + self.t_axis * height,
+        ];
+        // Sutherland-Hodgman against each edge of the convex face
+        let orientation = winding(positions).dot(normal).signum();
+        for (&start, &end) in positions.iter().zip(positions.iter().cycle().skip(1)) {
+            let distance = |p| orientation * edge_distance(start, end, normal, p);
+            let mut clipped = Vec::with_capacity(polygon.len() + 1);
+            for (&a, &b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+                let (da, db) = (distance(a), distance(b));
+                if da >= 0.0 {
+                    clipped.push(a);
+                }
+                if (da >= 0.0) != (db >= 0.0) {
+                    clipped.push(a + (b - a) * (da / (da - db)));
+                }
+            }
+            polygon = clipped;
+            if polygon.len() < 3 {
+                return vec![];
+            }
+        }
+
+        if winding(&polygon).dot(winding(positions)) < 0.0 {
+            polygon.reverse();
+        }
+        polygon
+            .into_iter()
+            .map(|p| {
+                let offset = p - corner;
+                (p, [offset.dot(self.s_axis), offset.dot(self.t_axis)])
+            })
+            .collect()
+    }
+}
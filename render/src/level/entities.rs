@@ -1,31 +1,137 @@
-use file::{
-    bsp::lumps::parse_entities_str,
-    map::{Entities, Entity},
-};
+use file::{bsp::RawMap, map::Entity};
 
-const INFO_PLAYER_START_CLASSNAME: &str = "info_player_start";
+/// Entities the player may spawn at, in the order of preference.
+const PLAYER_START_CLASSNAMES: [&str; 3] =
+    ["info_player_start", "info_player_deathmatch", "worldspawn"];
+/// Sky of maps which don't set `skyname`, the same as the game uses.
+const DEFAULT_SKYNAME: &str = "desert";
 
 pub type Vec3 = (f32, f32, f32);
 
-pub fn parse_entities(i: &[u8]) -> Option<Entities> {
-    let s = parse_entities_str(i).ok()?; // TODO : do not ok
-    Entities::parse(s).ok() // TODO : same as above
+/// The `rendermode` key of entities.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderMode {
+    Normal,
+    /// Blended by `renderamt`, the game also fills it with `rendercolor`.
+    Color,
+    /// Blended by `renderamt`.
+    Texture,
+    /// Additive like `Additive`, meant for sprites.
+    Glow,
+    /// Opaque except for transparent pixels of the texture.
+    Solid,
+    /// Added to what's behind it, scaled by `renderamt`.
+    Additive,
 }
 
-pub fn get_skyname(entities: &Entities) -> Option<String> {
-    entities
-        .entities()
-        .iter()
-        .find_map(|e| e.properties().get("skyname"))
-        .map(|e| e.to_string())
+impl RenderMode {
+    fn from_key(value: &str) -> Self {
+        match value.trim().parse() {
+            Ok(1) => Self::Color,
+            Ok(2) => Self::Texture,
+            Ok(3) => Self::Glow,
+            Ok(4) => Self::Solid,
+            Ok(5) => Self::Additive,
+            _ => Self::Normal,
+        }
+    }
+
+    /// Drawn after opaque geometry, blended with what's behind.
+    pub const fn is_translucent(self) -> bool {
+        matches!(
+            self,
+            Self::Color | Self::Texture | Self::Glow | Self::Additive
+        )
+    }
+}
+
+/// Entity drawn with one of the map's submodels, e.g. a door or water.
+pub struct BrushEntity {
+    pub model: usize,
+    pub origin: Vec3,
+    pub render_mode: RenderMode,
+    /// `renderamt` scaled to 0..1.
+    pub render_amount: f32,
+}
+
+/// Fog blending distant geometry into its color.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fog {
+    /// RGB from 0 to 1.
+    pub color: [f32; 3],
+    pub falloff: FogFalloff,
 }
 
-pub fn find_info_player_start<'a>(entities: &'a Entities) -> Option<&'a Entity<'a>> {
-    entities.entities().iter().find(|e| {
-        e.properties()
-            .get("classname")
-            .filter(|&classname| classname == &INFO_PLAYER_START_CLASSNAME)
-            .is_some()
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FogFalloff {
+    /// Visibility is `exp(-density * distance)`.
+    Exponential { density: f32 },
+    /// Fog thickens from none at `start` units away to full at `end`.
+    Linear { start: f32, end: f32 },
+}
+
+impl Fog {
+    /// How much of the color of geometry `distance` units away stays, 1 means unfogged.
+    /// The map shader does the same.
+    pub fn visibility(&self, distance: f32) -> f32 {
+        match self.falloff {
+            FogFalloff::Exponential { density } => (-density * distance).exp(),
+            FogFalloff::Linear { start, end } => ((end - distance) / (end - start)).clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// `infodecal` entity, a decal texture stuck to the wall nearest to its origin.
+pub struct DecalEntity {
+    pub origin: Vec3,
+    pub texture: String,
+}
+
+/// Entity placed at a point rather than drawn with a submodel, e.g. a light or a sound.
+pub struct PointEntity {
+    pub classname: String,
+    pub origin: Vec3,
+}
+
+/// Entity drawn with a studio model, e.g. a monster or an `env_model`.
+pub struct ModelEntity {
+    /// Path of the `.mdl` file relative to the game directory, e.g. `models/scientist.mdl`.
+    pub model: String,
+    pub origin: Vec3,
+    /// Pitch, yaw and roll in degrees.
+    pub angles: Vec3,
+}
+
+/// Entity drawn with a sprite, e.g. an `env_sprite` or `env_glow`.
+pub struct SpriteEntity {
+    /// Path of the `.spr` file relative to the game directory, e.g. `sprites/flare1.spr`.
+    pub model: String,
+    pub origin: Vec3,
+    /// Size of the sprite relative to its frames, one texel per unit.
+    pub scale: f32,
+    pub render_mode: RenderMode,
+    /// `renderamt` scaled to 0..1, only blended render modes take it.
+    pub render_amount: f32,
+    /// `rendercolor` scaled to 0..1, white if it's black or missing like in the game.
+    pub render_color: [f32; 3],
+    /// Frames shown per second.
+    pub framerate: f32,
+}
+
+pub fn get_skyname(map: &RawMap) -> String {
+    map.first_entity("worldspawn")
+        .and_then(|e| e.get("skyname"))
+        .unwrap_or(DEFAULT_SKYNAME)
+        .to_string()
+}
+
+/// Spawn of single player, otherwise of deathmatch, otherwise worldspawn if it has an origin.
+pub fn find_player_start<'a>(map: &'a RawMap) -> Option<&'a Entity<'a>> {
+    PLAYER_START_CLASSNAMES.iter().find_map(|&classname| {
+        map.entities()
+            .entities()
+            .iter()
+            .find(|e| e.classname() == Some(classname) && e.get("origin").is_some())
     })
 }
 
@@ -35,8 +141,183 @@ fn parse_vector3(s: &str) -> Option<Vec3> {
 }
 
 pub fn get_start_point(entity: &Entity) -> Option<Vec3> {
+    entity.get("origin").and_then(parse_vector3)
+}
+
+/// The `angles` (pitch, yaw and roll in degrees) or `angle` (yaw only) key.
+fn get_angles(entity: &Entity) -> Option<Vec3> {
+    entity.get("angles").and_then(parse_vector3).or_else(|| {
+        let yaw = entity.get("angle")?.trim().parse().ok()?;
+        Some((0.0, yaw, 0.0))
+    })
+}
+
+/// Unit vector the entity faces by its angles, see `get_angles`. Positive pitch looks down,
+/// yaw turns from X to Y.
+pub fn get_start_direction(entity: &Entity) -> Option<Vec3> {
+    let (pitch, yaw, _) = get_angles(entity)?;
+    let (pitch, yaw): (f32, f32) = (pitch.to_radians(), yaw.to_radians());
+    Some((
+        pitch.cos() * yaw.cos(),
+        pitch.cos() * yaw.sin(),
+        -pitch.sin(),
+    ))
+}
+
+fn get_render_mode(entity: &Entity) -> RenderMode {
+    entity
+        .get("rendermode")
+        .map_or(RenderMode::Normal, RenderMode::from_key)
+}
+
+/// `renderamt` scaled to 0..1, 1 if there's none.
+fn get_render_amount(entity: &Entity) -> f32 {
     entity
-        .properties()
-        .get("origin")
-        .and_then(|o| parse_vector3(o))
+        .get("renderamt")
+        .and_then(|amount| amount.trim().parse::<f32>().ok())
+        .map_or(1.0, |amount| (amount / 255.0).clamp(0.0, 1.0))
+}
+
+/// Entities with `model` keys like `*1`, referencing submodels of the map.
+pub fn get_brush_entities(map: &RawMap) -> Vec<BrushEntity> {
+    map.entities()
+        .entities()
+        .iter()
+        .filter_map(|entity| {
+            let model = entity.get("model")?.strip_prefix('*')?.parse().ok()?;
+            let origin = entity
+                .get("origin")
+                .and_then(parse_vector3)
+                .unwrap_or((0.0, 0.0, 0.0));
+            Some(BrushEntity {
+                model,
+                origin,
+                render_mode: get_render_mode(entity),
+                render_amount: get_render_amount(entity),
+            })
+        })
+        .collect()
+}
+
+/// Entities with origins which don't reference submodels of the map.
+pub fn get_point_entities(map: &RawMap) -> Vec<PointEntity> {
+    map.entities()
+        .entities()
+        .iter()
+        .filter(|entity| {
+            !entity
+                .get("model")
+                .is_some_and(|model| model.starts_with('*'))
+        })
+        .filter_map(|entity| {
+            Some(PointEntity {
+                classname: entity.classname()?.to_string(),
+                origin: entity.get("origin").and_then(parse_vector3)?,
+            })
+        })
+        .collect()
+}
+
+/// Entities with `model` keys of `.mdl` files. Monsters without the key are drawn with models
+/// chosen by the game code, so they're left out.
+pub fn get_model_entities(map: &RawMap) -> Vec<ModelEntity> {
+    map.entities()
+        .entities()
+        .iter()
+        .filter_map(|entity| {
+            let model = entity.get("model")?;
+            if !model.to_ascii_lowercase().ends_with(".mdl") {
+                return None;
+            }
+            Some(ModelEntity {
+                model: model.to_string(),
+                origin: entity.get("origin").and_then(parse_vector3)?,
+                angles: get_angles(entity).unwrap_or((0.0, 0.0, 0.0)),
+            })
+        })
+        .collect()
+}
+
+/// Entities with `model` keys of `.spr` files. Zero scales and framerates are taken as the
+/// defaults 1 and 10, as the game does.
+pub fn get_sprite_entities(map: &RawMap) -> Vec<SpriteEntity> {
+    map.entities()
+        .entities()
+        .iter()
+        .filter_map(|entity| {
+            let model = entity.get("model")?;
+            if !model.to_ascii_lowercase().ends_with(".spr") {
+                return None;
+            }
+            let number = |key, default| {
+                entity
+                    .get(key)
+                    .and_then(|value| value.trim().parse::<f32>().ok())
+                    .filter(|&value| value > 0.0)
+                    .unwrap_or(default)
+            };
+            let render_color = entity
+                .get("rendercolor")
+                .and_then(parse_vector3)
+                .filter(|&color| color != (0.0, 0.0, 0.0))
+                .map_or([1.0; 3], |(r, g, b)| [r / 255.0, g / 255.0, b / 255.0]);
+            let render_mode = get_render_mode(entity);
+            Some(SpriteEntity {
+                model: model.to_string(),
+                origin: entity.get("origin").and_then(parse_vector3)?,
+                scale: number("scale", 1.0),
+                render_mode,
+                render_amount: if render_mode == RenderMode::Normal {
+                    1.0
+                } else {
+                    get_render_amount(entity)
+                },
+                render_color,
+                framerate: number("framerate", 10.0),
+            })
+        })
+        .collect()
+}
+
+/// Decals placed by the mapper, the ones of `infodecal` entities which have a texture.
+pub fn get_decals(map: &RawMap) -> Vec<DecalEntity> {
+    map.entities()
+        .entities()
+        .iter()
+        .filter(|entity| entity.classname() == Some("infodecal"))
+        .filter_map(|entity| {
+            Some(DecalEntity {
+                origin: entity.get("origin").and_then(parse_vector3)?,
+                texture: entity.get("texture")?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Fog of an `env_fog` entity (`rendercolor`, then `density` or `startdist` and `enddist`),
+/// otherwise of worldspawn's `fog_color`, `fog_density`, `fog_start` and `fog_end` keys.
+/// Colors are 0 to 255.
+pub fn get_fog(map: &RawMap) -> Option<Fog> {
+    let fog_of = |entity: &Entity, [color, density, start, end]: [&str; 4]| {
+        let (r, g, b) = entity.get(color).and_then(parse_vector3)?;
+        let number = |key| entity.get(key)?.trim().parse::<f32>().ok();
+        let falloff = match (number(density), number(start), number(end)) {
+            (Some(density), _, _) if density > 0.0 => FogFalloff::Exponential { density },
+            (_, Some(start), Some(end)) if start >= 0.0 && start < end => {
+                FogFalloff::Linear { start, end }
+            }
+            _ => return None,
+        };
+        Some(Fog {
+            color: [r / 255.0, g / 255.0, b / 255.0],
+            falloff,
+        })
+    };
+    map.first_entity("env_fog")
+        .and_then(|entity| fog_of(entity, ["rendercolor", "density", "startdist", "enddist"]))
+        .or_else(|| {
+            map.first_entity("worldspawn").and_then(|entity| {
+                fog_of(entity, ["fog_color", "fog_density", "fog_start", "fog_end"])
+            })
+        })
 }
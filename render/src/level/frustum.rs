@@ -0,0 +1,34 @@
+use cgmath::{Matrix4, Vector4};
+
+/// View volume as six inward-facing planes `(a, b, c, d)`, so a point is inside when
+/// `a * x + b * y + c * z + d >= 0` holds for all of them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts planes of the clip volume from the combined model-view-projection matrix,
+    /// so they're in the space the matrix transforms from.
+    pub fn from_matrix(mvp: Matrix4<f32>) -> Self {
+        let row = |i: usize| Vector4::new(mvp.x[i], mvp.y[i], mvp.z[i], mvp.w[i]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Whether an axis-aligned box is at least partially inside. Boxes near corners of
+    /// the frustum may be reported inside even when they aren't.
+    pub fn contains_aabb(&self, mins: [f32; 3], maxs: [f32; 3]) -> bool {
+        self.planes.iter().all(|p| {
+            // The box corner farthest along the plane's normal
+            let corner = [
+                if p.x >= 0.0 { maxs[0] } else { mins[0] },
+                if p.y >= 0.0 { maxs[1] } else { mins[1] },
+                if p.z >= 0.0 { maxs[2] } else { mins[2] },
+            ];
+            p.x * corner[0] + p.y * corner[1] + p.z * corner[2] + p.w >= 0.0
+        })
+    }
+}
@@ -1,41 +1,77 @@
-use cgmath::Matrix4;
+use super::{
+    decal::{find_face, DecalSpot},
+    entities::{get_brush_entities, get_decals, Fog, FogFalloff, RenderMode},
+    frustum::Frustum,
+};
+use cgmath::{vec3, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
 use elapsed::measure_time;
 use file::{
-    bsp::{lumps::*, LumpType, RawMap},
-    miptex::MipTexture,
-    wad::Archive,
+    bsp::{
+        contents::Contents,
+        face_normal,
+        lightstyle::{style_brightness, MAX_FACE_STYLES, STYLES_NUM},
+        lumps::*,
+        merge::{merge_coplanar, polygon_area},
+        trace::{Hull, TraceResult},
+        uv::{luxel_extents, texel_uv},
+        vis::{decompress_vis, find_leaf},
+        LumpType, RawMap,
+    },
+    miptex::{animation_chains, AnimationChain, AnimationFrame, MipTexture, MASKED_PREFIX},
+    replacement::ReplacementDir,
+    wad::{Archive, StreamedArchive},
 };
 use glium::{
-    backend::Facade,
+    backend::{Context, Facade},
+    draw_parameters::PolygonOffset,
     implement_vertex,
-    index::{IndexBuffer, IndexBufferAny, PrimitiveType},
+    index::{IndexBuffer, IndexBufferAny, IndicesSource, NoIndices, PrimitiveType},
     program,
     texture::{
         buffer_texture::{BufferTexture, BufferTextureType},
         MipmapsOption, RawImage2d, Texture2d,
     },
     uniform,
-    uniforms::MinifySamplerFilter,
-    vertex::{VertexBuffer, VertexBufferAny},
-    DrawParameters, Program, Rect, Surface,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler},
+    vertex::{MultiVerticesSource, VertexBuffer, VertexBufferAny},
+    BackfaceCullingMode, Blend, BlendingFunction, Depth, DepthTest, DrawParameters,
+    LinearBlendingFactor, PolygonMode, Program, Rect, Surface,
 };
 use itertools::Itertools;
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
     collections::{HashMap, HashSet},
-    iter::Iterator,
+    iter::{self, Iterator},
+    mem,
+    ops::Range,
+    rc::Rc,
+    str::FromStr,
 };
 
-const TRANSPARENT_TEXTURES: [&str; 2] = ["sky", "aaatrigger"];
+const TRANSPARENT_TEXTURES: [&str; 1] = ["aaatrigger"];
+/// Faces with this texture show the skybox instead.
+const SKY_TEXTURE: &str = "sky";
+/// Faces of less area, in square units, are counted as degenerate.
+const DEGENERATE_AREA: f32 = 0.01;
+/// Texels of a side of a square of the placeholder for missing textures.
+const PLACEHOLDER_CELL: u32 = 4;
 
 #[derive(Copy, Clone)]
 struct Vertex {
     position: [f32; 3],
+    /// In texels, the shader divides them by the texture's size, see `texel_uv`.
     tex_coords: [f32; 2],
     light_tex_coords: [f32; 2],
     lightmap_offset: u32,
     lightmap_size: [u32; 2],
+    /// Styles of the lightmaps following each other from `lightmap_offset`, see `NO_STYLE`.
+    light_styles: [u32; MAX_FACE_STYLES],
     normal: [f32; 3],
+    texel_density: f32,
+    /// First leaf of the world containing the face, 0 if there's none, e.g. for brush models.
+    leaf: u32,
 }
 
 implement_vertex!(
@@ -45,18 +81,12 @@ implement_vertex!(
     light_tex_coords,
     lightmap_offset,
     lightmap_size,
-    normal
+    light_styles,
+    normal,
+    texel_density,
+    leaf
 );
 
-#[inline]
-fn calculate_uvs(vertex: &Vec3, texinfo: &TexInfo) -> [f32; 2] {
-    let dot_product = |a: &Vec3, b: &Vec3| a.0 * b.0 + a.1 * b.1 + a.2 * b.2;
-    [
-        dot_product(vertex, &texinfo.vs) + texinfo.ss,
-        dot_product(vertex, &texinfo.vt) + texinfo.st,
-    ]
-}
-
 fn triangulate(vertices: Vec<usize>) -> Vec<usize> {
     let n = vertices.len();
     match n {
@@ -69,146 +99,926 @@ fn triangulate(vertices: Vec<usize>) -> Vec<usize> {
     }
 }
 
+/// Same as in the vertex shader, turns Z up map space into Y up view space.
+#[rustfmt::skip]
+const ROTATE_MAT: Matrix4<f32> = Matrix4::new(
+    0.0, 0.0, -1.0, 0.0,
+    -1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.0, 1.0,
+);
+
+const NO_OVERLAY: [f32; 4] = [0.0; 4];
+/// Values of the `fog_mode` uniform.
+const NO_FOG: i32 = 0;
+const EXPONENTIAL_FOG: i32 = 1;
+const LINEAR_FOG: i32 = 2;
+const NO_TINT: [f32; 3] = [1.0; 3];
+
+/// Values of the `fog_mode` and `fog_params` uniforms of the fog, shaders of studio models
+/// take them too.
+pub fn fog_uniforms(fog: Option<Fog>) -> (i32, [f32; 2]) {
+    match fog.map(|fog| fog.falloff) {
+        None => (NO_FOG, [0.0; 2]),
+        Some(FogFalloff::Exponential { density }) => (EXPONENTIAL_FOG, [density, 0.0]),
+        Some(FogFalloff::Linear { start, end }) => (LINEAR_FOG, [start, end]),
+    }
+}
+
+/// Texels per map unit, the geometric mean of both texture axes.
+fn texel_density(texinfo: &TexInfo) -> f32 {
+    let length = |v: &Vec3| (v.0 * v.0 + v.1 * v.1 + v.2 * v.2).sqrt();
+    (length(&texinfo.vs) * length(&texinfo.vt)).sqrt()
+}
+
+/// Coloring of faces by texel density for `Map::render`. Densities are interpolated in log
+/// scale, so the middle of `min` 0.5 and `max` 2 is the default scale 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DensityRamp {
+    /// Density in texels per map unit drawn with `low_color` (and all lower ones).
+    pub min: f32,
+    /// Density in texels per map unit drawn with `high_color` (and all higher ones).
+    pub max: f32,
+    pub low_color: [f32; 3],
+    pub high_color: [f32; 3],
+}
+
+impl Default for DensityRamp {
+    fn default() -> Self {
+        Self {
+            min: 0.5,
+            max: 2.0,
+            low_color: [0.0, 0.0, 1.0],
+            high_color: [1.0, 0.0, 0.0],
+        }
+    }
+}
+
+/// Frames per second of animated textures.
+const ANIMATION_FPS: f32 = 10.0;
+/// Textures with this prefix are water, lava and the like, drawn with a sine warp.
+const WATER_PREFIX: char = '!';
+/// Faces of masked and water textures are drawn from both sides: fences and grates are often
+/// single faces of thin brushes, and water surfaces are seen from below as well.
+fn is_two_sided(tex: &str) -> bool {
+    tex.starts_with(MASKED_PREFIX) || tex.starts_with(WATER_PREFIX)
+}
+
+/// Textures with this prefix move along their S axis, as on conveyors.
+const SCROLL_PREFIX: &str = "scroll";
+/// Texels per second of scrolling textures.
+const SCROLL_SPEED: f32 = 64.0;
+
+/// Selects frames of animated textures, see `AnimationFrame`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct TextureAnimation {
+    /// Seconds since any fixed moment, e.g. the start.
+    pub time: f32,
+    /// Show alternate sequences (`+a`) instead of the primary ones (`+0`) and vice versa.
+    pub toggled: bool,
+    /// Warp water and scroll conveyor textures.
+    pub surfaces: bool,
+}
+
+/// Selects leaves whose faces are drawn, see `Map::render`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct PvsOptions {
+    /// Leaf whose PVS is drawn instead of the camera's one, like `r_lockpvs` of the game.
+    pub locked_leaf: Option<usize>,
+    /// Seconds the PVS of the previous leaf is still drawn after the camera leaves it, so
+    /// faces don't pop in and out at leaf boundaries. 0 turns it off.
+    pub blend: f32,
+    /// Time in seconds `blend` is counted from.
+    pub time: f32,
+}
+
+/// Last leaf change of the camera, see `PvsOptions::blend`.
+#[derive(Debug, Default, Copy, Clone)]
+struct LeafTransition {
+    leaf: usize,
+    previous: usize,
+    since: f32,
+}
+
+/// Where a frame is seen from. `model` places the world model in the view space, and
+/// `camera_position` is in the view space and selects faces visible by the PVS.
+#[derive(Debug, Copy, Clone)]
+pub struct FrameView {
+    pub projection: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub model: Matrix4<f32>,
+    pub camera_position: [f32; 3],
+}
+
+/// Uniforms which differ between kinds of `Map::draw` calls.
+#[derive(Copy, Clone)]
+pub struct Shading {
+    pub filter: TextureFilter,
+    pub overlay_color: [f32; 4],
+    pub tint: [f32; 3],
+    pub density_ramp: Option<DensityRamp>,
+    /// Replace texture colors by colors of the leaves faces are in.
+    pub leaf_colors: bool,
+    /// Multiplies alpha of the color.
+    pub opacity: f32,
+    /// Discard pixels with low alpha instead of blending them.
+    pub alpha_test: bool,
+    pub animation: TextureAnimation,
+    /// Lit colors are raised to the power of its inverse.
+    pub gamma: f32,
+    /// Light textures in linear space, see `RenderSettings::srgb`.
+    pub srgb: bool,
+    /// Maximal anisotropy of `TextureFilter::Anisotropic`.
+    pub anisotropy: u16,
+    /// Textures are opacity gradients of decals, so masked ones aren't cut out.
+    pub decal: bool,
+    pub fog: Option<Fog>,
+}
+
+impl Shading {
+    pub const fn textured(filter: TextureFilter, animation: TextureAnimation) -> Self {
+        Self {
+            filter,
+            animation,
+            overlay_color: NO_OVERLAY,
+            tint: NO_TINT,
+            density_ramp: None,
+            leaf_colors: false,
+            opacity: 1.0,
+            alpha_test: false,
+            gamma: 1.0,
+            srgb: false,
+            anisotropy: MAX_ANISOTROPY,
+            decal: false,
+            fog: None,
+        }
+    }
+}
+
+/// Lighting lump offset of faces without a lightmap.
+const NO_LIGHTMAP: u32 = u32::MAX;
+
+struct FacePolygon {
+    tex_name: String,
+    /// Faces of the faces lump the polygon was built from.
+    face_ids: Vec<usize>,
+    plane_id: usize,
+    side: bool,
+    texinfo_id: usize,
+    positions: Vec<Vec3>,
+    lightmap: Option<usize>,
+    styles: [u8; MAX_FACE_STYLES],
+}
+
+fn face_vertices(
+    positions: &[Vec3],
+    texinfo: &TexInfo,
+    normal: [f32; 3],
+    lightmap: Option<usize>,
+    styles: [u8; MAX_FACE_STYLES],
+) -> Vec<Vertex> {
+    let lightmap_offset = lightmap.map_or(NO_LIGHTMAP, |offset| (offset / 3) as u32);
+    let light_styles = styles.map(u32::from);
+    let texel_density = texel_density(texinfo);
+    let mut verts = positions
+        .iter()
+        .map(|v| Vertex {
+            position: [v.0, v.1, v.2],
+            tex_coords: texel_uv(*v, texinfo),
+            light_tex_coords: [0.0, 0.0],
+            lightmap_offset,
+            lightmap_size: [0, 0],
+            light_styles,
+            normal,
+            texel_density,
+            leaf: 0,
+        })
+        .collect_vec();
+
+    let tex_coords = verts.iter().map(|v| v.tex_coords).collect_vec();
+    let ([luxel_min_u, luxel_min_v], lightmap_size) = luxel_extents(&tex_coords);
+
+    verts.iter_mut().for_each(|v| {
+        let [s, t] = v.tex_coords;
+
+        v.lightmap_size = lightmap_size;
+        v.light_tex_coords = [s / 16.0 - luxel_min_u, t / 16.0 - luxel_min_v];
+    });
+    verts
+}
+
+/// Highest anisotropy the anisotropic filter asks for, drivers may support less.
+pub const MAX_ANISOTROPY: u16 = 16;
+
+/// Sampling of map textures, from the blockiest to the smoothest.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TextureFilter {
+    #[default]
+    Nearest,
+    /// Linear filtering of the nearest mip level.
+    Bilinear,
+    /// Linear filtering between two mip levels.
+    Trilinear,
+    /// Trilinear filtering with anisotropy, see `RenderSettings::anisotropy`.
+    Anisotropic,
+}
+
+impl TextureFilter {
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Nearest => Self::Bilinear,
+            Self::Bilinear => Self::Trilinear,
+            Self::Trilinear => Self::Anisotropic,
+            Self::Anisotropic => Self::Nearest,
+        }
+    }
+
+    fn apply<'t>(self, sampler: Sampler<'t, Texture2d>, anisotropy: u16) -> Sampler<'t, Texture2d> {
+        match self {
+            Self::Nearest => sampler
+                .minify_filter(MinifySamplerFilter::Nearest)
+                .magnify_filter(MagnifySamplerFilter::Nearest),
+            Self::Bilinear => sampler.minify_filter(MinifySamplerFilter::LinearMipmapNearest),
+            Self::Trilinear => sampler.minify_filter(MinifySamplerFilter::LinearMipmapLinear),
+            Self::Anisotropic => sampler
+                .minify_filter(MinifySamplerFilter::LinearMipmapLinear)
+                .anisotropy(anisotropy),
+        }
+    }
+}
+
+impl FromStr for TextureFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "bilinear" | "linear" => Ok(Self::Bilinear),
+            "trilinear" => Ok(Self::Trilinear),
+            "anisotropic" => Ok(Self::Anisotropic),
+            _ => Err(format!("Unknown texture filter `{}`", s)),
+        }
+    }
+}
+
+/// Stage of `Map::new` reported to the progress callback along with its completed fraction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadStage {
+    Parse,
+    Geometry,
+    Lightmap,
+    /// Textures embedded into the bsp.
+    Textures,
+    /// Textures loaded from wads, reported by the `Level` only.
+    WadTextures,
+}
+
+/// Position-only identity of a face, used to match faces across maps.
+pub type FaceKey = Vec<[i32; 3]>;
+
+/// Faces closer than that are considered the same.
+const FACE_KEY_PRECISION: f32 = 8.0;
+
+fn face_key(positions: &[Vec3]) -> FaceKey {
+    let quantize = |x: f32| (x * FACE_KEY_PRECISION).round() as i32;
+    let mut key = positions
+        .iter()
+        .map(|v| [quantize(v.0), quantize(v.1), quantize(v.2)])
+        .collect_vec();
+    key.sort_unstable();
+    key
+}
+
+struct FaceRange {
+    tex_name: String,
+    key: FaceKey,
+    vertices: Range<u32>,
+    /// Corners of the face's bounding box in map's coordinates.
+    bounds: Bounds,
+}
+
+type Bounds = ([f32; 3], [f32; 3]);
+
+/// Bounding box of all the points.
+fn bounds_of(points: impl IntoIterator<Item = [f32; 3]>) -> Bounds {
+    points.into_iter().fold(
+        ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+        |(mins, maxs), p| {
+            (
+                [mins[0].min(p[0]), mins[1].min(p[1]), mins[2].min(p[2])],
+                [maxs[0].max(p[0]), maxs[1].max(p[1]), maxs[2].max(p[2])],
+            )
+        },
+    )
+}
+
+fn build_ibos<'a, F: ?Sized + Facade, I: Iterator<Item = &'a FaceRange>>(
+    facade: &F,
+    faces: I,
+) -> HashMap<String, IndexBufferAny> {
+    faces
+        .map(|face| {
+            let vertices = face.vertices.clone().map(|x| x as usize).collect_vec();
+            (face.tex_name.clone(), triangulate(vertices))
+        })
+        .into_group_map()
+        .into_iter()
+        .map(|(k, v)| {
+            let indices = v.into_iter().flatten().map(|x| x as u32).collect_vec();
+            (
+                k,
+                IndexBuffer::new(facade, PrimitiveType::TrianglesList, &indices)
+                    .unwrap()
+                    .into(),
+            )
+        })
+        .collect()
+}
+
+/// Tree of the world model and PVS data needed to find faces visible from a point.
+struct Visibility {
+    head_node: usize,
+    visleafs: usize,
+    nodes: Vec<Node>,
+    planes: Vec<Plane>,
+    leaves: Vec<Leaf>,
+    /// Indices of `Map::faces` in every leaf.
+    leaf_faces: Vec<Vec<usize>>,
+    vis: Vec<u8>,
+}
+
+/// Faces of the cached set of leaves in the PVS of the camera, batched by textures.
+struct VisibleIbos {
+    /// Leaf of the camera and the previous one if its PVS is still blended in.
+    key: (usize, Option<usize>),
+    batches: HashMap<String, LeafBatch>,
+}
+
+/// Faces of a texture ordered by leaves, so leaves outside of the view frustum are skipped
+/// by drawing only slices of the batch.
+struct LeafBatch {
+    ibo: IndexBuffer<u32>,
+    leaves: LeafRanges,
+}
+
+/// Ranges of indices of faces of a leaf and their bounding box.
+type LeafRanges = Vec<(Bounds, Range<usize>)>;
+
+/// Face of a translucent model, drawn on its own to be sorted by distance.
+struct TranslucentFace {
+    tex_name: String,
+    /// Range of `TranslucentFaces::ibo`.
+    indices: Range<usize>,
+    center: Point3<f32>,
+}
+
+struct TranslucentFaces {
+    ibo: IndexBuffer<u32>,
+    faces: Vec<TranslucentFace>,
+}
+
+/// Faces of a brush entity's submodel, drawn moved to the entity's origin.
+struct BrushModel {
+    translation: Vector3<f32>,
+    render_mode: RenderMode,
+    render_amount: f32,
+    ibos: HashMap<String, IndexBufferAny>,
+    /// Set for translucent render modes only.
+    translucent: Option<TranslucentFaces>,
+}
+
+impl BrushModel {
+    fn shading(&self, shading: Shading) -> Shading {
+        match self.render_mode {
+            RenderMode::Normal => shading,
+            RenderMode::Solid => Shading {
+                alpha_test: true,
+                ..shading
+            },
+            _ => Shading {
+                opacity: self.render_amount,
+                ..shading
+            },
+        }
+    }
+
+    fn draw_params<'a>(&self, draw_params: &DrawParameters<'a>) -> DrawParameters<'a> {
+        let blend = match self.render_mode {
+            RenderMode::Normal | RenderMode::Solid => return draw_params.clone(),
+            RenderMode::Color | RenderMode::Texture => Blend::alpha_blending(),
+            RenderMode::Glow | RenderMode::Additive => {
+                let additive = BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One,
+                };
+                Blend {
+                    color: additive,
+                    alpha: additive,
+                    ..Blend::default()
+                }
+            }
+        };
+        DrawParameters {
+            blend,
+            // Faces behind translucent ones are drawn before, but must stay visible
+            depth: Depth {
+                write: false,
+                ..draw_params.depth
+            },
+            ..draw_params.clone()
+        }
+    }
+}
+
+fn translucent_faces<F: ?Sized + Facade>(
+    facade: &F,
+    ranges: &[FaceRange],
+    vertices: &[Vertex],
+    translation: Vector3<f32>,
+) -> TranslucentFaces {
+    let mut indices = Vec::new();
+    let faces = ranges
+        .iter()
+        .map(|range| {
+            let begin = indices.len();
+            let face_vertices = range.vertices.clone().map(|x| x as usize).collect_vec();
+            indices.extend(triangulate(face_vertices).into_iter().map(|x| x as u32));
+
+            let positions = &vertices[range.vertices.start as usize..range.vertices.end as usize];
+            let sum = positions
+                .iter()
+                .fold(Vector3::new(0.0, 0.0, 0.0), |sum, v| {
+                    sum + Vector3::from(v.position)
+                });
+            TranslucentFace {
+                tex_name: range.tex_name.clone(),
+                indices: begin..indices.len(),
+                center: Point3::from_vec(sum / positions.len().max(1) as f32 + translation),
+            }
+        })
+        .collect();
+    TranslucentFaces {
+        ibo: IndexBuffer::new(facade, PrimitiveType::TrianglesList, &indices).unwrap(),
+        faces,
+    }
+}
+
+/// Faces of a map split by whether another map has the same ones.
+struct DiffIbos {
+    shared: HashMap<String, IndexBufferAny>,
+    unique: HashMap<String, IndexBufferAny>,
+}
+
+/// Decal of an `infodecal` entity on the world face nearest to it.
+struct Decal {
+    tex_name: String,
+    spot: DecalSpot,
+    /// Vertices of the face, the decal is clipped to them and takes their lightmap.
+    face: Vec<Vertex>,
+    normal: Vector3<f32>,
+    texinfo: TexInfo,
+}
+
+/// Triangles of decals whose textures are loaded, grouped by textures.
+struct DecalGeometry {
+    vbo: VertexBuffer<Vertex>,
+    ranges: Vec<(String, Range<usize>)>,
+}
+
+/// Problems of the map's faces, see `Map::report`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapReport {
+    /// Sorted names of textures used by faces which are neither embedded nor found in wads.
+    pub missing_textures: Vec<String>,
+    /// Faces using `missing_textures`, they're drawn with a magenta checkerboard.
+    pub missing_texture_faces: usize,
+    /// Faces with no area, e.g. of less than 3 vertices or with all of them on a line.
+    pub degenerate_faces: usize,
+}
+
 pub struct Map {
     origin: [f32; 3],
     vbo: VertexBufferAny,
+    faces: Vec<FaceRange>,
     textured_ibos: HashMap<String, IndexBufferAny>, // lowercase
-    textures: HashMap<String, Texture2d>,           // lowercase
+    diff_ibos: Option<DiffIbos>,
+    brush_models: Vec<BrushModel>,
+    /// Sky faces of the world, drawn only into the depth buffer.
+    sky_ibos: HashMap<String, IndexBufferAny>,
+    visibility: Visibility,
+    /// Collision hull of the standing player.
+    hull: Hull,
+    visible_ibos: RefCell<Option<VisibleIbos>>,
+    leaf_transition: Cell<LeafTransition>,
+    decals: Vec<Decal>,
+    decal_geometry: Option<DecalGeometry>,
+    context: Rc<Context>,
+    textures: HashMap<String, Texture2d>, // lowercase
+    /// Textures loaded from wads rather than embedded into the map.
+    wad_textures: HashSet<String>,
+    /// Sizes of the textures in the map's textures lump, embedded or not. Texture coordinates
+    /// are in texels of these.
+    texture_sizes: HashMap<String, [u32; 2]>,
+    /// Textures loaded from a replacement directory, whose sizes needn't match `texture_sizes`.
+    replaced_textures: HashSet<String>,
+    /// Drawn in place of textures which aren't loaded.
+    placeholder: Texture2d,
+    /// Faces of the world and brush models using each texture, before merging.
+    texture_faces: HashMap<String, usize>,
+    degenerate_faces: usize,
     lightmap: BufferTexture<[u8; 4]>,
+    /// Brightness of light styles at the drawn moment, see `style_brightness`.
+    light_styles: BufferTexture<f32>,
+    emissive: HashMap<String, f32>,
+    /// Frames of loaded animated textures by lowercase base names.
+    animations: HashMap<String, AnimationChain>,
     program: Program,
+    depth_program: Program,
 }
 
 impl Map {
-    pub fn new<F: ?Sized + Facade>(facade: &F, map: &RawMap) -> Self {
-        let vertices = parse_vertices(map.lump_data(LumpType::Vertices)).unwrap();
-        let edges = parse_edges(map.lump_data(LumpType::Edges)).unwrap();
-        let surfedges = parse_surfedges(map.lump_data(LumpType::Surfegdes)).unwrap();
-        let normals = parse_normals_from_planes(map.lump_data(LumpType::Planes)).unwrap();
-        let faces = parse_faces(map.lump_data(LumpType::Faces)).unwrap();
+    /// With `merge_faces` adjacent faces sharing plane and texinfo are joined into bigger
+    /// convex polygons. It changes exact geometry and merged polygons lose their lightmaps,
+    /// as every face has its own, so those are drawn fullbright.
+    /// `hull` is the clip hull `trace` goes through, see `LevelOptions::hull`.
+    /// `progress` is called with every stage's completed fraction, from 0 to 1.
+    /// Fails with the name of a malformed lump.
+    pub fn new<F: ?Sized + Facade>(
+        facade: &F,
+        map: &RawMap,
+        merge_faces: bool,
+        hull: usize,
+        progress: Option<&dyn Fn(LoadStage, f32)>,
+    ) -> Result<Self, &'static str> {
+        let report = |stage, fraction| {
+            if let Some(progress) = progress {
+                progress(stage, fraction);
+            }
+        };
+
+        const LUMPS_NUM: f32 = 12.0;
+        report(LoadStage::Parse, 0.0);
+        let vertices = parse_vertices(map.lump_data(LumpType::Vertices)).map_err(|_| "vertices")?;
+        report(LoadStage::Parse, 1.0 / LUMPS_NUM);
+        let edges = parse_edges(map.lump_data(LumpType::Edges)).map_err(|_| "edges")?;
+        report(LoadStage::Parse, 2.0 / LUMPS_NUM);
+        let surfedges =
+            parse_surfedges(map.lump_data(LumpType::Surfegdes)).map_err(|_| "surfedges")?;
+        report(LoadStage::Parse, 3.0 / LUMPS_NUM);
+        let planes = parse_planes(map.lump_data(LumpType::Planes)).map_err(|_| "planes")?;
+        report(LoadStage::Parse, 4.0 / LUMPS_NUM);
+        let faces = parse_faces(map.lump_data(LumpType::Faces)).map_err(|_| "faces")?;
+        report(LoadStage::Parse, 5.0 / LUMPS_NUM);
         let lightmap = map.lump_data(LumpType::Lighting);
-        let texinfos = parse_texinfos(map.lump_data(LumpType::TexInfo)).unwrap();
-        let textures = parse_textures(map.lump_data(LumpType::Textures)).unwrap();
-        let models = parse_models(map.lump_data(LumpType::Models)).unwrap();
+        let texinfos = parse_texinfos(map.lump_data(LumpType::TexInfo)).map_err(|_| "texinfo")?;
+        report(LoadStage::Parse, 6.0 / LUMPS_NUM);
+        let textures = parse_textures(map.lump_data(LumpType::Textures)).map_err(|_| "textures")?;
+        let texture_sizes = textures
+            .iter()
+            .map(|texture| {
+                let size = [texture.main_width(), texture.main_height()];
+                (texture.name().to_string(), size)
+            })
+            .collect();
+        report(LoadStage::Parse, 7.0 / LUMPS_NUM);
+        let models = parse_models(map.lump_data(LumpType::Models)).map_err(|_| "models")?;
+        report(LoadStage::Parse, 8.0 / LUMPS_NUM);
+        let nodes = parse_nodes(map.lump_data(LumpType::Nodes)).map_err(|_| "nodes")?;
+        report(LoadStage::Parse, 9.0 / LUMPS_NUM);
+        let leaves = parse_leaves(map.lump_data(LumpType::Leaves)).map_err(|_| "leaves")?;
+        report(LoadStage::Parse, 10.0 / LUMPS_NUM);
+        let marksurfaces = parse_marksurfaces(map.lump_data(LumpType::Marksurfaces))
+            .map_err(|_| "marksurfaces")?;
+        report(LoadStage::Parse, 11.0 / LUMPS_NUM);
+        let clipnodes =
+            parse_clipnodes(map.lump_data(LumpType::Clipnodes)).map_err(|_| "clipnodes")?;
+        report(LoadStage::Parse, 1.0);
 
-        let root_model = &models[0];
+        let root_model = models.first().ok_or("models")?;
 
         let origin = {
             let o = root_model.origin;
             [o.0, o.1, o.2]
         };
 
-        let vbo_size = faces
+        let brush_entities = get_brush_entities(map)
+            .into_iter()
+            .filter(|entity| {
+                let exists = entity.model > 0 && entity.model < models.len();
+                if !exists {
+                    warn!("Entity references missing model *{}", entity.model);
+                }
+                exists
+            })
+            .collect_vec();
+        // The world goes first, then models of brush entities in the same order
+        let drawn_models = iter::once(root_model)
+            .chain(brush_entities.iter().map(|entity| &models[entity.model]))
+            .collect_vec();
+
+        let faces_num: usize = drawn_models.iter().map(|model| model.face_num).sum();
+        let vbo_size = drawn_models
             .iter()
-            .skip(root_model.face_id)
-            .take(root_model.face_num)
+            .flat_map(|model| faces.iter().skip(model.face_id).take(model.face_num))
             .map(|f| f.surfedge_num)
             .sum();
         let mut vbo_vertices = Vec::with_capacity(vbo_size);
-        let mut loaded_textures = HashMap::new();
+        let mut intern_textures = HashMap::new();
 
-        let textured_ibos: HashMap<_, _> = faces
-            .iter()
-            .skip(root_model.face_id)
-            .take(root_model.face_num)
-            .filter_map(|f| {
-                let texinfo = &texinfos[f.texinfo_id];
-                let texture = &textures[texinfo.texture_id];
+        report(LoadStage::Geometry, 0.0);
+        let mut faces_done = 0;
+        let mut model_polygons = Vec::with_capacity(drawn_models.len());
+        let mut sky_polygons = Vec::new();
+        let mut texture_faces = HashMap::new();
+        let mut degenerate_faces = 0;
+        for (model_id, model) in drawn_models.iter().enumerate() {
+            let mut polygons = Vec::with_capacity(model.face_num);
+            for (face_id, f) in faces
+                .iter()
+                .enumerate()
+                .skip(model.face_id)
+                .take(model.face_num)
+            {
+                // Geometry is built in two passes, so each takes a half
+                report(
+                    LoadStage::Geometry,
+                    0.5 * faces_done as f32 / faces_num as f32,
+                );
+                faces_done += 1;
+
+                let texinfo = texinfos.get(f.texinfo_id).ok_or("texinfo")?;
+                let texture = textures.get(texinfo.texture_id).ok_or("textures")?;
+                planes.get(f.plane_id).ok_or("planes")?;
                 let tex_name = texture.name().to_string();
 
-                if TRANSPARENT_TEXTURES
-                    .iter()
-                    .any(|x| tex_name.eq_ignore_ascii_case(x))
+                // Sky of brush entities is rare enough to be simply skipped
+                let is_sky = tex_name.eq_ignore_ascii_case(SKY_TEXTURE);
+                if (is_sky && model_id > 0)
+                    || TRANSPARENT_TEXTURES
+                        .iter()
+                        .any(|x| tex_name.eq_ignore_ascii_case(x))
                 {
-                    return None;
+                    continue;
                 }
 
-                if !texture.is_empty() && !loaded_textures.contains_key(&tex_name) {
-                    let (elapsed, ()) = measure_time(|| {
-                        loaded_textures
-                            .insert(tex_name.clone(), Self::upload_miptex(facade, texture));
-                    });
-                    debug!("Load intern miptex `{}` in {}", &tex_name, elapsed);
+                if !is_sky && !texture.is_empty() {
+                    intern_textures.insert(tex_name.clone(), texture);
                 }
 
-                let n = &normals[f.plane_id];
-                let normal = if f.side {
-                    [n.0, n.1, n.2]
-                } else {
-                    [-n.0, -n.1, -n.2]
-                };
-
-                let begin = vbo_vertices.len();
-                let lightmap_offset = f.lightmap;
-                let mut verts = surfedges
+                let positions = surfedges
                     .iter()
                     .skip(f.surfedge_id)
                     .take(f.surfedge_num)
                     .map(|&s| {
-                        let i = if s < 0 {
-                            edges[-s as usize].1
-                        } else {
-                            edges[s as usize].0
-                        } as usize;
-                        &vertices[i]
+                        let edge = edges.get(s.unsigned_abs() as usize).ok_or("edges")?;
+                        let i = if s < 0 { edge.1 } else { edge.0 };
+                        vertices.get(i as usize).copied().ok_or("vertices")
                     })
-                    .map(move |v| Vertex {
-                        position: [v.0, v.1, v.2],
-                        tex_coords: calculate_uvs(&v, texinfo),
-                        light_tex_coords: [0.0, 0.0],
-                        lightmap_offset: (lightmap_offset / 3) as u32,
-                        lightmap_size: [0, 0],
-                        normal,
-                    })
-                    .collect_vec();
+                    .collect::<Result<Vec<_>, _>>()?;
+                if polygon_area(&positions) < DEGENERATE_AREA {
+                    degenerate_faces += 1;
+                }
+                let lightmap = if f.lightmap == NO_LIGHTMAP as usize {
+                    None
+                } else {
+                    Some(f.lightmap)
+                };
 
-                let ([mut min_u, mut min_v], [mut max_u, mut max_v]) =
-                    (verts[0].tex_coords, verts[0].tex_coords);
+                let polygon = FacePolygon {
+                    tex_name,
+                    face_ids: vec![face_id],
+                    plane_id: f.plane_id,
+                    side: f.side,
+                    texinfo_id: f.texinfo_id,
+                    positions,
+                    lightmap,
+                    styles: f.styles,
+                };
+                if is_sky {
+                    sky_polygons.push(polygon);
+                } else {
+                    *texture_faces.entry(polygon.tex_name.clone()).or_insert(0) += 1;
+                    polygons.push(polygon);
+                }
+            }
+            model_polygons.push(polygons);
+        }
 
-                for vert in &verts {
-                    let [u, v] = vert.tex_coords;
-                    min_u = u.min(min_u);
-                    max_u = u.max(max_u);
+        // Faces reference only the first frames, so add the rest of them
+        let animated_bases: HashSet<_> = model_polygons
+            .iter()
+            .flatten()
+            .chain(&sky_polygons)
+            .filter_map(|polygon| AnimationFrame::parse(&polygon.tex_name))
+            .map(|frame| frame.base.to_ascii_lowercase())
+            .collect();
+        textures
+            .iter()
+            .filter(|texture| !texture.is_empty())
+            .filter(|texture| {
+                AnimationFrame::parse(texture.name())
+                    .is_some_and(|frame| animated_bases.contains(&frame.base.to_ascii_lowercase()))
+            })
+            .for_each(|texture| {
+                intern_textures.insert(texture.name().to_string(), texture);
+            });
 
-                    min_v = v.min(min_v);
-                    max_v = v.max(max_v);
-                }
+        // Placed before merging, so decals get lightmaps of single faces
+        let world_faces = model_polygons[0]
+            .iter()
+            .map(|polygon| {
+                let plane = planes.get(polygon.plane_id).ok_or("planes")?;
+                let (x, y, z) = face_normal(plane, polygon.side);
+                let normal = vec3(x, y, z);
+                let positions = polygon
+                    .positions
+                    .iter()
+                    .map(|&(x, y, z)| vec3(x, y, z))
+                    .collect_vec();
+                Ok((normal, positions))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let decals = get_decals(map)
+            .into_iter()
+            .filter_map(|entity| {
+                let (x, y, z) = entity.origin;
+                let origin = vec3(x, y, z);
+                let faces = world_faces
+                    .iter()
+                    .map(|(normal, positions)| (*normal, positions.as_slice()));
+                let face_index = match find_face(origin, faces) {
+                    Some(face_index) => face_index,
+                    None => {
+                        warn!(
+                            "Decal `{}` at {:?} has no face to stick to",
+                            entity.texture, entity.origin
+                        );
+                        return None;
+                    }
+                };
+                let polygon = &model_polygons[0][face_index];
+                let (normal, positions) = &world_faces[face_index];
+                let texinfo = texinfos.get(polygon.texinfo_id)?;
+                let (s, t) = (texinfo.vs, texinfo.vt);
+                let spot = DecalSpot::new(
+                    origin,
+                    positions[0],
+                    *normal,
+                    vec3(s.0, s.1, s.2),
+                    vec3(t.0, t.1, t.2),
+                );
+                let face = face_vertices(
+                    &polygon.positions,
+                    texinfo,
+                    (*normal).into(),
+                    polygon.lightmap,
+                    polygon.styles,
+                );
+                Some(Decal {
+                    tex_name: entity.texture,
+                    spot,
+                    face,
+                    normal: *normal,
+                    texinfo: texinfo.clone(),
+                })
+            })
+            .collect_vec();
 
-                let lightmap_size = [
-                    ((max_u / 16.0).ceil() - (min_u / 16.0).floor() + 1.0) as u32,
-                    ((max_v / 16.0).ceil() - (min_v / 16.0).floor() + 1.0) as u32,
-                ];
+        if merge_faces {
+            let before: usize = model_polygons.iter().map(Vec::len).sum();
+            model_polygons = model_polygons
+                .into_iter()
+                .map(Self::merge_polygons)
+                .collect_vec();
+            let after: usize = model_polygons.iter().map(Vec::len).sum();
+            info!("Merged {} faces into {} polygons", before, after);
+        }
 
-                verts.iter_mut().for_each(|v| {
-                    let [s, t] = v.tex_coords;
+        if let Some((min, max)) = model_polygons
+            .iter()
+            .flatten()
+            .filter_map(|p| texinfos.get(p.texinfo_id).map(texel_density))
+            .minmax()
+            .into_option()
+        {
+            info!(
+                "Texel density: [Min={:.3}, Max={:.3}] texels per unit",
+                min, max
+            );
+        }
 
-                    v.lightmap_size = lightmap_size;
-                    v.light_tex_coords = [
-                        (s.ceil() - min_u.floor()) / 16.0,
-                        (t.ceil() - min_v.floor()) / 16.0,
-                    ];
-                });
+        // Leaves reference faces of the world only
+        let face_polygons: HashMap<_, _> = model_polygons[0]
+            .iter()
+            .enumerate()
+            .flat_map(|(i, polygon)| polygon.face_ids.iter().map(move |&face_id| (face_id, i)))
+            .collect();
+
+        // Sky goes last, after the brush models
+        model_polygons.push(sky_polygons);
+        let polygons_num: usize = model_polygons.iter().map(Vec::len).sum();
+        let mut polygons_done = 0;
+        let mut model_ranges = model_polygons
+            .into_iter()
+            .map(|polygons| {
+                polygons
+                    .into_iter()
+                    .map(|polygon| {
+                        report(
+                            LoadStage::Geometry,
+                            0.5 + 0.5 * polygons_done as f32 / polygons_num as f32,
+                        );
+                        polygons_done += 1;
+                        let plane = planes.get(polygon.plane_id).ok_or("planes")?;
+                        let normal = face_normal(plane, polygon.side).into();
+                        let texinfo = texinfos.get(polygon.texinfo_id).ok_or("texinfo")?;
 
-                vbo_vertices.extend(verts);
-                let end = vbo_vertices.len();
-                let indices = triangulate((begin..end).collect_vec());
+                        let begin = vbo_vertices.len();
+                        vbo_vertices.extend(face_vertices(
+                            &polygon.positions,
+                            texinfo,
+                            normal,
+                            polygon.lightmap,
+                            polygon.styles,
+                        ));
+                        let end = vbo_vertices.len();
 
-                Some((tex_name, indices))
+                        Ok(FaceRange {
+                            key: face_key(&polygon.positions),
+                            tex_name: polygon.tex_name,
+                            vertices: begin as u32..end as u32,
+                            bounds: bounds_of(polygon.positions.iter().map(|p| [p.0, p.1, p.2])),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
             })
-            .into_group_map()
-            .into_iter()
-            .map(|(k, v)| {
-                let indices = v.into_iter().flatten().map(|x| x as u32).collect_vec();
-                debug!("{} triangles using `{}` miptex", indices.len() / 3, &k);
-                (
-                    k,
-                    IndexBuffer::new(facade, PrimitiveType::TrianglesList, &indices)
-                        .unwrap()
-                        .into(),
-                )
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter();
+        let face_ranges = model_ranges.next().unwrap();
+        let sky_ranges = model_ranges.next_back().unwrap();
+        let sky_ibos = build_ibos(facade, sky_ranges.iter());
+        let brush_models = brush_entities
+            .iter()
+            .zip(model_ranges)
+            .map(|(entity, ranges)| {
+                let (x, y, z) = entity.origin;
+                let translation = vec3(x, y, z);
+                let translucent = Some(())
+                    .filter(|_| entity.render_mode.is_translucent())
+                    .map(|_| translucent_faces(facade, &ranges, &vbo_vertices, translation));
+                BrushModel {
+                    translation,
+                    render_mode: entity.render_mode,
+                    render_amount: entity.render_amount,
+                    ibos: build_ibos(facade, ranges.iter()),
+                    translucent,
+                }
             })
-            .collect();
+            .collect_vec();
+        let textured_ibos = build_ibos(facade, face_ranges.iter());
+        textured_ibos.iter().for_each(|(k, v)| {
+            debug!(
+                "{} triangles using `{}` miptex",
+                v.get_elements_count() / 3,
+                k
+            );
+        });
 
+        let leaf_faces = leaves
+            .iter()
+            .map(|leaf| {
+                marksurfaces
+                    .iter()
+                    .skip(leaf.marksurface_id)
+                    .take(leaf.marksurface_num)
+                    .filter_map(|face_id| face_polygons.get(face_id).copied())
+                    .collect_vec()
+            })
+            .collect_vec();
+        let hull = match hull.min(root_model.head_nodes.len() - 1) {
+            0 => Hull::from_nodes(&nodes, &leaves, planes.clone(), root_model.head_nodes[0]),
+            hull => Hull::new(clipnodes, planes.clone(), root_model.head_nodes[hull]),
+        };
+        let visibility = Visibility {
+            head_node: root_model.head_nodes[0],
+            visleafs: root_model.visleafs,
+            nodes,
+            planes,
+            leaves,
+            leaf_faces,
+            vis: map.lump_data(LumpType::Visibility).to_vec(),
+        };
+
+        // Going from the last leaf, faces seen from several leaves end up with the first one
+        for (leaf, faces) in visibility.leaf_faces.iter().enumerate().rev() {
+            for range in faces.iter().filter_map(|&i| face_ranges.get(i)) {
+                vbo_vertices[range.vertices.start as usize..range.vertices.end as usize]
+                    .iter_mut()
+                    .for_each(|vertex| vertex.leaf = leaf as u32);
+            }
+        }
         let vbo = VertexBuffer::new(facade, &vbo_vertices).unwrap().into();
+        report(LoadStage::Geometry, 1.0);
 
         let (elapsed, program) = measure_time(|| {
             program!(facade,
@@ -220,7 +1030,15 @@ impl Map {
             .unwrap()
         });
         debug!("Map shader was loaded in {}", elapsed);
+        let depth_program = program!(facade,
+            140 => {
+                vertex: include_str!("../../shaders/map/vert.glsl"),
+                fragment: include_str!("../../shaders/map/depth_frag.glsl"),
+            },
+        )
+        .unwrap();
 
+        report(LoadStage::Lightmap, 0.0);
         let (elapsed, lightmap) = measure_time(|| {
             let lightmap = lightmap
                 .chunks(3)
@@ -235,25 +1053,113 @@ impl Map {
             BufferTexture::persistent(facade, &lightmap, BufferTextureType::Float).unwrap()
         });
         debug!("Lightmap was loaded in {}", elapsed);
+        report(LoadStage::Lightmap, 1.0);
+
+        report(LoadStage::Textures, 0.0);
+        let intern_textures_num = intern_textures.len();
+        let loaded_textures = intern_textures
+            .into_iter()
+            .enumerate()
+            .map(|(i, (tex_name, texture))| {
+                let (elapsed, tex2d) = measure_time(|| Self::upload_miptex(facade, texture, false));
+                debug!("Load intern miptex `{}` in {}", &tex_name, elapsed);
+                report(
+                    LoadStage::Textures,
+                    (i + 1) as f32 / intern_textures_num as f32,
+                );
+                (tex_name, tex2d)
+            })
+            .collect::<HashMap<_, _>>();
+        report(LoadStage::Textures, 1.0);
+        let animations = animation_chains(loaded_textures.keys().map(String::as_str));
 
         info!(
-            "Map summary: [Vertices={}, Texture groups={}, Lightmap texels={}]",
+            "Map summary: [Vertices={}, Texture groups={}, Brush models={}, Lightmap texels={}]",
             vbo_vertices.len(),
             textured_ibos.len(),
+            brush_models.len(),
             lightmap.len()
         );
 
-        Self {
+        let mut map = Self {
             origin,
             vbo,
+            faces: face_ranges,
             textured_ibos,
+            diff_ibos: None,
+            brush_models,
+            sky_ibos,
+            visibility,
+            hull,
+            visible_ibos: RefCell::new(None),
+            leaf_transition: Cell::default(),
+            decals,
+            decal_geometry: None,
+            context: facade.get_context().clone(),
             textures: loaded_textures,
+            wad_textures: HashSet::new(),
+            texture_sizes,
+            replaced_textures: HashSet::new(),
+            placeholder: Self::upload_placeholder(facade),
+            texture_faces,
+            degenerate_faces,
             lightmap,
+            light_styles: BufferTexture::dynamic(
+                facade,
+                &[1.0; STYLES_NUM],
+                BufferTextureType::Float,
+            )
+            .unwrap(),
+            emissive: HashMap::new(),
+            animations,
             program,
-        }
+            depth_program,
+        };
+        map.build_decals(facade);
+        Ok(map)
+    }
+
+    fn merge_polygons(polygons: Vec<FacePolygon>) -> Vec<FacePolygon> {
+        polygons
+            .into_iter()
+            .map(|p| ((p.tex_name.clone(), p.plane_id, p.side, p.texinfo_id), p))
+            .into_group_map()
+            .into_values()
+            .flat_map(|group| {
+                let positions = group.iter().map(|p| p.positions.clone()).collect_vec();
+                merge_coplanar(&positions)
+                    .into_iter()
+                    .map(|(positions, sources)| {
+                        let first = &group[sources[0]];
+                        FacePolygon {
+                            tex_name: first.tex_name.clone(),
+                            face_ids: sources
+                                .iter()
+                                .flat_map(|&i| group[i].face_ids.iter().copied())
+                                .collect(),
+                            plane_id: first.plane_id,
+                            side: first.side,
+                            texinfo_id: first.texinfo_id,
+                            positions,
+                            lightmap: if sources.len() == 1 {
+                                first.lightmap
+                            } else {
+                                None
+                            },
+                            styles: first.styles,
+                        }
+                    })
+                    .collect_vec()
+            })
+            .collect()
     }
 
-    fn upload_miptex<F: ?Sized + Facade>(facade: &F, miptex: &MipTexture) -> Texture2d {
+    /// Decals are uploaded with `MipTexture::decal_pixels`.
+    fn upload_miptex<F: ?Sized + Facade>(
+        facade: &F,
+        miptex: &MipTexture,
+        decal: bool,
+    ) -> Texture2d {
         let texture = Texture2d::empty_with_mipmaps(
             facade,
             MipmapsOption::EmptyMipmapsMax((MipTexture::layers() - 1) as u32),
@@ -271,27 +1177,209 @@ impl Map {
                 width: dims.0,
                 height: dims.1,
             };
-            let pixels = miptex.pixels(i as usize).unwrap();
+            let pixels = if decal {
+                miptex.decal_pixels(i as usize)
+            } else {
+                miptex.pixels(i as usize)
+            }
+            .unwrap();
             let image = RawImage2d::from_raw_rgba_reversed(&pixels, dims);
             miplevel.write(rect, image);
         }
         texture
     }
 
+    /// Magenta and black checkerboard of 2 by 2 squares, stretched over a texture's size.
+    fn upload_placeholder<F: ?Sized + Facade>(facade: &F) -> Texture2d {
+        let side = 2 * PLACEHOLDER_CELL;
+        let pixels: Vec<u8> = (0..side * side)
+            .flat_map(|i| {
+                let (x, y) = (i % side / PLACEHOLDER_CELL, i / side / PLACEHOLDER_CELL);
+                if (x + y) % 2 == 0 {
+                    [255, 0, 255, 255]
+                } else {
+                    [0, 0, 0, 255]
+                }
+            })
+            .collect();
+        let image = RawImage2d::from_raw_rgba(pixels, (side, side));
+        Texture2d::with_mipmaps(facade, image, MipmapsOption::AutoGeneratedMipmaps).unwrap()
+    }
+
+    /// Textures of faces which aren't loaded and degenerate faces. Missing textures are
+    /// looked up again on every call, so ones provided by reloaded wads are left out.
+    pub fn report(&self) -> MapReport {
+        let missing = self
+            .texture_faces
+            .iter()
+            .filter(|(name, _)| !self.textures.contains_key(*name))
+            .sorted()
+            .collect_vec();
+        MapReport {
+            missing_texture_faces: missing.iter().map(|(_, faces)| **faces).sum(),
+            missing_textures: missing.into_iter().map(|(name, _)| name.clone()).collect(),
+            degenerate_faces: self.degenerate_faces,
+        }
+    }
+
+    /// Names of textures used by the world, brush models and decals.
+    fn texture_names(&self) -> HashSet<&String> {
+        self.textured_ibos
+            .keys()
+            .chain(self.brush_models.iter().flat_map(|m| m.ibos.keys()))
+            .chain(self.decals.iter().map(|decal| &decal.tex_name))
+            .collect()
+    }
+
+    /// Names of decal textures which aren't loaded, so their decals aren't drawn.
+    pub fn missing_decal_textures(&self) -> Vec<&str> {
+        self.decals
+            .iter()
+            .map(|decal| decal.tex_name.as_str())
+            .filter(|name| !self.textures.contains_key(*name))
+            .unique()
+            .collect()
+    }
+
+    /// Clips decals whose textures are loaded to their faces, the textures define their sizes.
+    fn build_decals<F: ?Sized + Facade>(&mut self, facade: &F) {
+        let mut vertices = vec![];
+        let mut ranges: Vec<(String, Range<usize>)> = vec![];
+        let mut drawn = 0;
+        let decals = self
+            .decals
+            .iter()
+            .filter_map(|decal| Some((decal, self.textures.get(&decal.tex_name)?)))
+            .sorted_by(|a, b| a.0.tex_name.cmp(&b.0.tex_name));
+        for (decal, texture) in decals {
+            let face = &decal.face[0];
+            let positions = decal
+                .face
+                .iter()
+                .map(|v| Vector3::from(v.position))
+                .collect_vec();
+            let [u, v] = face.tex_coords;
+            let luxel_min = [
+                u / 16.0 - face.light_tex_coords[0],
+                v / 16.0 - face.light_tex_coords[1],
+            ];
+            let polygon = decal.spot.clip_to_face(
+                &positions,
+                decal.normal,
+                texture.width() as f32,
+                texture.height() as f32,
+            );
+            let polygon = polygon
+                .into_iter()
+                .map(|(position, tex_coords)| {
+                    let position = (position.x, position.y, position.z);
+                    let [s, t] = texel_uv(position, &decal.texinfo);
+                    Vertex {
+                        position: [position.0, position.1, position.2],
+                        tex_coords,
+                        light_tex_coords: [s / 16.0 - luxel_min[0], t / 16.0 - luxel_min[1]],
+                        texel_density: 1.0,
+                        ..*face
+                    }
+                })
+                .collect_vec();
+            if !polygon.is_empty() {
+                drawn += 1;
+            }
+            let begin = vertices.len();
+            vertices.extend(
+                triangulate((0..polygon.len()).collect())
+                    .into_iter()
+                    .map(|i| polygon[i]),
+            );
+            match ranges.last_mut() {
+                Some((name, range)) if *name == decal.tex_name => range.end = vertices.len(),
+                _ => ranges.push((decal.tex_name.clone(), begin..vertices.len())),
+            }
+        }
+        debug!("{} of {} decals are drawn", drawn, self.decals.len());
+        self.decal_geometry = if vertices.is_empty() {
+            None
+        } else {
+            Some(DecalGeometry {
+                vbo: VertexBuffer::new(facade, &vertices).unwrap(),
+                ranges,
+            })
+        };
+    }
+
+    /// Marks textures as self-illuminated. Keys are lowercase texture names, values are
+    /// the minimal light level applied instead of the lightmap, so 1 means fullbright.
+    /// This is a rough stand-in for the glow RAD would bake, not actual light emission.
+    pub fn set_emissive(&mut self, emissive: &HashMap<String, f32>) {
+        self.emissive = self
+            .texture_names()
+            .into_iter()
+            .filter_map(|name| {
+                let strength = emissive.get(&name.to_ascii_lowercase())?;
+                Some((name.clone(), *strength))
+            })
+            .collect();
+        debug!("{} emissive texture groups", self.emissive.len());
+    }
+
     pub fn is_textures_loaded(&self) -> bool {
-        self.textured_ibos.len() == self.textures.len()
+        self.texture_names()
+            .iter()
+            .all(|name| self.textures.contains_key(*name))
     }
 
     pub fn load_from_archive<F: ?Sized + Facade>(&mut self, facade: &F, archive: &Archive) {
+        self.load_textures(facade, |name| {
+            archive
+                .get_by_name(name.to_ascii_uppercase())
+                .or_else(|| archive.get_by_name(name.to_ascii_lowercase()))
+                .map(|entry| Cow::Borrowed(entry.data()))
+        });
+    }
+
+    pub fn load_from_streamed_archive<F: ?Sized + Facade>(
+        &mut self,
+        facade: &F,
+        archive: &StreamedArchive,
+    ) {
+        let read = |name: String| match archive.read(&name) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Couldn't read `{}` from wad: {}", name, e);
+                None
+            }
+        };
+        self.load_textures(facade, |name| {
+            read(name.to_ascii_uppercase())
+                .or_else(|| read(name.to_ascii_lowercase()))
+                .map(Cow::Owned)
+        });
+    }
+
+    fn load_textures<'a, F: ?Sized + Facade, L: Fn(&str) -> Option<Cow<'a, [u8]>>>(
+        &mut self,
+        facade: &F,
+        lookup: L,
+    ) {
         let present: HashSet<_> = self.textures.keys().cloned().collect();
-        let required: HashSet<_> = self.textured_ibos.keys().cloned().collect();
+        let used = self.texture_names();
+        // Wads are searched for all frames of animated textures, as there's no list of them
+        let frames = used
+            .iter()
+            .filter_map(|name| AnimationFrame::parse(name))
+            .flat_map(|frame| {
+                AnimationFrame::sequence_names(frame.base, false)
+                    .chain(AnimationFrame::sequence_names(frame.base, true))
+            })
+            .collect_vec();
+        let required: HashSet<_> = used.into_iter().cloned().chain(frames).collect();
         let loaded = required.difference(&present).cloned().filter_map(|name| {
             let (elapsed, tex2d) = measure_time(|| {
-                let entry = archive
-                    .get_by_name(name.to_ascii_uppercase())
-                    .or_else(|| archive.get_by_name(name.to_ascii_lowercase()))?;
-                let miptex = MipTexture::parse(entry.data()).ok()?;
-                Some(Self::upload_miptex(facade, &miptex))
+                let data = lookup(&name)?;
+                let miptex = MipTexture::parse(&data).ok()?;
+                let decal = self.decals.iter().any(|decal| decal.tex_name == name);
+                Some(Self::upload_miptex(facade, &miptex, decal))
             });
             if tex2d.is_some() {
                 debug!("Load extern miptex `{}` in {}", &name, elapsed);
@@ -299,31 +1387,798 @@ impl Map {
 
             Some((name, tex2d?))
         });
-        self.textures.extend(loaded);
+        for (name, texture) in loaded.collect_vec() {
+            self.wad_textures.insert(name.clone());
+            self.textures.insert(name, texture);
+        }
+        self.animations = animation_chains(self.textures.keys().map(String::as_str));
+        self.build_decals(facade);
+    }
+
+    /// Loads textures of `dir` in place of ones of the map, including frames of animated
+    /// textures. They're stretched over the faces like the originals, so those may be of any
+    /// resolution. Textures which aren't in the textures lump of the map, like decals, are
+    /// never replaced. Returns the number of replaced textures.
+    pub fn load_replacements<F: ?Sized + Facade>(
+        &mut self,
+        facade: &F,
+        dir: &ReplacementDir,
+    ) -> usize {
+        let used = self.texture_names();
+        let animated_bases: HashSet<_> = used
+            .iter()
+            .filter_map(|name| AnimationFrame::parse(name))
+            .map(|frame| frame.base.to_ascii_lowercase())
+            .collect();
+        let frames = self
+            .texture_sizes
+            .keys()
+            .filter(|name| {
+                AnimationFrame::parse(name)
+                    .is_some_and(|frame| animated_bases.contains(&frame.base.to_ascii_lowercase()))
+            })
+            .cloned();
+        let required: HashSet<_> = used.into_iter().cloned().chain(frames).collect();
+        let loaded = required
+            .into_iter()
+            .filter(|name| self.texture_sizes.contains_key(name))
+            .filter(|name| !self.decals.iter().any(|decal| &decal.tex_name == name))
+            .filter_map(|name| {
+                let (elapsed, image) = measure_time(|| dir.read(&name));
+                match image? {
+                    Ok(image) => {
+                        debug!("Load replacement of `{}` in {}", name, elapsed);
+                        let raw = RawImage2d::from_raw_rgba_reversed(
+                            &image.pixels,
+                            (image.width, image.height),
+                        );
+                        let texture = Texture2d::with_mipmaps(
+                            facade,
+                            raw,
+                            MipmapsOption::AutoGeneratedMipmaps,
+                        )
+                        .unwrap();
+                        Some((name, texture))
+                    }
+                    Err(e) => {
+                        warn!("Failed to read replacement of `{}`: {}", name, e);
+                        None
+                    }
+                }
+            })
+            .collect_vec();
+
+        let replaced = loaded.len();
+        for (name, texture) in loaded {
+            self.wad_textures.remove(&name);
+            self.replaced_textures.insert(name.clone());
+            self.textures.insert(name, texture);
+        }
+        self.animations = animation_chains(self.textures.keys().map(String::as_str));
+        replaced
+    }
+
+    /// Takes textures `old` loaded from wads, except ones this map embeds, so a recompiled map
+    /// needn't read them again.
+    pub fn reuse_wad_textures<F: ?Sized + Facade>(&mut self, facade: &F, old: &mut Map) {
+        for name in old.wad_textures.drain() {
+            if self.textures.contains_key(&name) {
+                continue;
+            }
+            if let Some(texture) = old.textures.remove(&name) {
+                self.wad_textures.insert(name.clone());
+                self.textures.insert(name, texture);
+            }
+        }
+        self.animations = animation_chains(self.textures.keys().map(String::as_str));
+        self.build_decals(facade);
+    }
+
+    /// Uploads again textures loaded from the wad for which `provided` is true, e.g. after it
+    /// was edited, then loads ones which are still missing. Textures whose entries are gone
+    /// from the wad stay as they were, so do embedded ones.
+    pub fn reload_textures<F: ?Sized + Facade, P: Fn(&str) -> bool>(
+        &mut self,
+        facade: &F,
+        archive: &Archive,
+        provided: P,
+    ) {
+        let mut reloaded = 0;
+        for name in self.wad_textures.iter().filter(|name| provided(name)) {
+            let miptex = archive
+                .get_by_name(name.to_ascii_uppercase())
+                .or_else(|| archive.get_by_name(name.to_ascii_lowercase()))
+                .and_then(|entry| MipTexture::parse(entry.data()).ok());
+            match miptex {
+                Some(miptex) => {
+                    let decal = self.decals.iter().any(|decal| &decal.tex_name == name);
+                    self.textures
+                        .insert(name.clone(), Self::upload_miptex(facade, &miptex, decal));
+                    reloaded += 1;
+                }
+                None => warn!(
+                    "Texture `{}` is gone from the wad, keeping the old one",
+                    name
+                ),
+            }
+        }
+        debug!("Reloaded {} textures", reloaded);
+        self.load_from_archive(facade, archive);
+    }
+
+    /// Texture to draw instead of `tex` according to the animation state, `tex` itself if it
+    /// isn't animated.
+    fn animated_texture<'a>(&'a self, tex: &'a str, animation: TextureAnimation) -> &'a str {
+        let frame = match AnimationFrame::parse(tex) {
+            Some(frame) => frame,
+            None => return tex,
+        };
+        let chain = match self.animations.get(&frame.base.to_ascii_lowercase()) {
+            Some(chain) => chain,
+            None => return tex,
+        };
+        let (own, other) = if frame.alternate {
+            (&chain.alternate, &chain.frames)
+        } else {
+            (&chain.frames, &chain.alternate)
+        };
+        let frames = if animation.toggled && !other.is_empty() {
+            other
+        } else {
+            own
+        };
+        if frames.is_empty() {
+            return tex;
+        }
+        // Faces may start from any frame of the sequence
+        let passed = (animation.time.max(0.0) * ANIMATION_FPS) as usize;
+        &frames[(frame.index + passed) % frames.len()]
     }
 
+    /// The vertex buffer is static and faces of the world are batched by textures: there's a
+    /// draw call per texture visible from the camera, not per face, so a map of thousands of
+    /// faces usually takes tens of calls. Batches of the visible faces are rebuilt only when
+    /// the PVS changes, see `with_visible_ibos`.
     pub fn render<S: Surface>(
         &self,
         surface: &mut S,
-        projection: Matrix4<f32>,
-        view: Matrix4<f32>,
+        frame: &FrameView,
+        draw_params: &DrawParameters,
+        pvs: PvsOptions,
+        shading: &Shading,
+    ) {
+        let FrameView {
+            projection,
+            view,
+            model,
+            camera_position,
+        } = *frame;
+        let shading = *shading;
+        self.light_styles
+            .write(&style_brightness(shading.animation.time));
+        self.draw_sky(surface, projection, view, model, draw_params);
+        self.with_visible_ibos(projection * view, model, camera_position, pvs, |ibos| {
+            self.draw(surface, ibos, projection, view, model, draw_params, shading);
+        });
+        // Decals would cover leaf colors of the faces under them
+        if !shading.leaf_colors {
+            self.draw_decals(surface, projection, view, model, draw_params, shading);
+        }
+        self.draw_brush_models(
+            surface,
+            projection,
+            view,
+            model,
+            draw_params,
+            camera_position,
+            shading,
+        );
+    }
+
+    /// Leaf of the world model containing a point of the view space, 0 if it's outside.
+    pub fn find_leaf(&self, model: Matrix4<f32>, point: [f32; 3]) -> usize {
+        let vis = &self.visibility;
+        if vis.nodes.is_empty() {
+            return 0;
+        }
+        let point = self.to_map_space(model, point);
+        find_leaf(
+            &vis.nodes,
+            &vis.planes,
+            vis.head_node,
+            (point.x, point.y, point.z),
+        )
+    }
+
+    /// Contents of the world's leaf containing a point of the view space, solid outside of it.
+    pub fn contents_at(&self, model: Matrix4<f32>, point: [f32; 3]) -> Contents {
+        match self.find_leaf(model, point) {
+            0 => Contents::Solid,
+            leaf => self
+                .visibility
+                .leaves
+                .get(leaf)
+                .map_or(Contents::Solid, |leaf| Contents::from_value(leaf.contents)),
+        }
+    }
+
+    /// Converts a point of the view space to the one of map's vertices.
+    pub fn to_map_space(&self, model: Matrix4<f32>, point: [f32; 3]) -> Point3<f32> {
+        let point = (ROTATE_MAT * model)
+            .invert()
+            .map_or(Point3::from(point), |from_view| {
+                from_view.transform_point(Point3::from(point))
+            });
+        point - Vector3::from(self.origin)
+    }
+
+    /// Transform of map's coordinates, e.g. origins of entities, to the view space.
+    pub fn to_view_transform(&self, model: Matrix4<f32>) -> Matrix4<f32> {
+        ROTATE_MAT * model * Matrix4::from_translation(self.origin.into())
+    }
+
+    /// Traces the center of the clip hull's box from `start` to `end` through the world model,
+    /// all in the view space.
+    pub fn trace(&self, model: Matrix4<f32>, start: [f32; 3], end: [f32; 3]) -> TraceResult {
+        let to_view = self.to_view_transform(model);
+        let from_view = to_view.invert().unwrap_or_else(Matrix4::identity);
+        let to_map = |p: [f32; 3]| {
+            let p = from_view.transform_point(Point3::from(p));
+            (p.x, p.y, p.z)
+        };
+
+        let result = self.hull.trace(to_map(start), to_map(end));
+        let end = to_view.transform_point(result.end.into());
+        TraceResult {
+            end: (end.x, end.y, end.z),
+            normal: result.normal.map(|n| {
+                let n = to_view.transform_vector(n.into());
+                (n.x, n.y, n.z)
+            }),
+            ..result
+        }
+    }
+
+    /// Calls `f` with batches of faces of leaves visible from the camera according to the PVS
+    /// which are inside of the view frustum, or all the batches if the camera is outside of
+    /// the world. The PVS is of `PvsOptions::locked_leaf` instead if there's one, and joined
+    /// with the PVS of the previous leaf for `PvsOptions::blend` after the camera changes
+    /// leaves. Batches are rebuilt only when the PVS changes, turning the camera only skips
+    /// slices of leaves outside of the frustum.
+    fn with_visible_ibos<R, F: FnOnce(Vec<(&String, IndicesSource)>) -> R>(
+        &self,
+        view_projection: Matrix4<f32>,
+        model: Matrix4<f32>,
+        camera_position: [f32; 3],
+        pvs: PvsOptions,
+        f: F,
+    ) -> R {
+        let leaf = pvs
+            .locked_leaf
+            .unwrap_or_else(|| self.find_leaf(model, camera_position));
+        let mut transition = self.leaf_transition.get();
+        if transition.leaf != leaf {
+            transition = LeafTransition {
+                leaf,
+                previous: transition.leaf,
+                since: pvs.time,
+            };
+            self.leaf_transition.set(transition);
+        }
+        if leaf == 0 || leaf >= self.visibility.leaves.len() {
+            return f(self
+                .textured_ibos
+                .iter()
+                .map(|(tex, ibo)| (tex, ibo.into()))
+                .collect());
+        }
+
+        let vis = &self.visibility;
+        let previous = Some(transition.previous).filter(|&previous| {
+            previous != 0 && previous < vis.leaves.len() && pvs.time - transition.since < pvs.blend
+        });
+        let key = (leaf, previous);
+        let mut visible_ibos = self.visible_ibos.borrow_mut();
+        if !matches!(&*visible_ibos, Some(cached) if cached.key == key) {
+            let pvs_leaves = |leaf: usize| {
+                let visible_leaves = decompress_vis(&vis.vis, &vis.leaves[leaf], vis.visleafs);
+                iter::once(leaf).chain(
+                    visible_leaves
+                        .into_iter()
+                        .positions(|visible| visible)
+                        .map(|i| i + 1)
+                        .filter(move |&i| i != leaf),
+                )
+            };
+            let mut leaves = pvs_leaves(leaf).collect_vec();
+            if let Some(previous) = previous {
+                let mut seen: HashSet<_> = leaves.iter().copied().collect();
+                leaves.extend(pvs_leaves(previous).filter(|&i| seen.insert(i)));
+            }
+
+            // A face may be in several leaves, it's put into the first one only and the
+            // bounding box of the leaf's range covers the face anyway
+            let mut taken = vec![false; self.faces.len()];
+            let mut batches: HashMap<&str, (Vec<u32>, LeafRanges)> = HashMap::new();
+            for &leaf in &leaves {
+                let faces = vis
+                    .leaf_faces
+                    .get(leaf)
+                    .into_iter()
+                    .flatten()
+                    .filter(|&&i| !mem::replace(&mut taken[i], true))
+                    .map(|&i| &self.faces[i])
+                    .into_group_map_by(|face| face.tex_name.as_str());
+                for (tex, faces) in faces {
+                    let (indices, ranges) = batches.entry(tex).or_default();
+                    let start = indices.len();
+                    faces.iter().for_each(|face| {
+                        let vertices = face.vertices.clone().map(|x| x as usize).collect_vec();
+                        indices.extend(triangulate(vertices).into_iter().map(|x| x as u32));
+                    });
+                    let bounds =
+                        bounds_of(faces.iter().flat_map(|face| [face.bounds.0, face.bounds.1]));
+                    ranges.push((bounds, start..indices.len()));
+                }
+            }
+            debug!(
+                "{} leaves of the PVS are drawn with {} batches",
+                leaves.len(),
+                batches.len()
+            );
+            let batches = batches
+                .into_iter()
+                .map(|(tex, (indices, leaves))| {
+                    let ibo =
+                        IndexBuffer::new(&self.context, PrimitiveType::TrianglesList, &indices)
+                            .unwrap();
+                    (tex.to_owned(), LeafBatch { ibo, leaves })
+                })
+                .collect();
+            *visible_ibos = Some(VisibleIbos { key, batches });
+        }
+
+        let frustum = Frustum::from_matrix(
+            view_projection
+                * ROTATE_MAT
+                * model
+                * Matrix4::from_translation(Vector3::from(self.origin)),
+        );
+        let cached = visible_ibos.as_ref().unwrap();
+        let slices = cached
+            .batches
+            .iter()
+            .flat_map(|(tex, batch)| {
+                batch
+                    .leaves
+                    .iter()
+                    .filter(|(bounds, _)| frustum.contains_aabb(bounds.0, bounds.1))
+                    .map(|(_, range)| range.clone())
+                    // Neighbouring leaves in the frustum are drawn with a single call
+                    .coalesce(|a, b| {
+                        if a.end == b.start {
+                            Ok(a.start..b.end)
+                        } else {
+                            Err((a, b))
+                        }
+                    })
+                    .filter_map(move |range| batch.ibo.slice(range))
+                    .map(move |slice| (tex, slice.into()))
+            })
+            .collect();
+        f(slices)
+    }
+
+    pub fn face_keys(&self) -> HashSet<FaceKey> {
+        self.faces.iter().map(|f| f.key.clone()).collect()
+    }
+
+    /// Splits faces into the ones present in `other` map and the unique ones for `render_diff`.
+    pub fn set_diff<F: ?Sized + Facade>(&mut self, facade: &F, other: &HashSet<FaceKey>) {
+        let (shared, unique): (Vec<_>, Vec<_>) =
+            self.faces.iter().partition(|f| other.contains(&f.key));
+        info!(
+            "Diff summary: [Shared faces={}, Unique faces={}]",
+            shared.len(),
+            unique.len()
+        );
+        self.diff_ibos = Some(DiffIbos {
+            shared: build_ibos(facade, shared.into_iter()),
+            unique: build_ibos(facade, unique.into_iter()),
+        });
+    }
+
+    /// Draws faces unique to this map with the tint of `shading`, and shared ones as is if
+    /// `draw_shared` is set. Requires `set_diff` to be called first.
+    pub fn render_diff<S: Surface>(
+        &self,
+        surface: &mut S,
+        frame: &FrameView,
         draw_params: &DrawParameters,
+        shading: &Shading,
+        draw_shared: bool,
     ) {
-        let lightmap = &self.lightmap;
-        let mvp = projection * view;
-        let mvp: [[f32; 4]; 4] = mvp.into();
-        self.textured_ibos.iter().for_each(|(tex, ibo)| {
-            if let Some(colormap) = self.textures.get(tex) {
-                let uniforms = uniform! {
-                    mvp: mvp,
-                    origin: self.origin,
-                    colormap: colormap.sampled().minify_filter(MinifySamplerFilter::LinearMipmapNearest),
-                    lightmap: lightmap,
+        let FrameView {
+            projection,
+            view,
+            model,
+            camera_position,
+        } = *frame;
+        self.light_styles
+            .write(&style_brightness(shading.animation.time));
+        if let Some(diff) = &self.diff_ibos {
+            if draw_shared {
+                let ibos = &diff.shared;
+                let shading = Shading {
+                    tint: NO_TINT,
+                    ..*shading
                 };
-                surface
-                    .draw(&self.vbo, ibo, &self.program, &uniforms, draw_params)
-                    .unwrap();
+                self.draw_sky(surface, projection, view, model, draw_params);
+                self.draw(surface, ibos, projection, view, model, draw_params, shading);
+                self.draw_brush_models(
+                    surface,
+                    projection,
+                    view,
+                    model,
+                    draw_params,
+                    camera_position,
+                    shading,
+                );
             }
+            let ibos = &diff.unique;
+            self.draw(
+                surface,
+                ibos,
+                projection,
+                view,
+                model,
+                draw_params,
+                *shading,
+            );
+        }
+    }
+
+    /// Draws edges of all faces in the overlay color of `shading` over already rendered
+    /// geometry.
+    pub fn render_wireframe<S: Surface>(
+        &self,
+        surface: &mut S,
+        frame: &FrameView,
+        draw_params: &DrawParameters,
+        pvs: PvsOptions,
+        shading: &Shading,
+        reverse_z: bool,
+    ) {
+        let FrameView {
+            projection,
+            view,
+            model,
+            camera_position,
+        } = *frame;
+        let shading = *shading;
+        let offset = if reverse_z { 1.0 } else { -1.0 };
+        let draw_params = DrawParameters {
+            polygon_mode: PolygonMode::Line,
+            // Pull lines towards the camera, so they win the depth test against their own faces
+            polygon_offset: PolygonOffset {
+                factor: offset,
+                units: offset,
+                line: true,
+                ..PolygonOffset::default()
+            },
+            depth: Depth {
+                write: false,
+                ..draw_params.depth
+            },
+            ..draw_params.clone()
+        };
+        self.with_visible_ibos(projection * view, model, camera_position, pvs, |ibos| {
+            self.draw(
+                surface,
+                ibos,
+                projection,
+                view,
+                model,
+                &draw_params,
+                shading,
+            );
+        });
+        self.brush_models.iter().for_each(|brush_model| {
+            let model = model * Matrix4::from_translation(brush_model.translation);
+            let ibos = &brush_model.ibos;
+            self.draw(
+                surface,
+                ibos,
+                projection,
+                view,
+                model,
+                &draw_params,
+                shading,
+            );
+        });
+    }
+
+    /// Draws opaque brush models, then faces of translucent ones from the farthest to the
+    /// nearest, each blended according to the entity's render mode.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_brush_models<S: Surface>(
+        &self,
+        surface: &mut S,
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        model: Matrix4<f32>,
+        draw_params: &DrawParameters,
+        camera_position: [f32; 3],
+        shading: Shading,
+    ) {
+        let (translucent, opaque): (Vec<_>, Vec<_>) = self
+            .brush_models
+            .iter()
+            .partition(|brush_model| brush_model.translucent.is_some());
+        opaque.into_iter().for_each(|brush_model| {
+            let model = model * Matrix4::from_translation(brush_model.translation);
+            let ibos = &brush_model.ibos;
+            let draw_params = brush_model.draw_params(draw_params);
+            let shading = brush_model.shading(shading);
+            self.draw(
+                surface,
+                ibos,
+                projection,
+                view,
+                model,
+                &draw_params,
+                shading,
+            );
+        });
+
+        let camera = self.to_map_space(model, camera_position);
+        let faces = translucent
+            .into_iter()
+            .flat_map(|brush_model| {
+                let translucent = brush_model.translucent.as_ref().unwrap();
+                translucent.faces.iter().map(move |face| {
+                    let distance = (face.center - camera).magnitude2();
+                    (brush_model, translucent, face, distance)
+                })
+            })
+            .sorted_by(|a, b| b.3.total_cmp(&a.3));
+        for (brush_model, translucent, face, _) in faces {
+            let model = model * Matrix4::from_translation(brush_model.translation);
+            let indices = translucent.ibo.slice(face.indices.clone()).unwrap();
+            self.draw_indices(
+                surface,
+                &face.tex_name,
+                indices,
+                projection,
+                view,
+                model,
+                &brush_model.draw_params(draw_params),
+                brush_model.shading(shading),
+            );
+        }
+    }
+
+    /// Writes depth of sky faces, so the skybox drawn before stays visible through them and
+    /// hides everything behind.
+    fn draw_sky<S: Surface>(
+        &self,
+        surface: &mut S,
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        model: Matrix4<f32>,
+        draw_params: &DrawParameters,
+    ) {
+        let draw_params = DrawParameters {
+            color_mask: (false, false, false, false),
+            ..draw_params.clone()
+        };
+        let mvp: [[f32; 4]; 4] = (projection * view).into();
+        let model: [[f32; 4]; 4] = model.into();
+        let uniforms = uniform! {
+            mvp: mvp,
+            model: model,
+            origin: self.origin,
+        };
+        self.sky_ibos.values().for_each(|ibo| {
+            surface
+                .draw(&self.vbo, ibo, &self.depth_program, &uniforms, &draw_params)
+                .unwrap();
+        });
+    }
+
+    /// Blends decals over the world drawn before, pulled towards the camera so they win the
+    /// depth test against their faces.
+    fn draw_decals<S: Surface>(
+        &self,
+        surface: &mut S,
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        model: Matrix4<f32>,
+        draw_params: &DrawParameters,
+        shading: Shading,
+    ) {
+        let geometry = match &self.decal_geometry {
+            Some(geometry) => geometry,
+            None => return,
+        };
+        // Nearer is greater with reversed depth, see `RenderSettings::reverse_z`
+        let reverse_z = matches!(
+            draw_params.depth.test,
+            DepthTest::IfMore | DepthTest::IfMoreOrEqual
+        );
+        let offset = if reverse_z { 1.0 } else { -1.0 };
+        let draw_params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            polygon_offset: PolygonOffset {
+                factor: offset,
+                units: offset,
+                fill: true,
+                ..PolygonOffset::default()
+            },
+            depth: Depth {
+                write: false,
+                ..draw_params.depth
+            },
+            ..draw_params.clone()
+        };
+        let shading = Shading {
+            decal: true,
+            ..shading
+        };
+        for (tex, range) in &geometry.ranges {
+            self.draw_vertices(
+                surface,
+                tex,
+                geometry.vbo.slice(range.clone()).unwrap(),
+                NoIndices(PrimitiveType::TrianglesList),
+                projection,
+                view,
+                model,
+                &draw_params,
+                shading,
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw<'a, S, I, J>(
+        &self,
+        surface: &mut S,
+        ibos: I,
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        model: Matrix4<f32>,
+        draw_params: &DrawParameters,
+        shading: Shading,
+    ) where
+        S: Surface,
+        I: IntoIterator<Item = (&'a String, J)>,
+        J: Into<IndicesSource<'a>>,
+    {
+        ibos.into_iter().for_each(|(tex, ibo)| {
+            self.draw_indices(
+                surface,
+                tex,
+                ibo,
+                projection,
+                view,
+                model,
+                draw_params,
+                shading,
+            );
         });
     }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_indices<'i, S: Surface, I: Into<IndicesSource<'i>>>(
+        &self,
+        surface: &mut S,
+        tex: &str,
+        indices: I,
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        model: Matrix4<f32>,
+        draw_params: &DrawParameters,
+        shading: Shading,
+    ) {
+        self.draw_vertices(
+            surface,
+            tex,
+            &self.vbo,
+            indices,
+            projection,
+            view,
+            model,
+            draw_params,
+            shading,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_vertices<'v, 'i, S: Surface, V: MultiVerticesSource<'v>, I: Into<IndicesSource<'i>>>(
+        &self,
+        surface: &mut S,
+        tex: &str,
+        vertices: V,
+        indices: I,
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        model: Matrix4<f32>,
+        draw_params: &DrawParameters,
+        shading: Shading,
+    ) {
+        let animated = self.animated_texture(tex, shading.animation);
+        let colormap = self
+            .textures
+            .get_key_value(animated)
+            .or_else(|| self.textures.get_key_value(tex));
+        let (name, colormap, missing) = match colormap {
+            Some((name, colormap)) => (name.as_str(), colormap, false),
+            None => (tex, &self.placeholder, true),
+        };
+        let texture_size = match self.texture_sizes.get(name) {
+            Some(&[width, height]) if missing || self.replaced_textures.contains(name) => {
+                [width as f32, height as f32]
+            }
+            _ => [colormap.width() as f32, colormap.height() as f32],
+        };
+        let animation = shading.animation;
+        let water_warp = animation.surfaces && tex.starts_with(WATER_PREFIX);
+        let scrolls = animation.surfaces
+            && tex
+                .get(..SCROLL_PREFIX.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(SCROLL_PREFIX));
+        let uv_scroll = if scrolls {
+            [animation.time * SCROLL_SPEED, 0.0]
+        } else {
+            [0.0, 0.0]
+        };
+        let density_ramp = shading.density_ramp.unwrap_or_default();
+        let (fog_mode, fog_params) = fog_uniforms(shading.fog);
+        let mvp: [[f32; 4]; 4] = (projection * view).into();
+        let model: [[f32; 4]; 4] = model.into();
+        let uniforms = uniform! {
+            mvp: mvp,
+            model: model,
+            origin: self.origin,
+            colormap: shading
+                .filter
+                .apply(colormap.sampled(), shading.anisotropy),
+            texture_size: texture_size,
+            lightmap: &self.lightmap,
+            light_styles: &self.light_styles,
+            emissive: self.emissive.get(tex).copied().unwrap_or(0.0),
+            overlay_color: shading.overlay_color,
+            tint: shading.tint,
+            opacity: shading.opacity,
+            // Cutouts of masked textures are see-through in any render mode
+            alpha_test: shading.alpha_test || (!shading.decal && tex.starts_with(MASKED_PREFIX)),
+            gamma: shading.gamma,
+            srgb: shading.srgb,
+            fog_mode: fog_mode,
+            fog_color: shading.fog.map_or([0.0; 3], |fog| fog.color),
+            fog_params: fog_params,
+            water_warp: water_warp,
+            warp_time: animation.time,
+            uv_scroll: uv_scroll,
+            density_view: shading.density_ramp.is_some(),
+            density_range: [density_ramp.min, density_ramp.max],
+            density_low_color: density_ramp.low_color,
+            density_high_color: density_ramp.high_color,
+            leaf_colors: shading.leaf_colors,
+        };
+        let two_sided_params;
+        let draw_params = if is_two_sided(tex) {
+            two_sided_params = DrawParameters {
+                backface_culling: BackfaceCullingMode::CullingDisabled,
+                ..draw_params.clone()
+            };
+            &two_sided_params
+        } else {
+            draw_params
+        };
+        surface
+            .draw(vertices, indices, &self.program, &uniforms, draw_params)
+            .unwrap();
+    }
 }
@@ -1,26 +1,244 @@
+mod boxes;
+mod decal;
 mod entities;
+mod frustum;
 mod map;
+mod models;
 mod skybox;
+mod sprites;
+mod void;
 
-use cgmath::Matrix4;
+pub use entities::{Fog, FogFalloff};
+pub use frustum::Frustum;
+pub use map::{DensityRamp, LoadStage, MapReport, TextureFilter, MAX_ANISOTROPY};
+
+use cgmath::{vec3, Deg, Matrix4, Transform};
 use file::{
-    bsp::{LumpType, RawMap},
-    cubemap::Cubemap,
-    wad::Archive,
+    bsp::{contents::Contents, trace::TraceResult, BspError, RawMap},
+    container::{self, read as read_file},
+    cubemap::{Cubemap, FaceTransform},
+    load,
+    replacement::ReplacementDir,
+    wad::{Archive, StreamedArchive, WadOrder},
+};
+use glium::{backend::Facade, DrawParameters, PolygonMode, Surface};
+use itertools::Itertools;
+use log::{debug, error, info, warn};
+use std::{
+    collections::HashMap,
+    io, iter, mem,
+    path::{Path, PathBuf},
+    thread,
 };
-use glium::{backend::Facade, DrawParameters, Surface};
-use log::{debug, error, info};
-use std::{fs::read as read_file, path::Path};
+use thiserror::Error;
 use {
-    entities::{find_info_player_start, get_skyname, get_start_point, parse_entities, Vec3},
-    map::Map,
+    boxes::EntityBoxes,
+    entities::{
+        find_player_start, get_fog, get_skyname, get_start_direction, get_start_point, Vec3,
+    },
+    map::{FrameView, Map, PvsOptions, Shading, TextureAnimation},
+    models::Models,
     skybox::Skybox,
+    sprites::Sprites,
+    void::Checkerboard,
 };
 
+const WIREFRAME_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+/// Tint of faces found only in the base map.
+const BASE_ONLY_TINT: [f32; 3] = [1.0, 0.25, 0.25];
+/// Tint of faces found only in the compared map.
+const COMPARED_ONLY_TINT: [f32; 3] = [0.25, 1.0, 0.25];
+
+/// Bounds of `RenderSettings::gamma`.
+pub const GAMMA_RANGE: (f32, f32) = (0.5, 3.0);
+
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    /// Draw only the skybox, skipping map geometry.
+    pub skybox_only: bool,
+    /// Rotation of the world around the vertical axis, in degrees.
+    pub world_rotate: f32,
+    /// Translation of the world, in map units (Z is up).
+    pub world_offset: [f32; 3],
+    /// Apply `world_rotate` to the skybox as well.
+    pub rotate_skybox: bool,
+    /// Draw face edges over the textured geometry.
+    pub wireframe_overlay: bool,
+    /// Draw map geometry as lines only, the skybox stays filled.
+    pub wireframe: bool,
+    /// Use the compared map as the base one, see `LevelOptions::compare_bsp`.
+    pub compare_swapped: bool,
+    /// The projection maps the near plane to depth 1 and the far one to 0, so the depth test
+    /// must be `IfMore` and the buffer cleared to 0. Only the clip z is negated: depth stays in
+    /// OpenGL's -1..1 clip range and the window's integer buffer, so precision is the same as
+    /// without it.
+    pub reverse_z: bool,
+    /// Sampling of map textures.
+    pub texture_filter: TextureFilter,
+    /// Maximal anisotropy of `TextureFilter::Anisotropic`, clamped to `1..=MAX_ANISOTROPY`.
+    pub anisotropy: u16,
+    /// Color faces by their texel density using `density_ramp` instead of textures.
+    pub texel_density: bool,
+    pub density_ramp: DensityRamp,
+    /// Color faces of the world by the leaves they're in instead of textures, to see leaf
+    /// boundaries and what the PVS culls. Colors are hashed from leaf indices, so they're the
+    /// same every run, brush models are gray.
+    pub leaf_colors: bool,
+    /// Draw wireframe boxes at origins of point entities, colored by their classnames.
+    pub entity_boxes: bool,
+    /// Draw studio models of entities, see `LevelOptions::game_dir`.
+    pub studio_models: bool,
+    /// Draw sprites of entities, also found in `LevelOptions::game_dir`.
+    pub sprites: bool,
+    /// Show alternate frames of toggled animated textures (`+a` instead of `+0`), like after
+    /// switching a button on in the game.
+    pub toggle_textures: bool,
+    /// Freeze animated textures and light styles at their first frames and don't warp or scroll
+    /// any textures.
+    pub no_animations: bool,
+    /// Gamma correction of lit colors, above 1 brightens dark areas. Clamped to `GAMMA_RANGE`.
+    pub gamma: f32,
+    /// Fog used instead of the map's one, see `Level::fog`.
+    pub fog: Option<Fog>,
+    /// Background behind the map, see `Level::clear_color`.
+    pub clear_color: [f32; 3],
+    /// Draw a faint checkerboard of `clear_color` behind the map instead of the skybox, so
+    /// leaks and holes are seen. There's none in the fog.
+    pub void_checkerboard: bool,
+    /// Color the screen is tinted with while the camera is in water, see `Underwater`.
+    pub water_tint: [f32; 3],
+    /// Textures and lightmaps are taken as sRGB, multiplied in linear space and the result is
+    /// converted back. Mostly midtones of interpolated lightmaps change: gradients between
+    /// luxels of different brightness are brighter in the middle instead of sagging like they
+    /// do in gamma space. Filtering of textures and blending of translucent faces stay in
+    /// gamma space.
+    pub srgb: bool,
+    /// Leaf of the base map whose PVS is drawn wherever the camera is, to fly around and see
+    /// what's visible from it, see `Level::leaf_at`. Compared maps are drawn whole anyway.
+    pub locked_leaf: Option<usize>,
+    /// Keep drawing the PVS of the previous leaf for `pvs_blend_time` seconds after the camera
+    /// changes leaves, hiding faces popping in and out at leaf boundaries.
+    pub pvs_blend: bool,
+    pub pvs_blend_time: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            skybox_only: false,
+            world_rotate: 0.0,
+            world_offset: [0.0; 3],
+            rotate_skybox: false,
+            wireframe_overlay: false,
+            wireframe: false,
+            compare_swapped: false,
+            reverse_z: false,
+            texture_filter: TextureFilter::default(),
+            anisotropy: MAX_ANISOTROPY,
+            texel_density: false,
+            density_ramp: DensityRamp::default(),
+            leaf_colors: false,
+            entity_boxes: false,
+            studio_models: true,
+            sprites: true,
+            toggle_textures: false,
+            no_animations: false,
+            gamma: 1.0,
+            fog: None,
+            clear_color: [0.0; 3],
+            void_checkerboard: false,
+            water_tint: [0.1, 0.35, 0.4],
+            srgb: false,
+            locked_leaf: None,
+            pvs_blend: true,
+            pvs_blend_time: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelOptions {
+    /// Per-face skybox transforms, in the order of `Cubemap::side_names`.
+    pub sky_transforms: [FaceTransform; 6],
+    /// Lowercase texture name to emissive strength, where 1 is fullbright.
+    pub emissive: HashMap<String, f32>,
+    /// Read wad entries on demand from the open file instead of loading whole wads.
+    /// Wads inside zip or pak archives are always loaded whole.
+    pub stream_wads: bool,
+    /// Merge adjacent coplanar faces, see `Map::new`.
+    pub merge_faces: bool,
+    /// Second map drawn overlaid, faces only in the base map are tinted red and only in this one
+    /// are tinted green.
+    pub compare_bsp: Option<PathBuf>,
+    /// Directory of png, tga or bmp images named after textures, loaded in place of those
+    /// from the map and wads.
+    pub texture_dir: Option<PathBuf>,
+    /// Directory `.mdl` and `.spr` paths of entities are relative to, e.g. `valve`. Defaults to the parent
+    /// of the map's directory unless the map is in an archive.
+    pub game_dir: Option<PathBuf>,
+    /// Clip hull collisions are traced with: 0 is a point, 1 the standing player, 2 large
+    /// monsters and 3 the crouching player. Clamped to 3.
+    pub hull: usize,
+}
+
+impl Default for LevelOptions {
+    fn default() -> Self {
+        Self {
+            sky_transforms: Default::default(),
+            emissive: HashMap::new(),
+            stream_wads: false,
+            merge_faces: false,
+            compare_bsp: None,
+            texture_dir: None,
+            game_dir: None,
+            hull: 1,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("failed to read {kind} at {}: {source}", path.display())]
+    Read {
+        kind: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    #[error("failed to parse {kind} at {}: {source}", path.display())]
+    Bsp {
+        kind: &'static str,
+        path: PathBuf,
+        source: BspError,
+    },
+    #[error("malformed {lump} lump of bsp at {}", path.display())]
+    Lump { lump: &'static str, path: PathBuf },
+    /// Errors of `Level::from_bytes`, which has no paths.
+    #[error("failed to parse bsp: {0}")]
+    BspBytes(BspError),
+    #[error("malformed {0} lump of bsp")]
+    LumpBytes(&'static str),
+}
+
 pub struct Level {
     start_point: Option<Vec3>,
+    fog: Option<Fog>,
+    start_direction: Option<Vec3>,
     map_render: Map,
+    compare_render: Option<Map>,
     skybox: Option<Skybox>,
+    /// Boxes of point entities of the base map.
+    entity_boxes: EntityBoxes,
+    /// Studio models of entities of the base map.
+    models: Models,
+    /// Sprites of entities of the base map.
+    sprites: Sprites,
+    void: Checkerboard,
+    /// Which of the scanned wads provides each entry, see `reload_wad`.
+    wad_order: WadOrder,
+    /// Wads in the order of `wad_order`.
+    ordered_wads: Vec<PathBuf>,
+    /// Directory studio models and sprites were loaded from.
+    game_dir: Option<PathBuf>,
 }
 
 impl Level {
@@ -29,61 +247,597 @@ impl Level {
         bsp_path: P,
         wad_paths: &[P],
         skybox_path: Option<P>,
-    ) -> Self {
-        // TODO : remove unwraps
-        let bsp_file = read_file(bsp_path).unwrap();
-        let raw_map = RawMap::parse(&bsp_file).unwrap();
-        let mut map_render = Map::new(facade, &raw_map);
+        options: &LevelOptions,
+    ) -> Result<Self, LoadError> {
+        Self::with_progress(facade, bsp_path, wad_paths, skybox_path, options, None)
+    }
 
-        for path in wad_paths {
-            if map_render.is_textures_loaded() {
+    /// Same as `new`, but reports loading of the base map to `progress`, see `Map::new`.
+    pub fn with_progress<F: ?Sized + Facade, P: AsRef<Path>>(
+        facade: &F,
+        bsp_path: P,
+        wad_paths: &[P],
+        skybox_path: Option<P>,
+        options: &LevelOptions,
+        progress: Option<&dyn Fn(LoadStage, f32)>,
+    ) -> Result<Self, LoadError> {
+        let bsp_path = bsp_path.as_ref();
+        let bsp_file = read_file(bsp_path).map_err(|source| LoadError::Read {
+            kind: "bsp",
+            path: bsp_path.to_path_buf(),
+            source,
+        })?;
+        let is_streamed = |path: &Path| options.stream_wads && !container::is_archived(path);
+        // Reading takes most of the time, so whole wads are read in parallel. Textures are
+        // still taken in the order of `wad_paths`, the first wad having a texture wins.
+        let wad_files = read_files(
+            &wad_paths
+                .iter()
+                .map(|path| Some(path.as_ref()).filter(|path| !is_streamed(path)))
+                .collect_vec(),
+        );
+        let wad_bytes = wad_files
+            .iter()
+            .map(|file| match file {
+                Some(Ok(file)) => file.as_slice(),
+                _ => &[],
+            })
+            .collect_vec();
+        let loaded = load::parse_map(&bsp_file, &wad_bytes).map_err(|source| LoadError::Bsp {
+            kind: "bsp",
+            path: bsp_path.to_path_buf(),
+            source,
+        })?;
+        let raw_map = loaded.map;
+        let mut map_render = load_map(facade, &raw_map, bsp_path, options, progress)?;
+
+        let mut compare_render = None;
+        if let Some(path) = &options.compare_bsp {
+            let compare_file = read_file(path).map_err(|source| LoadError::Read {
+                kind: "compared bsp",
+                path: path.clone(),
+                source,
+            })?;
+            let compare_map = RawMap::parse(&compare_file).map_err(|source| LoadError::Bsp {
+                kind: "compared bsp",
+                path: path.clone(),
+                source,
+            })?;
+            compare_render = Some(load_map(facade, &compare_map, path, options, None)?);
+        }
+        if let Some(compare_render) = &mut compare_render {
+            let (base_keys, compare_keys) = (map_render.face_keys(), compare_render.face_keys());
+            map_render.set_diff(facade, &compare_keys);
+            compare_render.set_diff(facade, &base_keys);
+        }
+
+        if let Some(dir) = &options.texture_dir {
+            match ReplacementDir::scan(dir) {
+                Ok(replacements) => {
+                    let maps = iter::once(&mut map_render).chain(compare_render.as_mut());
+                    let replaced: usize = maps
+                        .map(|map| map.load_replacements(facade, &replacements))
+                        .sum();
+                    info!("{} textures replaced from {}", replaced, dir.display());
+                }
+                Err(e) => warn!("Failed to scan textures at {}: {}", dir.display(), e),
+            }
+        }
+
+        if let Some(progress) = progress {
+            progress(LoadStage::WadTextures, 0.0);
+        }
+        // Paths of the wads pushed to `wad_order`, broken ones are left out
+        let (mut wad_order, mut ordered_paths) = (WadOrder::new(), vec![]);
+        for (i, path) in wad_paths.iter().enumerate() {
+            let maps = iter::once(&mut map_render).chain(compare_render.as_mut());
+            let mut maps = maps.filter(|map| !map.is_textures_loaded()).collect_vec();
+            if maps.is_empty() {
                 break;
             }
-            let file = read_file(path).unwrap();
-            let archive = Archive::parse(&file).unwrap();
             if let Some(file_name) = path.as_ref().file_name() {
                 debug!("Scanning {:?} for textures", file_name);
             }
-            map_render.load_from_archive(facade, &archive);
+            // Textures of a broken wad may still be found in the rest of them
+            if is_streamed(path.as_ref()) {
+                match StreamedArchive::open(path) {
+                    Ok(archive) => {
+                        let names = archive.entries().map(|(name, _)| name);
+                        push_wad(&mut wad_order, &mut ordered_paths, path.as_ref(), names);
+                        maps.iter_mut()
+                            .for_each(|map| map.load_from_streamed_archive(facade, &archive))
+                    }
+                    Err(e) => warn!("Failed to open wad at {}: {}", path.as_ref().display(), e),
+                }
+            } else {
+                match (&wad_files[i], &loaded.wads[i]) {
+                    (Some(Err(e)), _) => {
+                        warn!("Failed to read wad at {}: {}", path.as_ref().display(), e)
+                    }
+                    (_, Some(archive)) => {
+                        let names = archive.entries().map(|(name, _)| name);
+                        push_wad(&mut wad_order, &mut ordered_paths, path.as_ref(), names);
+                        maps.iter_mut()
+                            .for_each(|map| map.load_from_archive(facade, archive))
+                    }
+                    (_, None) => warn!("Failed to parse wad at {}", path.as_ref().display()),
+                }
+            }
+            if let Some(progress) = progress {
+                progress(
+                    LoadStage::WadTextures,
+                    (i + 1) as f32 / wad_paths.len() as f32,
+                );
+            }
+        }
+        if let Some(progress) = progress {
+            progress(LoadStage::WadTextures, 1.0);
         }
 
-        let entities = parse_entities(raw_map.lump_data(LumpType::Entities)).unwrap();
-        let info_player_start = find_info_player_start(&entities);
-        let start_point = info_player_start.and_then(get_start_point);
-        let skybox = get_skyname(&entities).and_then(|skyname| {
-            skybox_path.and_then(|skybox_path| {
-                if let Ok(cubemap) = Cubemap::read(&skyname, skybox_path) {
+        let skyname = get_skyname(&raw_map);
+        // The map is still worth viewing without its sky, so faces lacking files are reported
+        // and a flat color is drawn instead
+        let skybox = skybox_path.map(|skybox_path| {
+            let skybox_path = skybox_path.as_ref();
+            match Cubemap::read_with_transforms(&skyname, skybox_path, options.sky_transforms) {
+                Ok(cubemap) => {
                     info!("Skybox loaded: {}", skyname);
-                    Some(Skybox::new(facade, &cubemap))
-                } else {
-                    error!("Error loading skybox: {}", skyname);
-                    None
+                    Skybox::new(facade, Some(&cubemap))
                 }
-            })
+                Err(e) => {
+                    error!("Error loading skybox {}: {}", skyname, e);
+                    for path in Cubemap::missing_faces(&skyname, skybox_path) {
+                        warn!("Skybox face not found: {}", path.display());
+                    }
+                    Skybox::new(facade, None)
+                }
+            }
         });
 
+        let game_dir = options.game_dir.clone().or_else(|| {
+            Some(bsp_path.parent()?.parent()?.to_path_buf())
+                .filter(|_| !container::is_archived(bsp_path))
+        });
+        let mut level = Self::with_map(
+            facade,
+            &raw_map,
+            map_render,
+            compare_render,
+            skybox,
+            game_dir.as_deref(),
+        );
+        level.wad_order = wad_order;
+        level.ordered_wads = ordered_paths.into_iter().map(Path::to_path_buf).collect();
+        Ok(level)
+    }
+
+    /// Same as `new`, but takes contents of the map and wads instead of paths, e.g. to load
+    /// them from the network or from resources of the program. Both go through
+    /// `file::load::parse_map`, so textures are taken from the first wad having them and broken
+    /// wads are skipped. There's no skybox, studio models or sprites and paths of `options`
+    /// are ignored.
+    pub fn from_bytes<F: ?Sized + Facade>(
+        facade: &F,
+        bsp: &[u8],
+        wads: &[&[u8]],
+        options: &LevelOptions,
+    ) -> Result<Self, LoadError> {
+        let loaded = load::parse_map(bsp, wads).map_err(LoadError::BspBytes)?;
+        let raw_map = loaded.map;
+        let mut map_render = Map::new(facade, &raw_map, options.merge_faces, options.hull, None)
+            .map_err(LoadError::LumpBytes)?;
+        map_render.set_emissive(&options.emissive);
+        for (i, wad) in loaded.wads.iter().enumerate() {
+            if map_render.is_textures_loaded() {
+                break;
+            }
+            match wad {
+                Some(archive) => map_render.load_from_archive(facade, archive),
+                None => warn!("Failed to parse wad #{}", i),
+            }
+        }
+        Ok(Self::with_map(
+            facade, &raw_map, map_render, None, None, None,
+        ))
+    }
+
+    /// Level of loaded maps, which didn't scan any wads yet. Studio models are loaded from
+    /// `game_dir`.
+    fn with_map<F: ?Sized + Facade>(
+        facade: &F,
+        raw_map: &RawMap,
+        map_render: Map,
+        compare_render: Option<Map>,
+        skybox: Option<Skybox>,
+        game_dir: Option<&Path>,
+    ) -> Self {
+        for name in map_render.missing_decal_textures() {
+            warn!(
+                "Decal texture `{}` isn't in any of the wads, skipping it",
+                name
+            );
+        }
+        let report = map_render.report();
+        if !report.missing_textures.is_empty() {
+            warn!(
+                "{} faces reference {} missing textures: {}",
+                report.missing_texture_faces,
+                report.missing_textures.len(),
+                report.missing_textures.join(", ")
+            );
+        }
+        if report.degenerate_faces > 0 {
+            warn!("{} faces have no area", report.degenerate_faces);
+        }
+
+        let player_start = find_player_start(raw_map);
         Self {
-            start_point,
+            start_point: player_start.and_then(get_start_point),
+            fog: get_fog(raw_map),
+            start_direction: player_start.and_then(get_start_direction),
             map_render,
+            compare_render,
             skybox,
+            entity_boxes: EntityBoxes::new(facade, raw_map),
+            models: Models::new(facade, raw_map, game_dir),
+            sprites: Sprites::new(facade, raw_map, game_dir),
+            void: Checkerboard::new(facade),
+            wad_order: WadOrder::new(),
+            ordered_wads: vec![],
+            game_dir: game_dir.map(Path::to_path_buf),
+        }
+    }
+
+    /// Reads the base map at `bsp_path` again, e.g. after it was recompiled. Textures loaded
+    /// from wads are kept and wads are read again only for textures which are still missing.
+    /// The skybox and the compared map stay as they were. On failure the level is unchanged.
+    pub fn reload_map<F: ?Sized + Facade>(
+        &mut self,
+        facade: &F,
+        bsp_path: &Path,
+        options: &LevelOptions,
+    ) -> Result<(), LoadError> {
+        let bsp_file = read_file(bsp_path).map_err(|source| LoadError::Read {
+            kind: "bsp",
+            path: bsp_path.to_path_buf(),
+            source,
+        })?;
+        let raw_map = RawMap::parse(&bsp_file).map_err(|source| LoadError::Bsp {
+            kind: "bsp",
+            path: bsp_path.to_path_buf(),
+            source,
+        })?;
+        let mut map_render = load_map(facade, &raw_map, bsp_path, options, None)?;
+
+        if let Some(dir) = &options.texture_dir {
+            match ReplacementDir::scan(dir) {
+                Ok(replacements) => {
+                    map_render.load_replacements(facade, &replacements);
+                }
+                Err(e) => warn!("Failed to scan textures at {}: {}", dir.display(), e),
+            }
+        }
+        map_render.reuse_wad_textures(facade, &mut self.map_render);
+        // Streamed wads are read whole too, as there are only a few new textures
+        for path in &self.ordered_wads {
+            if map_render.is_textures_loaded() {
+                break;
+            }
+            match read_file(path) {
+                Ok(file) => match Archive::parse(&file) {
+                    Ok(archive) => map_render.load_from_archive(facade, &archive),
+                    Err(_) => warn!("Failed to parse wad at {}", path.display()),
+                },
+                Err(e) => warn!("Failed to read wad at {}: {}", path.display(), e),
+            }
+        }
+
+        let mut compare_render = self.compare_render.take();
+        if let Some(compare_render) = &mut compare_render {
+            let (base_keys, compare_keys) = (map_render.face_keys(), compare_render.face_keys());
+            map_render.set_diff(facade, &compare_keys);
+            compare_render.set_diff(facade, &base_keys);
+        }
+        let game_dir = self.game_dir.take();
+        let mut level = Self::with_map(
+            facade,
+            &raw_map,
+            map_render,
+            compare_render,
+            self.skybox.take(),
+            game_dir.as_deref(),
+        );
+        level.wad_order = mem::take(&mut self.wad_order);
+        level.ordered_wads = mem::take(&mut self.ordered_wads);
+        *self = level;
+        info!("Reloaded map at {}", bsp_path.display());
+        Ok(())
+    }
+
+    /// Reads the wad at `path`, one of `wad_paths` passed on loading, again and reloads textures
+    /// it provides. Wads scanned after it are kept out of that, just like on loading.
+    pub fn reload_wad<F: ?Sized + Facade>(&mut self, facade: &F, path: &Path) {
+        let file = match read_file(path) {
+            Ok(file) => file,
+            Err(e) => return warn!("Failed to read wad at {}: {}", path.display(), e),
+        };
+        let archive = match Archive::parse(&file) {
+            Ok(archive) => archive,
+            Err(_) => return warn!("Failed to parse wad at {}", path.display()),
+        };
+        let index = self.ordered_wads.iter().position(|wad| wad == path);
+        let wad_order = &self.wad_order;
+        let provided = |name: &str| index.is_some() && wad_order.provider(name) == index;
+        for map in iter::once(&mut self.map_render).chain(self.compare_render.as_mut()) {
+            map.reload_textures(facade, &archive, provided);
         }
+        info!("Reloaded wad at {}", path.display());
     }
 
-    pub const fn start_point(&self) -> Option<Vec3> {
+    /// Fog of the settings, otherwise of the map. Fogged levels are drawn without the skybox,
+    /// so the fog color should be the clear one.
+    pub fn fog(&self, settings: &RenderSettings) -> Option<Fog> {
+        settings.fog.or(self.fog)
+    }
+
+    /// Color to clear the screen with before `render`, the fog's one in fogged levels and
+    /// `RenderSettings::clear_color` otherwise.
+    pub fn clear_color(&self, settings: &RenderSettings) -> (f32, f32, f32, f32) {
+        let [r, g, b] = self
+            .fog(settings)
+            .map_or(settings.clear_color, |fog| fog.color);
+        (r, g, b, 1.0)
+    }
+
+    /// Problems of faces of the base map, the compared one is left out.
+    pub fn report(&self) -> MapReport {
+        self.map_render.report()
+    }
+
+    /// Where the player spawns in the view space, see `find_player_start`.
+    pub fn start_point(&self, settings: &RenderSettings) -> Option<[f32; 3]> {
+        let to_view = self.map_render.to_view_transform(world_model(settings));
         self.start_point
+            .map(|point| to_view.transform_point(point.into()).into())
+    }
+
+    /// Direction the spawned player looks at in the view space.
+    pub fn start_direction(&self, settings: &RenderSettings) -> Option<[f32; 3]> {
+        let to_view = self.map_render.to_view_transform(world_model(settings));
+        self.start_direction
+            .map(|direction| to_view.transform_vector(direction.into()).into())
     }
 
+    /// Traces the standing player's box center through the base map, all in the view space.
+    pub fn trace(&self, settings: &RenderSettings, start: [f32; 3], end: [f32; 3]) -> TraceResult {
+        self.base_map(settings)
+            .trace(world_model(settings), start, end)
+    }
+
+    /// Converts a point of the view space to map units of the base map.
+    pub fn to_map_space(&self, settings: &RenderSettings, point: [f32; 3]) -> [f32; 3] {
+        self.base_map(settings)
+            .to_map_space(world_model(settings), point)
+            .into()
+    }
+
+    /// Leaf of the base map containing a point of the view space, 0 if it's outside. Leaves
+    /// are also the clusters of the PVS.
+    pub fn leaf_at(&self, settings: &RenderSettings, point: [f32; 3]) -> usize {
+        self.base_map(settings)
+            .find_leaf(world_model(settings), point)
+    }
+
+    /// Contents of the base map at a point of the view space, e.g. `Contents::Water` when
+    /// it's under water.
+    pub fn contents_at(&self, settings: &RenderSettings, point: [f32; 3]) -> Contents {
+        self.base_map(settings)
+            .contents_at(world_model(settings), point)
+    }
+
+    /// The map drawn as the base one, see `RenderSettings::compare_swapped`.
+    fn base_map(&self, settings: &RenderSettings) -> &Map {
+        match &self.compare_render {
+            Some(compare_render) if settings.compare_swapped => compare_render,
+            _ => &self.map_render,
+        }
+    }
+
+    /// `camera_position` is in the view space and selects faces visible by the PVS. `time` is
+    /// in seconds and selects frames of animated textures.
+    #[allow(clippy::too_many_arguments)]
     pub fn render<S: Surface>(
         &self,
         surface: &mut S,
         projection: Matrix4<f32>,
         view: Matrix4<f32>,
+        camera_position: [f32; 3],
+        time: f32,
         draw_params: &DrawParameters,
+        settings: &RenderSettings,
     ) {
-        if let Some(skybox) = &self.skybox {
-            skybox.render(surface, projection, view, draw_params);
+        let fog = self.fog(settings);
+        // The sky is infinitely far, so it's all in the fog
+        if fog.is_none() && settings.void_checkerboard {
+            self.void.render(surface, settings.clear_color);
+        } else if let Some(skybox) = self.skybox.as_ref().filter(|_| fog.is_none()) {
+            let sky_view = if settings.rotate_skybox {
+                // Map's Z axis is Y in the view space
+                view * Matrix4::from_angle_y(Deg(settings.world_rotate))
+            } else {
+                view
+            };
+            skybox.render(
+                surface,
+                projection,
+                sky_view,
+                draw_params,
+                settings.reverse_z,
+            );
+        }
+        if !settings.skybox_only {
+            let line_params;
+            let draw_params = if settings.wireframe {
+                line_params = DrawParameters {
+                    polygon_mode: PolygonMode::Line,
+                    ..draw_params.clone()
+                };
+                &line_params
+            } else {
+                draw_params
+            };
+            let model = world_model(settings);
+            let animation = TextureAnimation {
+                time: if settings.no_animations { 0.0 } else { time },
+                toggled: settings.toggle_textures,
+                surfaces: !settings.no_animations,
+            };
+            let gamma = settings.gamma.clamp(GAMMA_RANGE.0, GAMMA_RANGE.1);
+            // The locked leaf is of the base map, which is this one unless they're swapped
+            let pvs = PvsOptions {
+                locked_leaf: settings.locked_leaf.filter(|_| !settings.compare_swapped),
+                blend: if settings.pvs_blend {
+                    settings.pvs_blend_time
+                } else {
+                    0.0
+                },
+                time,
+            };
+            let shading = Shading {
+                gamma,
+                srgb: settings.srgb,
+                anisotropy: settings.anisotropy.clamp(1, MAX_ANISOTROPY),
+                fog,
+                ..Shading::textured(settings.texture_filter, animation)
+            };
+            let frame = FrameView {
+                projection,
+                view,
+                model,
+                camera_position,
+            };
+            if let Some(compare_render) = &self.compare_render {
+                let (base, compared) = if settings.compare_swapped {
+                    (compare_render, &self.map_render)
+                } else {
+                    (&self.map_render, compare_render)
+                };
+                let tinted = |tint| Shading { tint, ..shading };
+                base.render_diff(surface, &frame, draw_params, &tinted(BASE_ONLY_TINT), true);
+                compared.render_diff(
+                    surface,
+                    &frame,
+                    draw_params,
+                    &tinted(COMPARED_ONLY_TINT),
+                    false,
+                );
+            } else {
+                let shading = Shading {
+                    density_ramp: Some(settings.density_ramp).filter(|_| settings.texel_density),
+                    leaf_colors: settings.leaf_colors,
+                    ..shading
+                };
+                self.map_render
+                    .render(surface, &frame, draw_params, pvs, &shading);
+            }
+            if settings.wireframe_overlay {
+                let shading = Shading {
+                    overlay_color: WIREFRAME_COLOR,
+                    ..Shading::textured(settings.texture_filter, animation)
+                };
+                self.map_render.render_wireframe(
+                    surface,
+                    &frame,
+                    draw_params,
+                    pvs,
+                    &shading,
+                    settings.reverse_z,
+                );
+            }
+            let map_to_clip = projection * view * self.map_render.to_view_transform(model);
+            if settings.studio_models {
+                self.models
+                    .render(surface, map_to_clip, gamma, fog, draw_params);
+            }
+            if settings.entity_boxes {
+                self.entity_boxes
+                    .render(surface, map_to_clip.into(), draw_params);
+            }
+            if settings.sprites {
+                let map_to_view = view * self.map_render.to_view_transform(model);
+                self.sprites.render(
+                    surface,
+                    projection,
+                    map_to_view,
+                    animation.time,
+                    fog,
+                    draw_params,
+                );
+            }
         }
-        self.map_render
-            .render(surface, projection, view, draw_params);
     }
 }
+
+/// Adds entries of the next scanned wad to `order`, telling which of them are shadowed by earlier
+/// wads.
+fn push_wad<'a, 'n>(
+    order: &mut WadOrder,
+    ordered_paths: &mut Vec<&'a Path>,
+    path: &'a Path,
+    names: impl Iterator<Item = &'n str>,
+) {
+    for (name, provider) in order.push(names) {
+        info!(
+            "Entry `{}` of {} is shadowed by {}",
+            name,
+            path.display(),
+            ordered_paths[provider].display()
+        );
+    }
+    ordered_paths.push(path);
+}
+
+/// Reads files on their own threads, `None` paths are skipped. Results are in the order of
+/// `paths`.
+fn read_files(paths: &[Option<&Path>]) -> Vec<Option<io::Result<Vec<u8>>>> {
+    thread::scope(|scope| {
+        let handles = paths
+            .iter()
+            .map(|path| path.map(|path| scope.spawn(move || read_file(path))))
+            .collect_vec();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle.map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("reading thread panicked")))
+                })
+            })
+            .collect()
+    })
+}
+
+fn load_map<F: ?Sized + Facade>(
+    facade: &F,
+    raw_map: &RawMap,
+    path: &Path,
+    options: &LevelOptions,
+    progress: Option<&dyn Fn(LoadStage, f32)>,
+) -> Result<Map, LoadError> {
+    let mut map =
+        Map::new(facade, raw_map, options.merge_faces, options.hull, progress).map_err(|lump| {
+            LoadError::Lump {
+                lump,
+                path: path.to_path_buf(),
+            }
+        })?;
+    map.set_emissive(&options.emissive);
+    Ok(map)
+}
+
+/// Transform of the map by `world_offset` and `world_rotate`.
+fn world_model(settings: &RenderSettings) -> Matrix4<f32> {
+    let [x, y, z] = settings.world_offset;
+    Matrix4::from_translation(vec3(x, y, z)) * Matrix4::from_angle_z(Deg(settings.world_rotate))
+}
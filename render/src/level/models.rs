@@ -0,0 +1,221 @@
+use super::{
+    entities::{get_model_entities, Fog},
+    map::fog_uniforms,
+};
+use cgmath::{vec3, Deg, Matrix4};
+use file::{
+    bsp::RawMap,
+    container::read as read_file,
+    mdl::{StudioModel, Texture},
+};
+use glium::{
+    backend::Facade,
+    implement_vertex,
+    index::{NoIndices, PrimitiveType},
+    program,
+    texture::{MipmapsOption, RawImage2d, Texture2d},
+    uniform, BackfaceCullingMode, DrawParameters, Program, Surface, VertexBuffer,
+};
+use log::{info, warn};
+use std::{collections::HashMap, ops::Range, path::Path};
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    tex_coords: [f32; 2],
+}
+
+implement_vertex!(Vertex, position, normal, tex_coords);
+
+/// Model uploaded in its reference pose, meshes are ranges of the buffer.
+struct LoadedModel {
+    vbo: VertexBuffer<Vertex>,
+    meshes: Vec<(Range<usize>, usize)>,
+    textures: Vec<(Texture2d, bool)>,
+}
+
+impl LoadedModel {
+    fn new<F: ?Sized + Facade>(facade: &F, model: &StudioModel) -> Result<Self, String> {
+        let pose = model.pose();
+        let mut vertices = vec![];
+        let mut meshes = vec![];
+        for mesh in &model.meshes {
+            let texture = match model.skin_texture(mesh.skin_ref) {
+                Some(texture) => texture,
+                None => continue,
+            };
+            let Texture { width, height, .. } = model.textures[texture];
+            let start = vertices.len();
+            vertices.extend(mesh.vertices.iter().map(|vertex| {
+                let (position, normal) = StudioModel::posed(&pose, vertex);
+                let [s, t] = vertex.tex_coords;
+                Vertex {
+                    position,
+                    normal,
+                    tex_coords: [s / width.max(1) as f32, t / height.max(1) as f32],
+                }
+            }));
+            meshes.push((start..vertices.len(), texture));
+        }
+        let textures = model
+            .textures
+            .iter()
+            .map(|texture| {
+                let raw = RawImage2d::from_raw_rgba_reversed(
+                    &texture.pixels,
+                    (texture.width, texture.height),
+                );
+                let uploaded =
+                    Texture2d::with_mipmaps(facade, raw, MipmapsOption::AutoGeneratedMipmaps)
+                        .map_err(|e| {
+                            format!("failed to upload texture `{}`: {}", texture.name, e)
+                        })?;
+                Ok((uploaded, texture.is_masked()))
+            })
+            .collect::<Result<_, String>>()?;
+        let vbo = VertexBuffer::new(facade, &vertices).map_err(|e| e.to_string())?;
+        Ok(Self {
+            vbo,
+            meshes,
+            textures,
+        })
+    }
+}
+
+/// Reads the model at `path` of the game directory, with textures of its `*t.mdl` file if it
+/// has none of its own.
+fn read_model(game_dir: &Path, path: &str) -> Result<StudioModel, String> {
+    let full_path = game_dir.join(path);
+    let file = read_file(&full_path).map_err(|e| e.to_string())?;
+    let mut model = StudioModel::parse(&file).map_err(|_| "malformed model".to_string())?;
+    if model.textures.is_empty() {
+        let stem = &path[..path.len() - ".mdl".len()];
+        let texture_file = ["t.mdl", "T.mdl"]
+            .iter()
+            .find_map(|suffix| read_file(game_dir.join(format!("{}{}", stem, suffix))).ok())
+            .ok_or_else(|| "textures of the model are missing".to_string())?;
+        let (textures, skin) = StudioModel::parse_textures(&texture_file)
+            .map_err(|_| "malformed textures of the model".to_string())?;
+        model.textures = textures;
+        model.skin = skin;
+    }
+    Ok(model)
+}
+
+/// Studio models of entities, drawn in the reference pose of their first sequences at the
+/// entities' origins and angles. They're lit evenly from above, as they have no lightmaps.
+pub struct Models {
+    models: Vec<LoadedModel>,
+    /// Models drawn by the entities with their transforms in map's coordinates.
+    placements: Vec<(usize, Matrix4<f32>)>,
+    program: Program,
+}
+
+impl Models {
+    /// Paths of `model` keys are relative to `game_dir`, models which can't be read, parsed or
+    /// uploaded are skipped. There are no models without `game_dir`.
+    pub fn new<F: ?Sized + Facade>(facade: &F, map: &RawMap, game_dir: Option<&Path>) -> Self {
+        let entities = game_dir.map_or_else(Vec::new, |_| get_model_entities(map));
+        let mut models = vec![];
+        let mut indices: HashMap<String, Option<usize>> = HashMap::new();
+        let mut placements = vec![];
+        for entity in &entities {
+            let index = *indices
+                .entry(entity.model.to_ascii_lowercase())
+                .or_insert_with(|| {
+                    let loaded = read_model(game_dir.unwrap(), &entity.model)
+                        .and_then(|model| LoadedModel::new(facade, &model));
+                    match loaded {
+                        Ok(model) => {
+                            models.push(model);
+                            Some(models.len() - 1)
+                        }
+                        Err(e) => {
+                            warn!(
+                                "Failed to load model `{}`, skipping it: {}",
+                                entity.model, e
+                            );
+                            None
+                        }
+                    }
+                });
+            if let Some(index) = index {
+                let (x, y, z) = entity.origin;
+                let (pitch, yaw, roll) = entity.angles;
+                // Positive pitch looks down like of `get_start_direction`
+                let transform = Matrix4::from_translation(vec3(x, y, z))
+                    * Matrix4::from_angle_z(Deg(yaw))
+                    * Matrix4::from_angle_y(Deg(pitch))
+                    * Matrix4::from_angle_x(Deg(roll));
+                placements.push((index, transform));
+            }
+        }
+        if !entities.is_empty() {
+            info!(
+                "{} of {} studio model entities are drawn with {} models",
+                placements.len(),
+                entities.len(),
+                models.len()
+            );
+        }
+
+        let program = program!(facade,
+            140 => {
+                vertex: include_str!("../../shaders/model/vert.glsl"),
+                fragment: include_str!("../../shaders/model/frag.glsl"),
+            },
+        )
+        .unwrap();
+        Self {
+            models,
+            placements,
+            program,
+        }
+    }
+
+    /// `map_to_clip` transforms coordinates of the map, see `Map::to_view_transform`. `gamma`
+    /// and `fog` are applied like to faces of the map.
+    pub fn render<S: Surface>(
+        &self,
+        surface: &mut S,
+        map_to_clip: Matrix4<f32>,
+        gamma: f32,
+        fog: Option<Fog>,
+        draw_params: &DrawParameters,
+    ) {
+        // Triangles of models are wound either way, and they're small enough to draw them all
+        let draw_params = DrawParameters {
+            backface_culling: BackfaceCullingMode::CullingDisabled,
+            ..draw_params.clone()
+        };
+        let (fog_mode, fog_params) = fog_uniforms(fog);
+        for &(index, transform) in &self.placements {
+            let model = &self.models[index];
+            let mvp: [[f32; 4]; 4] = (map_to_clip * transform).into();
+            let transform: [[f32; 4]; 4] = transform.into();
+            for (range, texture) in &model.meshes {
+                let (colormap, masked) = &model.textures[*texture];
+                let uniforms = uniform! {
+                    mvp: mvp,
+                    model: transform,
+                    colormap: colormap,
+                    alpha_test: *masked,
+                    gamma: gamma,
+                    fog_mode: fog_mode,
+                    fog_color: fog.map_or([0.0; 3], |fog| fog.color),
+                    fog_params: fog_params,
+                };
+                surface
+                    .draw(
+                        model.vbo.slice(range.clone()).unwrap(),
+                        NoIndices(PrimitiveType::TrianglesList),
+                        &self.program,
+                        &uniforms,
+                        &draw_params,
+                    )
+                    .unwrap();
+            }
+        }
+    }
+}
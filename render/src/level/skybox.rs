@@ -111,6 +111,9 @@ const CUBEMAP_SIDES: [CubeLayer; 6] = [
     CubeLayer::NegativeZ,
 ];
 
+/// Color of all faces when there's no cubemap.
+const FALLBACK_COLOR: [u8; 4] = [112, 128, 150, 255];
+
 pub struct Skybox {
     vbo: VertexBufferAny,
     ibo: IndexBufferAny,
@@ -119,7 +122,8 @@ pub struct Skybox {
 }
 
 impl Skybox {
-    pub fn new<F: ?Sized + Facade>(facade: &F, cubemap_file: &CubemapFile) -> Self {
+    /// Without `cubemap_file` the sky is filled with a flat color.
+    pub fn new<F: ?Sized + Facade>(facade: &F, cubemap_file: Option<&CubemapFile>) -> Self {
         let vbo = VertexBuffer::new(facade, &CUBE_VERTICES).unwrap();
         let ibo = IndexBuffer::new(facade, PrimitiveType::TrianglesList, &CUBE_INDICES).unwrap();
 
@@ -134,8 +138,14 @@ impl Skybox {
         });
         debug!("Skybox shader was loaded in {}", elapsed);
 
-        let dimension = cubemap_file.dimension();
-        let sides = cubemap_file.sides();
+        let fallback_sides;
+        let (dimension, sides) = match cubemap_file {
+            Some(cubemap_file) => (cubemap_file.dimension(), cubemap_file.sides()),
+            None => {
+                fallback_sides = vec![FALLBACK_COLOR.to_vec(); CUBEMAP_SIDES.len()];
+                (1, &fallback_sides[..])
+            }
+        };
 
         let cubemap = Cubemap::empty(facade, dimension).unwrap();
         let blit_rect = BlitTarget {
@@ -175,6 +185,7 @@ impl Skybox {
         projection: Matrix4<f32>,
         view: Matrix4<f32>,
         draw_params: &DrawParameters,
+        reverse_z: bool,
     ) {
         let view = Matrix3::from_cols(view.x.truncate(), view.y.truncate(), view.z.truncate());
         let view = Matrix4::from(view);
@@ -183,6 +194,7 @@ impl Skybox {
 
         let uniforms = uniform! {
             mvp: mvp,
+            far_depth: if reverse_z { -1.0f32 } else { 1.0f32 },
             cubetex: self.cubemap.sampled().magnify_filter(MagnifySamplerFilter::Linear),
         };
         surface
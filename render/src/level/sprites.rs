@@ -0,0 +1,236 @@
+use super::{
+    entities::{get_sprite_entities, Fog, RenderMode, SpriteEntity},
+    map::fog_uniforms,
+};
+use cgmath::{vec3, InnerSpace, Matrix4, Point3, Transform, Vector3};
+use file::{
+    bsp::RawMap,
+    container::read as read_file,
+    spr::{Orientation, Sprite, TextureFormat},
+};
+use glium::{
+    backend::Facade,
+    implement_vertex,
+    index::{NoIndices, PrimitiveType},
+    program,
+    texture::{RawImage2d, Texture2d},
+    uniform, Blend, BlendingFunction, Depth, DrawParameters, LinearBlendingFactor, Program,
+    Surface, VertexBuffer,
+};
+use log::{info, warn};
+use std::{collections::HashMap, path::Path};
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    corner: [f32; 2],
+}
+
+implement_vertex!(Vertex, corner);
+
+/// Corners of a frame from its top left one, as a triangle strip wound clockwise like faces
+/// of the map, so it isn't culled.
+const QUAD: [Vertex; 4] = [
+    Vertex { corner: [0.0, 0.0] },
+    Vertex { corner: [1.0, 0.0] },
+    Vertex { corner: [0.0, 1.0] },
+    Vertex { corner: [1.0, 1.0] },
+];
+
+struct LoadedFrame {
+    texture: Texture2d,
+    origin: [f32; 2],
+    size: [f32; 2],
+}
+
+struct LoadedSprite {
+    frames: Vec<LoadedFrame>,
+    additive: bool,
+    /// Turned to the viewer around the map's vertical axis only.
+    upright: bool,
+}
+
+impl LoadedSprite {
+    fn new<F: ?Sized + Facade>(facade: &F, sprite: &Sprite) -> Self {
+        let frames = sprite
+            .frames
+            .iter()
+            .map(|frame| {
+                let raw =
+                    RawImage2d::from_raw_rgba_reversed(&frame.pixels, (frame.width, frame.height));
+                LoadedFrame {
+                    texture: Texture2d::new(facade, raw).unwrap(),
+                    origin: [frame.origin[0] as f32, frame.origin[1] as f32],
+                    size: [frame.width as f32, frame.height as f32],
+                }
+            })
+            .collect();
+        Self {
+            frames,
+            additive: sprite.format == TextureFormat::Additive,
+            upright: matches!(
+                sprite.orientation,
+                Orientation::ParallelUpright | Orientation::FacingUpright
+            ),
+        }
+    }
+}
+
+fn read_sprite(game_dir: &Path, path: &str) -> Result<Sprite, String> {
+    let file = read_file(game_dir.join(path)).map_err(|e| e.to_string())?;
+    Sprite::parse(&file).map_err(|_| "malformed sprite".to_string())
+}
+
+/// Sprites of entities drawn as billboards turned to the viewer, oriented ones are turned like
+/// parallel ones. Glowing and additive ones are added to what's behind them.
+pub struct Sprites {
+    sprites: Vec<LoadedSprite>,
+    placements: Vec<(usize, SpriteEntity)>,
+    quad: VertexBuffer<Vertex>,
+    program: Program,
+}
+
+impl Sprites {
+    /// Paths of `model` keys are relative to `game_dir`, sprites which can't be read or parsed
+    /// are skipped. There are no sprites without `game_dir`.
+    pub fn new<F: ?Sized + Facade>(facade: &F, map: &RawMap, game_dir: Option<&Path>) -> Self {
+        let entities = game_dir.map_or_else(Vec::new, |_| get_sprite_entities(map));
+        let entities_num = entities.len();
+        let mut sprites = vec![];
+        let mut indices: HashMap<String, Option<usize>> = HashMap::new();
+        let mut placements = vec![];
+        for entity in entities {
+            let index = *indices
+                .entry(entity.model.to_ascii_lowercase())
+                .or_insert_with(|| match read_sprite(game_dir.unwrap(), &entity.model) {
+                    Ok(sprite) => {
+                        sprites.push(LoadedSprite::new(facade, &sprite));
+                        Some(sprites.len() - 1)
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to load sprite `{}`, skipping it: {}",
+                            entity.model, e
+                        );
+                        None
+                    }
+                });
+            if let Some(index) = index {
+                placements.push((index, entity));
+            }
+        }
+        if entities_num > 0 {
+            info!(
+                "{} of {} sprite entities are drawn with {} sprites",
+                placements.len(),
+                entities_num,
+                sprites.len()
+            );
+        }
+
+        let program = program!(facade,
+            140 => {
+                vertex: include_str!("../../shaders/sprite/vert.glsl"),
+                fragment: include_str!("../../shaders/sprite/frag.glsl"),
+            },
+        )
+        .unwrap();
+        Self {
+            sprites,
+            placements,
+            quad: VertexBuffer::new(facade, &QUAD).unwrap(),
+            program,
+        }
+    }
+
+    /// `map_to_view` transforms coordinates of the map to the view space, see
+    /// `Map::to_view_transform`. `time` is in seconds and selects frames. Sprites are hidden
+    /// behind walls, but don't hide anything themselves.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render<S: Surface>(
+        &self,
+        surface: &mut S,
+        projection: Matrix4<f32>,
+        map_to_view: Matrix4<f32>,
+        time: f32,
+        fog: Option<Fog>,
+        draw_params: &DrawParameters,
+    ) {
+        let (fog_mode, fog_params) = fog_uniforms(fog);
+        let map_up = map_to_view
+            .transform_vector(vec3(0.0, 0.0, 1.0))
+            .normalize();
+        let projection: [[f32; 4]; 4] = projection.into();
+        for (index, entity) in &self.placements {
+            let sprite = &self.sprites[*index];
+            let frame = &sprite.frames
+                [(time * entity.framerate).max(0.0) as usize % sprite.frames.len().max(1)];
+            let (x, y, z) = entity.origin;
+            let center = map_to_view.transform_point(Point3::new(x, y, z));
+            let (right, up) = if sprite.upright {
+                // Right is across the view direction, which is -Z
+                let right = map_up.cross(Vector3::unit_z());
+                if right.magnitude2() > f32::EPSILON {
+                    (right.normalize(), map_up)
+                } else {
+                    (Vector3::unit_x(), Vector3::unit_y())
+                }
+            } else {
+                (Vector3::unit_x(), Vector3::unit_y())
+            };
+            let additive = sprite.additive
+                || matches!(entity.render_mode, RenderMode::Glow | RenderMode::Additive);
+            let blend = if additive {
+                Blend {
+                    color: BlendingFunction::Addition {
+                        source: LinearBlendingFactor::SourceAlpha,
+                        destination: LinearBlendingFactor::One,
+                    },
+                    alpha: BlendingFunction::Addition {
+                        source: LinearBlendingFactor::Zero,
+                        destination: LinearBlendingFactor::One,
+                    },
+                    constant_value: (0.0, 0.0, 0.0, 0.0),
+                }
+            } else {
+                Blend::alpha_blending()
+            };
+            let params = DrawParameters {
+                blend,
+                depth: Depth {
+                    write: false,
+                    ..draw_params.depth
+                },
+                ..draw_params.clone()
+            };
+            let scale = entity.scale;
+            let (center, right, up): ([f32; 3], [f32; 3], [f32; 3]) =
+                (center.into(), right.into(), up.into());
+            let uniforms = uniform! {
+                projection: projection,
+                center: center,
+                right: right,
+                up: up,
+                frame_origin: [frame.origin[0] * scale, frame.origin[1] * scale],
+                frame_size: [frame.size[0] * scale, frame.size[1] * scale],
+                colormap: &frame.texture,
+                color: [
+                    entity.render_color[0],
+                    entity.render_color[1],
+                    entity.render_color[2],
+                    entity.render_amount,
+                ],
+                fog_mode: fog_mode,
+                fog_params: fog_params,
+            };
+            surface
+                .draw(
+                    &self.quad,
+                    NoIndices(PrimitiveType::TriangleStrip),
+                    &self.program,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use glium::{
+    backend::Facade,
+    implement_vertex,
+    index::{NoIndices, PrimitiveType},
+    program, uniform, Depth, DepthTest, DrawParameters, Program, Surface, VertexBuffer,
+};
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+implement_vertex!(Vertex, position);
+
+/// A triangle covering the whole screen.
+const SCREEN: [Vertex; 3] = [
+    Vertex {
+        position: [-1.0, -1.0],
+    },
+    Vertex {
+        position: [3.0, -1.0],
+    },
+    Vertex {
+        position: [-1.0, 3.0],
+    },
+];
+/// Pixels of a square of the checkerboard.
+const CELL_SIZE: f32 = 32.0;
+/// Added to the background color in every other square.
+const CELL_BRIGHTNESS: f32 = 0.08;
+
+/// Faint checkerboard fixed to the screen behind the map, so holes into the void stand out
+/// from dark faces of the map.
+pub struct Checkerboard {
+    screen: VertexBuffer<Vertex>,
+    program: Program,
+}
+
+impl Checkerboard {
+    pub fn new<F: ?Sized + Facade>(facade: &F) -> Self {
+        let program = program!(facade,
+            140 => {
+                vertex: include_str!("../../shaders/void/vert.glsl"),
+                fragment: include_str!("../../shaders/void/frag.glsl"),
+            },
+        )
+        .unwrap();
+        Self {
+            screen: VertexBuffer::new(facade, &SCREEN).unwrap(),
+            program,
+        }
+    }
+
+    /// Squares alternate between `color` and a bit brighter one. Nothing is written to the
+    /// depth buffer, so it's drawn first and the map covers it.
+    pub fn render<S: Surface>(&self, surface: &mut S, color: [f32; 3]) {
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::Overwrite,
+                write: false,
+                ..Depth::default()
+            },
+            ..DrawParameters::default()
+        };
+        let uniforms = uniform! {
+            cell_size: CELL_SIZE,
+            color: color,
+            bright: color.map(|c| (c + CELL_BRIGHTNESS).min(1.0)),
+        };
+        surface
+            .draw(
+                &self.screen,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
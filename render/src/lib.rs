@@ -1,3 +1,15 @@
 mod level;
+mod overlay;
+mod underwater;
 
-pub use level::Level; // to reduce repetition of names
+pub use file::{
+    bsp::{contents::Contents, trace::TraceResult, BspError},
+    cubemap::{Cubemap, FaceTransform},
+};
+
+pub use level::{
+    DensityRamp, Fog, FogFalloff, Frustum, Level, LevelOptions, LoadError, LoadStage, MapReport,
+    RenderSettings, TextureFilter, GAMMA_RANGE, MAX_ANISOTROPY,
+}; // to reduce repetition of names
+pub use overlay::TextOverlay;
+pub use underwater::Underwater;
@@ -0,0 +1,174 @@
+use glium::{
+    backend::Facade, implement_vertex, index::NoIndices, index::PrimitiveType, program, uniform,
+    Blend, DrawParameters, Program, Surface, VertexBuffer,
+};
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+implement_vertex!(Vertex, position);
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+/// Screen pixels per glyph pixel.
+const SCALE: f32 = 2.0;
+const MARGIN: f32 = 8.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SHADOW_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+/// Pixels from the center of the screen to the ends of the crosshair's lines.
+const CROSSHAIR_RADIUS: f32 = 8.0;
+const CROSSHAIR_THICKNESS: f32 = 2.0;
+
+/// Rows of a 5x7 glyph from the top, the highest of 5 bits is the leftmost pixel. Characters
+/// without one are drawn blank.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
+
+/// Two triangles covering the rectangle from (x0, y0) to (x1, y1).
+fn quad(x0: f32, y0: f32, x1: f32, y1: f32) -> [Vertex; 6] {
+    [(x0, y0), (x1, y0), (x1, y1), (x0, y0), (x1, y1), (x0, y1)]
+        .map(|(x, y)| Vertex { position: [x, y] })
+}
+
+/// Lines of text in the top left corner of the screen, drawn with a built-in bitmap font, and
+/// a crosshair in the center of it.
+pub struct TextOverlay {
+    program: Program,
+}
+
+impl TextOverlay {
+    pub fn new<F: ?Sized + Facade>(facade: &F) -> Self {
+        let program = program!(facade,
+            140 => {
+                vertex: include_str!("../shaders/overlay/vert.glsl"),
+                fragment: include_str!("../shaders/overlay/frag.glsl"),
+            },
+        )
+        .unwrap();
+        Self { program }
+    }
+
+    /// Two triangles for each lit pixel of the glyphs, in pixels from the top left corner.
+    fn vertices<S: AsRef<str>>(lines: &[S], offset: f32) -> Vec<Vertex> {
+        let mut vertices = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            let top = MARGIN + offset + row as f32 * (GLYPH_HEIGHT + 2) as f32 * SCALE;
+            for (column, c) in line.as_ref().chars().enumerate() {
+                let left = MARGIN + offset + column as f32 * (GLYPH_WIDTH + 1) as f32 * SCALE;
+                for (y, bits) in glyph(c).iter().enumerate() {
+                    for x in (0..GLYPH_WIDTH).filter(|x| bits & (1 << (GLYPH_WIDTH - 1 - x)) != 0) {
+                        let (x0, y0) = (left + x as f32 * SCALE, top + y as f32 * SCALE);
+                        vertices.extend_from_slice(&quad(x0, y0, x0 + SCALE, y0 + SCALE));
+                    }
+                }
+            }
+        }
+        vertices
+    }
+
+    /// A plus of two bars crossing at the center of a `width` x `height` screen.
+    fn crosshair_vertices(width: f32, height: f32, offset: f32) -> Vec<Vertex> {
+        let (x, y) = (
+            (width / 2.0).round() + offset,
+            (height / 2.0).round() + offset,
+        );
+        let (half, radius) = (CROSSHAIR_THICKNESS / 2.0, CROSSHAIR_RADIUS);
+        // The vertical bar is split, so the translucent shadow isn't darker where the bars cross
+        [
+            quad(x - radius, y - half, x + radius, y + half),
+            quad(x - half, y - radius, x + half, y - half),
+            quad(x - half, y + half, x + half, y + radius),
+        ]
+        .concat()
+    }
+
+    /// Draws the lines over everything with a shadow, so they're readable on any background.
+    pub fn render<F: ?Sized + Facade, S: Surface, L: AsRef<str>>(
+        &self,
+        facade: &F,
+        surface: &mut S,
+        lines: &[L],
+    ) {
+        self.draw_shadowed(facade, surface, |offset| Self::vertices(lines, offset));
+    }
+
+    /// Draws the crosshair over everything with a shadow, like the text.
+    pub fn render_crosshair<F: ?Sized + Facade, S: Surface>(&self, facade: &F, surface: &mut S) {
+        let (width, height) = surface.get_dimensions();
+        self.draw_shadowed(facade, surface, |offset| {
+            Self::crosshair_vertices(width as f32, height as f32, offset)
+        });
+    }
+
+    /// Draws the shadow of `vertices` shifted by the offset they're given, then them.
+    fn draw_shadowed<F: ?Sized + Facade, S: Surface, V: Fn(f32) -> Vec<Vertex>>(
+        &self,
+        facade: &F,
+        surface: &mut S,
+        vertices: V,
+    ) {
+        let (width, height) = surface.get_dimensions();
+        let draw_params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..DrawParameters::default()
+        };
+        for (offset, color) in [(SCALE / 2.0, SHADOW_COLOR), (0.0, TEXT_COLOR)].iter() {
+            let vertices = vertices(*offset);
+            if vertices.is_empty() {
+                return;
+            }
+            let vbo = VertexBuffer::new(facade, &vertices).unwrap();
+            let uniforms = uniform! {
+                screen_size: [width as f32, height as f32],
+                color: *color,
+            };
+            surface
+                .draw(
+                    &vbo,
+                    NoIndices(PrimitiveType::TrianglesList),
+                    &self.program,
+                    &uniforms,
+                    &draw_params,
+                )
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use glium::{
+    backend::Facade,
+    framebuffer::{DepthRenderBuffer, SimpleFrameBuffer},
+    implement_vertex,
+    index::{NoIndices, PrimitiveType},
+    program,
+    texture::{
+        DepthFormat, DepthTexture2dMultisample, MipmapsOption, Texture2d, Texture2dMultisample,
+    },
+    uniform,
+    uniforms::{MagnifySamplerFilter, SamplerWrapFunction},
+    BlitTarget, DrawParameters, Program, Rect, Surface, VertexBuffer,
+};
+use log::warn;
+use std::cell::{Cell, RefCell};
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    position: [f32; 2],
+}
+
+implement_vertex!(Vertex, position);
+
+/// A triangle covering the whole screen.
+const SCREEN: [Vertex; 3] = [
+    Vertex {
+        position: [-1.0, -1.0],
+    },
+    Vertex {
+        position: [3.0, -1.0],
+    },
+    Vertex {
+        position: [-1.0, 3.0],
+    },
+];
+/// Part of the screen the image is shifted by at most.
+const WARP_AMOUNT: f32 = 0.004;
+/// How much of the final color is the tint.
+const TINT_AMOUNT: f32 = 0.35;
+
+/// Depth format of about the given precision of the window's depth buffer.
+fn depth_format(bits: u8) -> DepthFormat {
+    match bits {
+        0..=16 => DepthFormat::I16,
+        17..=24 => DepthFormat::I24,
+        _ => DepthFormat::I32,
+    }
+}
+
+/// Offscreen target of the scene.
+enum Buffers {
+    Single(Texture2d, DepthRenderBuffer),
+    /// Multisampled color and depth, resolved into the texture the effect samples.
+    Multisampled(Texture2dMultisample, DepthTexture2dMultisample, Texture2d),
+}
+
+impl Buffers {
+    fn new<F: ?Sized + Facade>(
+        facade: &F,
+        (width, height): (u32, u32),
+        samples: u16,
+        depth: DepthFormat,
+    ) -> Result<Self, String> {
+        let color = Texture2d::empty(facade, width, height).map_err(|e| e.to_string())?;
+        if samples == 0 {
+            let depth =
+                DepthRenderBuffer::new(facade, depth, width, height).map_err(|e| e.to_string())?;
+            return Ok(Self::Single(color, depth));
+        }
+        let samples = samples.into();
+        let multisampled = Texture2dMultisample::empty(facade, width, height, samples)
+            .map_err(|e| e.to_string())?;
+        let depth = DepthTexture2dMultisample::empty_with_format(
+            facade,
+            depth,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+            samples,
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(Self::Multisampled(multisampled, depth, color))
+    }
+
+    /// Texture with the drawn scene.
+    fn color(&self) -> &Texture2d {
+        match self {
+            Self::Single(color, _) | Self::Multisampled(_, _, color) => color,
+        }
+    }
+}
+
+/// Post-process pass tinting and gently warping the whole image, like of the camera in water.
+/// The scene is drawn into an offscreen texture of the target's size with the window's
+/// multisampling and depth precision, which is then drawn over the target.
+pub struct Underwater {
+    screen: VertexBuffer<Vertex>,
+    program: Program,
+    /// Samples per pixel of the window, no multisampling if 0.
+    samples: u16,
+    depth: DepthFormat,
+    /// Offscreen target, recreated once the size changes. `None` if it couldn't be made.
+    buffers: RefCell<Option<Buffers>>,
+    /// Size `buffers` were made for.
+    buffers_size: Cell<(u32, u32)>,
+}
+
+impl Underwater {
+    /// `samples` and `depth_bits` are of the window, so the scene looks the same as when it's
+    /// drawn directly.
+    pub fn new<F: ?Sized + Facade>(facade: &F, samples: u16, depth_bits: u8) -> Self {
+        let program = program!(facade,
+            140 => {
+                vertex: include_str!("../shaders/underwater/vert.glsl"),
+                fragment: include_str!("../shaders/underwater/frag.glsl"),
+            },
+        )
+        .unwrap();
+        Self {
+            screen: VertexBuffer::new(facade, &SCREEN).unwrap(),
+            program,
+            samples,
+            depth: depth_format(depth_bits),
+            buffers: RefCell::new(None),
+            buffers_size: Cell::new((0, 0)),
+        }
+    }
+
+    /// Calls `draw` with the offscreen target, which it has to clear, then draws its result
+    /// with the effect over `surface`. `time` is in seconds and moves the warp. Returns `false`
+    /// without calling `draw` if there's no offscreen target, e.g. for a minimized window, so
+    /// the scene should be drawn directly.
+    pub fn render<F: ?Sized + Facade, S: Surface, D: FnOnce(&mut SimpleFrameBuffer)>(
+        &self,
+        facade: &F,
+        surface: &mut S,
+        tint: [f32; 3],
+        time: f32,
+        draw: D,
+    ) -> bool {
+        let size = surface.get_dimensions();
+        if size.0 == 0 || size.1 == 0 {
+            return false;
+        }
+        let mut buffers = self.buffers.borrow_mut();
+        if self.buffers_size.replace(size) != size {
+            *buffers = Buffers::new(facade, size, self.samples, self.depth)
+                .map_err(|e| warn!("Couldn't make the underwater target: {}", e))
+                .ok();
+        }
+        let buffers = match buffers.as_ref() {
+            Some(buffers) => buffers,
+            None => return false,
+        };
+        let framebuffer = match buffers {
+            Buffers::Single(color, depth) => {
+                SimpleFrameBuffer::with_depth_buffer(facade, color, depth)
+            }
+            Buffers::Multisampled(color, depth, _) => {
+                SimpleFrameBuffer::with_depth_buffer(facade, color, depth)
+            }
+        };
+        let mut framebuffer = match framebuffer {
+            Ok(framebuffer) => framebuffer,
+            Err(e) => {
+                warn!("Couldn't draw to the underwater target: {:?}", e);
+                return false;
+            }
+        };
+        draw(&mut framebuffer);
+        let color = buffers.color();
+        if let Buffers::Multisampled(..) = buffers {
+            let (width, height) = size;
+            // Same sized blits resolve the samples
+            if let Ok(resolved) = SimpleFrameBuffer::new(facade, color) {
+                framebuffer.blit_color(
+                    &Rect {
+                        left: 0,
+                        bottom: 0,
+                        width,
+                        height,
+                    },
+                    &resolved,
+                    &BlitTarget {
+                        left: 0,
+                        bottom: 0,
+                        width: width as i32,
+                        height: height as i32,
+                    },
+                    MagnifySamplerFilter::Nearest,
+                );
+            }
+        }
+
+        let uniforms = uniform! {
+            scene: color.sampled().wrap_function(SamplerWrapFunction::Clamp),
+            time: time,
+            warp_amount: WARP_AMOUNT,
+            tint: tint,
+            tint_amount: TINT_AMOUNT,
+        };
+        surface
+            .draw(
+                &self.screen,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &DrawParameters::default(),
+            )
+            .unwrap();
+        true
+    }
+}
@@ -0,0 +1,22 @@
+use render::{Fog, FogFalloff};
+
+#[test]
+fn fog_visibility_falls_with_distance() {
+    let exponential = Fog {
+        color: [0.5; 3],
+        falloff: FogFalloff::Exponential { density: 0.01 },
+    };
+    assert_eq!(exponential.visibility(0.0), 1.0);
+    assert!((exponential.visibility(100.0) - (-1.0f32).exp()).abs() < 1e-6);
+
+    let linear = Fog {
+        color: [0.5; 3],
+        falloff: FogFalloff::Linear {
+            start: 100.0,
+            end: 300.0,
+        },
+    };
+    assert_eq!(linear.visibility(50.0), 1.0);
+    assert_eq!(linear.visibility(200.0), 0.5);
+    assert_eq!(linear.visibility(1000.0), 0.0);
+}
@@ -0,0 +1,28 @@
+use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
+use render::Frustum;
+
+fn frustum() -> Frustum {
+    let projection = perspective(Deg(90.0), 1.0, 1.0, 100.0);
+    let view = Matrix4::look_at_rh(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(0.0, 0.0, -1.0),
+        Vector3::unit_y(),
+    );
+    Frustum::from_matrix(projection * view)
+}
+
+#[test]
+fn boxes_in_front_are_inside() {
+    let frustum = frustum();
+    assert!(frustum.contains_aabb([-1.0, -1.0, -11.0], [1.0, 1.0, -9.0]));
+    // Partially crossing the left plane
+    assert!(frustum.contains_aabb([-20.0, -1.0, -11.0], [-5.0, 1.0, -9.0]));
+}
+
+#[test]
+fn boxes_behind_or_aside_are_outside() {
+    let frustum = frustum();
+    assert!(!frustum.contains_aabb([-1.0, -1.0, 9.0], [1.0, 1.0, 11.0]));
+    assert!(!frustum.contains_aabb([20.0, -1.0, -11.0], [30.0, 1.0, -9.0]));
+    assert!(!frustum.contains_aabb([-1.0, -1.0, -300.0], [1.0, 1.0, -200.0]));
+}
@@ -0,0 +1,132 @@
+use crate::{draw_parameters, support::Camera, MapPaths, CAMERA_OFFSET};
+use cgmath::{vec3, Point3};
+use elapsed::measure_time;
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    glutin,
+    texture::{DepthTexture2d, Texture2d},
+    HeadlessRenderer, Surface,
+};
+use render::{Level, LevelOptions, RenderSettings};
+use std::{f32::consts::TAU, path::PathBuf, time::Duration};
+
+/// Size of the offscreen framebuffer, the same as of the window.
+const FRAME_SIZE: (u32, u32) = (1024, 768);
+/// Frames rendered before measuring, so shader compilation and uploads don't count.
+const WARMUP_FRAMES: usize = 10;
+/// Radius of the circle the camera flies around the spawn point.
+const ORBIT_RADIUS: f32 = 128.0;
+/// Animations advance as if frames were shown at this rate.
+const ANIMATION_FPS: f32 = 60.0;
+
+/// Statistics of frame times.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameStats {
+    pub frames: usize,
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl FrameStats {
+    /// `None` if there are no times.
+    pub fn new(mut times: Vec<Duration>) -> Option<Self> {
+        times.sort_unstable();
+        let percentile = |p: usize| times[((times.len() - 1) * p + 50) / 100];
+        Some(Self {
+            frames: times.len(),
+            min: *times.first()?,
+            avg: times.iter().sum::<Duration>() / times.len() as u32,
+            max: *times.last()?,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+        })
+    }
+}
+
+/// Renders `frames` frames offscreen while flying a circle around the spawn point, looking
+/// along the way, and measures each of them until the GPU is done with it.
+pub fn run(
+    paths: &MapPaths,
+    skybox_path: Option<&PathBuf>,
+    options: &LevelOptions,
+    settings: &RenderSettings,
+    mut camera: Camera,
+    frames: usize,
+    depth_bits: u8,
+) -> Result<FrameStats, String> {
+    let event_loop = glutin::event_loop::EventLoop::new();
+    let context = glutin::ContextBuilder::new()
+        .with_depth_buffer(depth_bits)
+        .build_headless(&event_loop, FRAME_SIZE.into())
+        .map_err(|e| format!("failed to create headless context: {}", e))?;
+    let display = HeadlessRenderer::new(context)
+        .map_err(|e| format!("failed to create headless context: {}", e))?;
+
+    let wad_paths = paths.wad_paths.iter().collect::<Vec<_>>();
+    let level = Level::new(&display, &paths.bsp_path, &wad_paths, skybox_path, options)
+        .map_err(|e| e.to_string())?;
+    let center = level
+        .start_point(settings)
+        .map_or_else(|| Point3::new(0.0, 0.0, 0.0), Point3::from);
+    let center = center + vec3(0.0, CAMERA_OFFSET, 0.0);
+    camera.aspect_ratio = FRAME_SIZE.0 as f32 / FRAME_SIZE.1 as f32;
+
+    let color = Texture2d::empty(&display, FRAME_SIZE.0, FRAME_SIZE.1)
+        .map_err(|e| format!("failed to create framebuffer: {}", e))?;
+    let depth = DepthTexture2d::empty(&display, FRAME_SIZE.0, FRAME_SIZE.1)
+        .map_err(|e| format!("failed to create framebuffer: {}", e))?;
+    let mut target = SimpleFrameBuffer::with_depth_buffer(&display, &color, &depth)
+        .map_err(|e| format!("failed to create framebuffer: {}", e))?;
+
+    let draw_params = draw_parameters(settings);
+    let far_depth = if settings.reverse_z { 0.0 } else { 1.0 };
+    let clear_color = level.clear_color(settings);
+    let times = (0..WARMUP_FRAMES + frames)
+        .map(|i| {
+            let angle = TAU * i.saturating_sub(WARMUP_FRAMES) as f32 / frames as f32;
+            let (sin, cos) = angle.sin_cos();
+            camera.position = center + vec3(cos, 0.0, sin) * ORBIT_RADIUS;
+            camera.look_along(vec3(-sin, 0.0, cos));
+
+            let (elapsed, _) = measure_time(|| {
+                target.clear_color_and_depth(clear_color, far_depth);
+                level.render(
+                    &mut target,
+                    camera.perspective(),
+                    camera.view(),
+                    camera.position.into(),
+                    i as f32 / ANIMATION_FPS,
+                    &draw_params,
+                    settings,
+                );
+                display.finish();
+            });
+            elapsed.duration()
+        })
+        .skip(WARMUP_FRAMES)
+        .collect();
+    FrameStats::new(times).ok_or_else(|| "no frames to measure".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_frame_times() {
+        let times = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = FrameStats::new(times).unwrap();
+        assert_eq!(stats.frames, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.avg, Duration::from_micros(50_500));
+        assert_eq!(stats.p50, Duration::from_millis(51));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert!(FrameStats::new(vec![]).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::support::CameraState;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Camera states saved to numbered slots and the clip planes last chosen with the keys, kept
+/// in a JSON file next to the map.
+pub struct Bookmarks {
+    path: PathBuf,
+    sidecar: Sidecar,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    #[serde(default)]
+    slots: BTreeMap<u8, CameraState>,
+    /// Near and far planes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clip_planes: Option<[f32; 2]>,
+}
+
+impl Sidecar {
+    /// Older files have only the slots.
+    fn parse(json: &str) -> serde_json::Result<Self> {
+        match serde_json::from_str(json) {
+            Ok(slots) => Ok(Self {
+                slots,
+                clip_planes: None,
+            }),
+            Err(_) => serde_json::from_str(json),
+        }
+    }
+}
+
+/// File of bookmarks for the bsp path, `foo.bookmarks.json` next to `foo.bsp`. Bookmarks of
+/// maps inside of zip or pak archives are kept next to the archive.
+pub fn bookmarks_path(bsp_path: &Path) -> PathBuf {
+    let file_name = |map: &Path| {
+        let stem = map.file_stem().unwrap_or_default().to_string_lossy();
+        format!("{}.bookmarks.json", stem)
+    };
+    match file::container::split_zip_path(bsp_path)
+        .or_else(|| file::container::split_pak_path(bsp_path))
+    {
+        Some((archive, map)) => archive.with_file_name(file_name(Path::new(&map))),
+        None => bsp_path.with_file_name(file_name(bsp_path)),
+    }
+}
+
+impl Bookmarks {
+    /// Reads bookmarks from the file, there are none if it's missing or broken.
+    pub fn load(path: PathBuf) -> Self {
+        let sidecar = match fs::read_to_string(&path) {
+            Ok(json) => Sidecar::parse(&json).unwrap_or_else(|e| {
+                warn!("Bookmarks at {} are broken: {}", path.display(), e);
+                Sidecar::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Sidecar::default(),
+            Err(e) => {
+                warn!("Couldn't read bookmarks at {}: {}", path.display(), e);
+                Sidecar::default()
+            }
+        };
+        Self { path, sidecar }
+    }
+
+    pub fn get(&self, slot: u8) -> Option<CameraState> {
+        self.sidecar.slots.get(&slot).copied()
+    }
+
+    /// Puts the state into the slot and writes all of them to the file.
+    pub fn set(&mut self, slot: u8, state: CameraState) {
+        self.sidecar.slots.insert(slot, state);
+        match self.save() {
+            Ok(()) => info!("Bookmark {} saved to {}", slot, self.path.display()),
+            Err(e) => warn!("Couldn't save bookmarks to {}: {}", self.path.display(), e),
+        }
+    }
+
+    /// Near and far planes saved for the map.
+    pub fn clip_planes(&self) -> Option<[f32; 2]> {
+        self.sidecar.clip_planes
+    }
+
+    pub fn set_clip_planes(&mut self, clip_planes: [f32; 2]) {
+        self.sidecar.clip_planes = Some(clip_planes);
+        if let Err(e) = self.save() {
+            warn!(
+                "Couldn't save clip planes to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.sidecar)?;
+        fs::write(&self.path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_is_next_to_the_map() {
+        assert_eq!(
+            bookmarks_path(Path::new("maps/c1a0.bsp")),
+            Path::new("maps/c1a0.bookmarks.json")
+        );
+        assert_eq!(
+            bookmarks_path(Path::new("valve/pak0.pak:maps/c1a0.bsp")),
+            Path::new("valve/c1a0.bookmarks.json")
+        );
+    }
+
+    #[test]
+    fn read_slots_of_older_files() {
+        let state = r#"{"position": [1.0, 2.0, 3.0], "rotation": [0.0, 90.0, 0.0]}"#;
+        let old = Sidecar::parse(&format!(r#"{{"1": {}}}"#, state)).unwrap();
+        assert_eq!(old.slots[&1].position, [1.0, 2.0, 3.0]);
+        assert_eq!(old.clip_planes, None);
+
+        let new = format!(
+            r#"{{"slots": {{"2": {}}}, "clip_planes": [4.0, 512.0]}}"#,
+            state
+        );
+        let new = Sidecar::parse(&new).unwrap();
+        assert_eq!(new.slots[&2].rotation, [0.0, 90.0, 0.0]);
+        assert_eq!(new.clip_planes, Some([4.0, 512.0]));
+        assert!(Sidecar::parse(r#"{"slots": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use file::{
+    bsp::{lumps::parse_textures, LumpType, RawMap},
+    container,
+    wad::Archive,
+};
+use serde::{Serialize, Serializer};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize)]
+pub struct WadReport {
+    #[serde(serialize_with = "serialize_paths")]
+    pub found_wads: Vec<PathBuf>,
+    pub missing_wads: Vec<String>,
+    /// Found wads which can't be read or parsed, their textures are unresolved.
+    #[serde(serialize_with = "serialize_paths")]
+    pub broken_wads: Vec<PathBuf>,
+    pub unresolved_textures: Vec<String>,
+}
+
+impl WadReport {
+    pub fn is_complete(&self) -> bool {
+        self.missing_wads.is_empty()
+            && self.broken_wads.is_empty()
+            && self.unresolved_textures.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            ok: bool,
+            #[serde(flatten)]
+            report: &'a WadReport,
+        }
+        serde_json::to_string(&Json {
+            ok: self.is_complete(),
+            report: self,
+        })
+        .expect("reports have only strings and lists of them")
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for path in &self.found_wads {
+            out += &format!("found wad: {}\n", path.display());
+        }
+        for name in &self.missing_wads {
+            out += &format!("missing wad: {}\n", name);
+        }
+        for path in &self.broken_wads {
+            out += &format!("broken wad: {}\n", path.display());
+        }
+        for name in &self.unresolved_textures {
+            out += &format!("unresolved texture: {}\n", name);
+        }
+        out += if self.is_complete() { "OK" } else { "FAILED" };
+        out
+    }
+}
+
+/// Paths aren't always UTF-8, so they're written lossily like they're printed.
+fn serialize_paths<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|path| path.display().to_string()))
+}
+
+/// Returns basenames of WADs listed in worldspawn's `wad` key.
+/// Compile tools write it as `;`-separated paths, often with `\` separators.
+pub fn referenced_wads(map: &RawMap) -> Vec<String> {
+    map.first_entity("worldspawn")
+        .and_then(|e| e.get("wad"))
+        .map(|wads| {
+            wads.split(';')
+                .filter_map(|path| path.rsplit(['/', '\\']).next())
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn find_wad(name: &str, wad_paths: &[PathBuf], search_dirs: &[&Path]) -> Option<PathBuf> {
+    let matches = |path: &Path| {
+        path.file_name()
+            .and_then(|f| f.to_str())
+            .filter(|f| f.eq_ignore_ascii_case(name))
+            .is_some()
+    };
+    wad_paths.iter().find(|p| matches(p)).cloned().or_else(|| {
+        search_dirs
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|p| matches(p))
+    })
+}
+
+fn read_bsp(bsp_path: &Path) -> Result<Vec<u8>, String> {
+    container::read(bsp_path)
+        .map_err(|e| format!("failed to read bsp at {}: {}", bsp_path.display(), e))
+}
+
+fn parse_bsp<'a>(bsp_path: &Path, bsp_file: &'a [u8]) -> Result<RawMap<'a>, String> {
+    RawMap::parse(bsp_file)
+        .map_err(|e| format!("failed to parse bsp at {}: {}", bsp_path.display(), e))
+}
+
+/// Directory of the map, or of the archive it's in for maps inside zips or paks.
+pub fn map_dir(bsp_path: &Path) -> Option<PathBuf> {
+    match container::split_zip_path(bsp_path).or_else(|| container::split_pak_path(bsp_path)) {
+        Some((archive, _)) => archive.parent().map(Path::to_path_buf),
+        None => bsp_path.parent().map(Path::to_path_buf),
+    }
+}
+
+/// WADs listed in worldspawn's `wad` key: paths of the ones found by basename in
+/// `search_dirs`, in the order of the key, and names of the missing ones.
+pub fn locate_wads<P: AsRef<Path>>(
+    bsp_path: P,
+    search_dirs: &[&Path],
+) -> Result<(Vec<PathBuf>, Vec<String>), String> {
+    let bsp_path = bsp_path.as_ref();
+    let bsp_file = read_bsp(bsp_path)?;
+    let raw_map = parse_bsp(bsp_path, &bsp_file)?;
+    let (mut found, mut missing) = (Vec::new(), Vec::new());
+    for name in referenced_wads(&raw_map) {
+        match find_wad(&name, &[], search_dirs) {
+            Some(path) if !found.contains(&path) => found.push(path),
+            Some(_) => {}
+            None => missing.push(name),
+        }
+    }
+    Ok((found, missing))
+}
+
+/// Checks that the map's wads are found in `wad_paths`, `wad_dir` or next to the map, like
+/// the viewer looks for them, and that they have all of its textures.
+pub fn check_wads<P: AsRef<Path>>(
+    bsp_path: P,
+    wad_paths: &[PathBuf],
+    wad_dir: Option<&Path>,
+) -> Result<WadReport, String> {
+    let bsp_path = bsp_path.as_ref();
+    let bsp_file = read_bsp(bsp_path)?;
+    let raw_map = parse_bsp(bsp_path, &bsp_file)?;
+
+    let map_dir = map_dir(bsp_path);
+    let search_dirs: Vec<_> = wad_dir.into_iter().chain(map_dir.as_deref()).collect();
+    let mut found_wads = Vec::new();
+    let mut missing_wads = Vec::new();
+    for name in referenced_wads(&raw_map) {
+        match find_wad(&name, wad_paths, &search_dirs) {
+            Some(path) => found_wads.push(path),
+            None => missing_wads.push(name),
+        }
+    }
+    for path in wad_paths {
+        if !found_wads.contains(path) {
+            found_wads.push(path.clone());
+        }
+    }
+
+    let mut available = BTreeSet::new();
+    let mut broken_wads = Vec::new();
+    for path in &found_wads {
+        let file = container::read(path).ok();
+        match file.as_deref().map(Archive::parse) {
+            Some(Ok(archive)) => {
+                available.extend(archive.entries().map(|(name, _)| name.to_ascii_lowercase()))
+            }
+            _ => broken_wads.push(path.clone()),
+        }
+    }
+
+    let textures = parse_textures(raw_map.lump_data(LumpType::Textures))
+        .map_err(|_| format!("failed to parse textures of {}", bsp_path.display()))?;
+    let unresolved_textures = textures
+        .iter()
+        .filter(|t| t.is_empty())
+        .map(|t| t.name().to_ascii_lowercase())
+        .filter(|name| !available.contains(name))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    Ok(WadReport {
+        found_wads,
+        missing_wads,
+        broken_wads,
+        unresolved_textures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_dir_of_archived_maps() {
+        assert_eq!(
+            map_dir(Path::new("maps/foo.bsp")),
+            Some(PathBuf::from("maps"))
+        );
+        assert_eq!(
+            map_dir(Path::new("valve/pak0.pak:maps/foo.bsp")),
+            Some(PathBuf::from("valve"))
+        );
+        assert_eq!(
+            map_dir(Path::new("mods/pack.zip:maps/foo.bsp")),
+            Some(PathBuf::from("mods"))
+        );
+    }
+
+    #[test]
+    fn json_report() {
+        let report = WadReport {
+            found_wads: vec![PathBuf::from("valve/halflife.wad")],
+            missing_wads: vec!["my \"new\".wad".to_string()],
+            broken_wads: vec![],
+            unresolved_textures: vec![],
+        };
+        assert_eq!(
+            report.to_json(),
+            r#"{"ok":false,"found_wads":["valve/halflife.wad"],"missing_wads":["my \"new\".wad"],"broken_wads":[],"unresolved_textures":[]}"#
+        );
+    }
+
+    #[test]
+    fn broken_wads_fail_the_report() {
+        let report = WadReport {
+            found_wads: vec![PathBuf::from("maps/new.wad")],
+            missing_wads: vec![],
+            broken_wads: vec![PathBuf::from("maps/new.wad")],
+            unresolved_textures: vec![],
+        };
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.to_text(),
+            "found wad: maps/new.wad\nbroken wad: maps/new.wad\nFAILED"
+        );
+    }
+}
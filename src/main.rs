@@ -1,16 +1,55 @@
+mod benchmark;
+mod bookmarks;
+mod check;
 mod support;
+mod watch;
 
-use cgmath::Deg;
+use bookmarks::{bookmarks_path, Bookmarks};
+use cgmath::{vec3, Deg, InnerSpace, Point3, Vector3, Zero};
+use file::bsp::RawMap;
 use glium::{glutin, Surface};
-use render::Level;
-use std::path::{Path, PathBuf};
+use log::{debug, info, warn};
+use render::{
+    Contents, DensityRamp, FaceTransform, Fog, FogFalloff, Level, LevelOptions, RenderSettings,
+    TextOverlay, TextureFilter, Underwater, GAMMA_RANGE, MAX_ANISOTROPY,
+};
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 use structopt::StructOpt;
-use support::{init_logger, Camera};
+use support::{init_logger, save_screenshot, Camera, FpsCounter, PausableClock, SpeedFov};
+use watch::WadWatcher;
 
-const MOVE_SPEED: f32 = 100.0;
+const WINDOW_TITLE: &str = "hlbsp viewer";
+/// Units per second, initially.
+const MOVE_SPEED: f32 = 320.0;
+/// Slowest and fastest movement, e.g. for tight vents and huge outdoor maps.
+const MOVE_SPEED_RANGE: (f32, f32) = (20.0, 5120.0);
+/// `[` and `]` change the speed by multiplying or dividing it by this.
+const MOVE_SPEED_STEP: f32 = 1.25;
+/// For how long the speed or the texture filter is shown on the screen after it was changed.
+const CHANGE_SHOWN_FOR: Duration = Duration::from_secs(2);
 const CAMERA_OFFSET: f32 = 64.0;
-// Safe, because there's no multiple thread accessing this
-static mut MOUSE_GRABBED: bool = true;
+/// Units per second squared of falling in walk mode.
+const GRAVITY: f32 = 800.0;
+/// Height of the eyes above the center of the box of every clip hull: a point, the standing
+/// player, a large monster and the crouching player.
+const EYE_HEIGHTS: [f32; 4] = [0.0, 28.0, 0.0, 12.0];
+/// Normals steeper than this are walls, not floors.
+const FLOOR_NORMAL_Y: f32 = 0.7;
+const SLIDE_ITERATIONS: usize = 4;
+/// Units between a surface teleported to and the player's box, along its normal.
+const TELEPORT_OFFSET: f32 = 16.0;
+/// Longer frames are simulated as this long, so a stall doesn't throw the player through floors.
+const MAX_FRAME_TIME: f32 = 0.1;
+const CLIP_PLANE_STEP: f32 = 2.0;
+const GAMMA_STEP: f32 = 0.1;
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -18,15 +57,32 @@ static mut MOUSE_GRABBED: bool = true;
     about = "A program allows you to view hlbsp maps (bsp v30)"
 )]
 struct Opt {
-    #[structopt(short, long = "bsp", parse(from_os_str), help = "Path to bsp map")]
-    bsp_path: PathBuf,
+    #[structopt(
+        short,
+        long = "bsp",
+        parse(from_os_str),
+        required = true,
+        min_values = 1,
+        help = "Paths to bsp maps, may point inside a zip or pak as `pack.zip:maps/foo.bsp` \
+                or `pak0.pak:maps/foo.bsp`. PageUp and PageDown switch between them, F9 reads \
+                the shown one again. Only the first one is checked, exported or benchmarked"
+    )]
+    bsp_paths: Vec<PathBuf>,
     #[structopt(
         short,
         long = "wad",
         parse(from_os_str),
-        help = "Path to wad files which are required to load textures"
+        help = "Path to wad files with textures not embedded into the map, may point inside \
+                a zip or pak. Earlier ones take precedence for textures present in several wads"
     )]
     wad_path: Vec<PathBuf>,
+    #[structopt(
+        long = "wad-dir",
+        parse(from_os_str),
+        help = "Directory to look for wads listed in the map's `wad` key when there's no --wad, \
+                they're also looked for next to the map"
+    )]
+    wad_dir: Option<PathBuf>,
     #[structopt(
         short,
         long = "skybox",
@@ -34,16 +90,870 @@ struct Opt {
         help = "Path to directory stores skybox textures"
     )]
     skybox_path: Option<PathBuf>,
+    #[structopt(
+        long = "sky-transform",
+        help = "Transform applied to a skybox face, e.g. `up=rot90` or `ft=flipx` \
+                (faces: ft, bk, up, dn, lf, rt; transforms: none, flipx, flipy, rot90, rot180, rot270)"
+    )]
+    sky_transforms: Vec<SkyFaceTransform>,
+    #[structopt(
+        long = "check-wads",
+        help = "Check that all WADs and textures required by the map are available, then exit"
+    )]
+    check_wads: bool,
+    #[structopt(
+        long = "export-obj",
+        parse(from_os_str),
+        help = "Export the world geometry to a Wavefront OBJ with its MTL next to it, then exit"
+    )]
+    export_obj: Option<PathBuf>,
+    #[structopt(
+        long,
+        help = "Render this many frames offscreen flying around the spawn point, print \
+                statistics of frame times, then exit"
+    )]
+    benchmark: Option<usize>,
+    #[structopt(long, help = "Print the --check-wads report as JSON")]
+    json: bool,
+    #[structopt(
+        long = "skybox-only",
+        help = "Render only the skybox, without map geometry (toggle with B)"
+    )]
+    skybox_only: bool,
+    #[structopt(
+        long = "lights-rad",
+        parse(from_os_str),
+        help = "Path to lights.rad; listed textures are drawn fullbright (approximates their glow)"
+    )]
+    lights_rad: Option<PathBuf>,
+    #[structopt(
+        long = "emissive-list",
+        parse(from_os_str),
+        help = "Path to a file of `texname strength` lines making textures self-illuminated \
+                (1 is fullbright, overrides --lights-rad)"
+    )]
+    emissive_list: Option<PathBuf>,
+    #[structopt(
+        long = "world-rotate",
+        default_value = "0",
+        help = "Rotate the world around the vertical axis, in degrees"
+    )]
+    world_rotate: f32,
+    #[structopt(
+        long = "world-offset",
+        default_value = "0,0,0",
+        parse(try_from_str = parse_vec3),
+        help = "Offset the world by `x,y,z` map units"
+    )]
+    world_offset: [f32; 3],
+    #[structopt(
+        long,
+        default_value = "1",
+        help = "Gamma correction of lit colors, above 1 brightens dark areas (adjust with + and -)"
+    )]
+    gamma: f32,
+    #[structopt(
+        long,
+        help = "Light textures in linear space, so midtones of lightmap gradients are more \
+                accurate (brighter than usual between light and shadow)"
+    )]
+    srgb: bool,
+    #[structopt(
+        long = "rotate-skybox",
+        help = "Apply --world-rotate to the skybox too"
+    )]
+    rotate_skybox: bool,
+    #[structopt(
+        long = "stream-wads",
+        help = "Read textures from wad files on demand instead of loading them whole"
+    )]
+    stream_wads: bool,
+    #[structopt(
+        long = "merge-faces",
+        help = "Merge adjacent coplanar faces with the same texture (merged ones are drawn unlit)"
+    )]
+    merge_faces: bool,
+    #[structopt(
+        long = "compare",
+        parse(from_os_str),
+        help = "Path to a second bsp map to overlay: faces only in the base map are red, \
+                only in this one are green (swap the base with C)"
+    )]
+    compare_bsp: Option<PathBuf>,
+    #[structopt(
+        long = "texture-dir",
+        parse(from_os_str),
+        help = "Path to a directory of png, tga or bmp images named after textures to use \
+                in place of them, e.g. crate1.png"
+    )]
+    texture_dir: Option<PathBuf>,
+    #[structopt(
+        long = "game-dir",
+        parse(from_os_str),
+        help = "Game directory studio models and sprites of entities are loaded from, e.g. `valve`; \
+                defaults to the parent of the map's directory"
+    )]
+    game_dir: Option<PathBuf>,
+    #[structopt(
+        long = "reverse-z",
+        help = "Use reversed-Z depth (near is 1, far is 0), drawn with the depth test flipped; \
+                precision of the depth buffer stays the same"
+    )]
+    reverse_z: bool,
+    #[structopt(
+        long = "depth-bits",
+        default_value = "24",
+        help = "Requested depth buffer precision in bits"
+    )]
+    depth_bits: u8,
+    #[structopt(
+        long,
+        default_value = "true",
+        parse(try_from_str),
+        help = "Wait for the display's refresh between frames, `--vsync false` to turn it off"
+    )]
+    vsync: bool,
+    #[structopt(
+        long,
+        default_value = "0",
+        parse(try_from_str = parse_msaa),
+        help = "Multisample antialiasing samples per pixel: 0 (off), 2, 4 or 8"
+    )]
+    msaa: u16,
+    #[structopt(
+        long,
+        default_value = "0.1",
+        help = "Degrees of camera rotation per pixel of mouse movement"
+    )]
+    sensitivity: f32,
+    #[structopt(
+        long,
+        default_value = "90",
+        help = "Vertical field of view in degrees, clamped to 30..120"
+    )]
+    fov: f32,
+    #[structopt(
+        long,
+        default_value = "1",
+        help = "Distance to the near clip plane, must be positive and less than --far. N and M \
+                adjust it and K and L the far one, the adjusted planes are saved next to the map \
+                with the bookmarks and used instead of these flags"
+    )]
+    near: f32,
+    #[structopt(
+        long,
+        default_value = "8192",
+        help = "Distance to the far clip plane (render distance), raise it for large maps"
+    )]
+    far: f32,
+    #[structopt(
+        long,
+        default_value = "1",
+        parse(try_from_str = parse_hull),
+        help = "Clip hull walk mode collides with: 0 (point), 1 (standing player), \
+                2 (large monster) or 3 (crouching player)"
+    )]
+    hull: usize,
+    #[structopt(
+        long = "pvs-blend",
+        default_value = "0.1",
+        help = "Seconds the PVS of the previous leaf is still drawn after the camera changes \
+                leaves, so faces don't pop at leaf boundaries (toggle with F8)"
+    )]
+    pvs_blend: f32,
+    #[structopt(
+        long = "filter",
+        default_value = "nearest",
+        help = "Texture filtering: nearest, bilinear (or linear), trilinear or anisotropic \
+                (cycle with F)"
+    )]
+    texture_filter: TextureFilter,
+    #[structopt(
+        long,
+        default_value = "16",
+        parse(try_from_str = parse_anisotropy),
+        help = "Maximal anisotropy of the anisotropic filter, 1 to 16"
+    )]
+    aniso: u16,
+    #[structopt(
+        long = "texel-density",
+        help = "Color faces by texel density instead of textures (toggle with T)"
+    )]
+    texel_density: bool,
+    #[structopt(
+        long = "density-range",
+        default_value = "0.5,2",
+        parse(try_from_str = parse_density_range),
+        help = "Texel densities (texels per unit) mapped to the low and high ramp colors"
+    )]
+    density_range: (f32, f32),
+    #[structopt(
+        long = "density-low-color",
+        default_value = "0,0,1",
+        parse(try_from_str = parse_vec3),
+        help = "`r,g,b` color of faces at the low end of --density-range"
+    )]
+    density_low_color: [f32; 3],
+    #[structopt(
+        long = "density-high-color",
+        default_value = "1,0,0",
+        parse(try_from_str = parse_vec3),
+        help = "`r,g,b` color of faces at the high end of --density-range"
+    )]
+    density_high_color: [f32; 3],
+    #[structopt(
+        long,
+        parse(try_from_str = parse_fog),
+        help = "`r,g,b,density` exponential fog used instead of the map's one, colors are 0 to 1"
+    )]
+    fog: Option<Fog>,
+    #[structopt(
+        long = "clear-color",
+        default_value = "0,0,0",
+        parse(try_from_str = parse_vec3),
+        help = "`r,g,b` color behind the map where there's no skybox, the fog's color is used \
+                in the fog"
+    )]
+    clear_color: [f32; 3],
+    #[structopt(
+        long = "void-checkerboard",
+        help = "Draw a faint checkerboard of --clear-color behind the map instead of the \
+                skybox, so leaks and holes stand out"
+    )]
+    void_checkerboard: bool,
+    #[structopt(
+        long = "water-tint",
+        default_value = "0.1,0.35,0.4",
+        parse(try_from_str = parse_vec3),
+        help = "`r,g,b` color the screen is tinted with while the camera is in water"
+    )]
+    water_tint: [f32; 3],
+    #[structopt(
+        long = "speed-fov",
+        help = "Widen the field of view while moving fast (toggle with V)"
+    )]
+    speed_fov: bool,
+    #[structopt(
+        long = "speed-fov-scale",
+        default_value = "0.02",
+        help = "Extra degrees of fov per unit/second of camera speed"
+    )]
+    speed_fov_scale: f32,
+    #[structopt(
+        long = "speed-fov-max",
+        default_value = "15",
+        help = "Maximal extra fov in degrees"
+    )]
+    speed_fov_max: f32,
+    #[structopt(
+        long = "no-animations",
+        help = "Don't animate textures: no frame sequences, water warp or scrolling"
+    )]
+    no_animations: bool,
+}
+
+fn parse_density_range(s: &str) -> Result<(f32, f32), String> {
+    match s
+        .split_once(',')
+        .map(|(a, b)| (a.trim().parse(), b.trim().parse()))
+    {
+        Some((Ok(min), Ok(max))) if min > 0.0 && min < max => Ok((min, max)),
+        _ => Err(format!(
+            "Expected `min,max` with 0 < min < max, got `{}`",
+            s
+        )),
+    }
+}
+
+fn parse_anisotropy(s: &str) -> Result<u16, String> {
+    match s.parse() {
+        Ok(level) if (1..=MAX_ANISOTROPY).contains(&level) => Ok(level),
+        _ => Err(format!(
+            "Expected anisotropy from 1 to {}, got `{}`",
+            MAX_ANISOTROPY, s
+        )),
+    }
+}
+
+fn parse_fog(s: &str) -> Result<Fog, String> {
+    let n = s
+        .split(',')
+        .map(|x| x.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match n[..] {
+        [r, g, b, density] if density > 0.0 => Ok(Fog {
+            color: [r, g, b],
+            falloff: FogFalloff::Exponential { density },
+        }),
+        _ => Err(format!(
+            "Expected `r,g,b,density` with density > 0, got `{}`",
+            s
+        )),
+    }
+}
+
+fn parse_msaa(s: &str) -> Result<u16, String> {
+    match s.parse() {
+        Ok(samples @ (0 | 2 | 4 | 8)) => Ok(samples),
+        _ => Err(format!("Expected 0, 2, 4 or 8 samples, got `{}`", s)),
+    }
+}
+
+fn parse_hull(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(hull @ 0..=3) => Ok(hull),
+        _ => Err(format!("Expected a hull from 0 to 3, got `{}`", s)),
+    }
+}
+
+fn parse_vec3(s: &str) -> Result<[f32; 3], String> {
+    let n = s
+        .split(',')
+        .map(|x| x.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match n[..] {
+        [x, y, z] => Ok([x, y, z]),
+        _ => Err(format!("Expected `x,y,z`, got `{}`", s)),
+    }
+}
+
+/// Window and input state persisting between events.
+struct AppState {
+    mouse_grabbed: bool,
+    /// Devices report motion to unfocused windows as well, which must be ignored.
+    focused: bool,
+    /// Save the frame once it's drawn.
+    screenshot_requested: bool,
+    /// Teleport to the surface in the center of the screen before drawing the next frame.
+    teleport_requested: bool,
+    /// Read the shown map again before drawing the next frame, keeping the camera.
+    reload_requested: bool,
+    /// Lock the PVS at the camera's leaf or unlock it before drawing the next frame.
+    pvs_lock_toggled: bool,
+    /// Load the map this many steps away in `--bsp` before drawing the next frame.
+    map_step: Option<isize>,
+    held_keys: HashSet<glutin::event::VirtualKeyCode>,
+    last_frame: Instant,
+    /// Collide with the map and fall instead of flying through walls.
+    walk: bool,
+    /// Height of the eyes above the center of the clip hull's box, see `EYE_HEIGHTS`.
+    eye_height: f32,
+    fall_speed: f32,
+    /// Show FPS, position and leaf of the camera on the screen.
+    show_overlay: bool,
+    show_crosshair: bool,
+    /// Units per second, adjusted with `[` and `]`.
+    move_speed: f32,
+    /// When the speed was last changed, so it's shown for a while.
+    speed_changed: Option<Instant>,
+    /// When the texture filter was last changed and to which one, so it's shown for a while.
+    filter_changed: Option<(Instant, TextureFilter)>,
+    /// Time of animated textures, paused together with movement.
+    clock: PausableClock,
+    /// Look around with the mouse while paused.
+    paused_look: bool,
+    fps: FpsCounter,
+    bookmarks: Bookmarks,
+}
+
+impl AppState {
+    fn new(bookmarks: Bookmarks, hull: usize) -> Self {
+        Self {
+            mouse_grabbed: true,
+            focused: true,
+            screenshot_requested: false,
+            teleport_requested: false,
+            reload_requested: false,
+            pvs_lock_toggled: false,
+            map_step: None,
+            held_keys: HashSet::new(),
+            last_frame: Instant::now(),
+            walk: false,
+            eye_height: EYE_HEIGHTS[hull],
+            fall_speed: 0.0,
+            show_overlay: false,
+            show_crosshair: false,
+            move_speed: MOVE_SPEED,
+            speed_changed: None,
+            filter_changed: None,
+            clock: PausableClock::new(Instant::now()),
+            paused_look: false,
+            fps: FpsCounter::new(),
+            bookmarks,
+        }
+    }
+
+    fn is_held(&self, key: glutin::event::VirtualKeyCode) -> bool {
+        self.held_keys.contains(&key)
+    }
+
+    fn toggle_pause(&mut self) {
+        let paused = !self.clock.is_paused();
+        self.clock.set_paused(paused, Instant::now());
+        self.fall_speed = 0.0;
+        info!("{}", if paused { "Paused" } else { "Resumed" });
+    }
+
+    /// Turns the camera by raw mouse motion while the cursor is grabbed.
+    fn look_around(&self, camera: &mut Camera, dx: f32, dy: f32) {
+        if self.mouse_grabbed && self.focused && (!self.clock.is_paused() || self.paused_look) {
+            camera.rotate_by(-dy, dx, 0.0);
+        }
+    }
+
+    /// Multiplies the movement speed by `factor`, keeping it in `MOVE_SPEED_RANGE`.
+    fn scale_move_speed(&mut self, factor: f32) {
+        self.move_speed = (self.move_speed * factor).clamp(MOVE_SPEED_RANGE.0, MOVE_SPEED_RANGE.1);
+        self.speed_changed = Some(Instant::now());
+        info!("Move speed: {:.0}", self.move_speed);
+    }
+
+    /// Lines of the text overlay: all of them if it's toggled on, otherwise only the speed for
+    /// a while after it was changed. The texture filter is shown for a while after it was
+    /// changed and pauses are always shown.
+    fn overlay_lines(
+        &self,
+        fps: f32,
+        position: [f32; 3],
+        leaf: usize,
+        locked_leaf: Option<usize>,
+    ) -> Vec<String> {
+        let mut lines = self.info_lines(fps, position, leaf);
+        if let Some(locked_leaf) = locked_leaf {
+            lines.push(format!("PVS LOCKED AT LEAF {}", locked_leaf));
+        }
+        if self.clock.is_paused() {
+            lines.push("PAUSED".to_string());
+        }
+        lines
+    }
+
+    /// Locks the PVS at the leaf of the camera or unlocks it, see `RenderSettings::locked_leaf`.
+    fn toggle_pvs_lock(&self, camera: &Camera, level: &Level, settings: &mut RenderSettings) {
+        if settings.locked_leaf.take().is_some() {
+            return info!("PVS unlocked");
+        }
+        match level.leaf_at(settings, camera.position.into()) {
+            0 => info!("Can't lock the PVS outside of the world"),
+            leaf => {
+                info!("PVS locked at leaf {}", leaf);
+                settings.locked_leaf = Some(leaf);
+            }
+        }
+    }
+
+    fn info_lines(&self, fps: f32, position: [f32; 3], leaf: usize) -> Vec<String> {
+        let speed = format!("SPEED {:.0}", self.move_speed);
+        let mut lines = if self.show_overlay {
+            vec![
+                format!("FPS {:.0}", fps),
+                format!(
+                    "POS {:.0} {:.0} {:.0}",
+                    position[0], position[1], position[2]
+                ),
+                format!("LEAF {}", leaf),
+                speed,
+            ]
+        } else if self
+            .speed_changed
+            .is_some_and(|changed| changed.elapsed() < CHANGE_SHOWN_FOR)
+        {
+            vec![speed]
+        } else {
+            vec![]
+        };
+        if let Some((changed, filter)) = self.filter_changed {
+            if changed.elapsed() < CHANGE_SHOWN_FOR {
+                lines.push(format!("FILTER {:?}", filter).to_uppercase());
+            }
+        }
+        lines
+    }
+
+    /// Moves the camera by held keys for the time passed since the previous frame, zooming
+    /// works while paused as well.
+    fn update_movement(&mut self, camera: &mut Camera, level: &Level, settings: &RenderSettings) {
+        use glutin::event::VirtualKeyCode;
+
+        let now = Instant::now();
+        let delta = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        camera.update_zoom(self.is_held(VirtualKeyCode::Z), delta);
+        if self.clock.is_paused() {
+            return;
+        }
+
+        let axis = |positive, negative| {
+            (self.is_held(positive) as i8 - self.is_held(negative) as i8) as f32
+        };
+        let forward = axis(VirtualKeyCode::W, VirtualKeyCode::S);
+        let right = axis(VirtualKeyCode::D, VirtualKeyCode::A);
+        if !self.walk {
+            camera.move_relative(forward, right, self.move_speed * delta);
+            return;
+        }
+
+        let delta = delta.min(MAX_FRAME_TIME);
+        let look = camera.forward();
+        let ahead = vec3(look.x, 0.0, look.z);
+        let mut motion = ahead * forward + camera.right() * right;
+        if motion.magnitude2() > 0.0 {
+            motion = motion.normalize() * self.move_speed * delta;
+        }
+        self.fall_speed += GRAVITY * delta;
+        motion -= Camera::up() * self.fall_speed * delta;
+
+        let eye = Camera::up() * self.eye_height;
+        let (body, on_floor) = slide_move(level, settings, camera.position - eye, motion);
+        if on_floor {
+            self.fall_speed = 0.0;
+        }
+        camera.position = body + eye;
+    }
+
+    /// Traces the player's box from the camera along the view up to the far plane and moves it
+    /// in front of the hit surface. The box is traced from below the eye in walk mode.
+    fn teleport_to_surface(
+        &mut self,
+        camera: &mut Camera,
+        level: &Level,
+        settings: &RenderSettings,
+    ) {
+        let eye = if self.walk {
+            Camera::up() * self.eye_height
+        } else {
+            Vector3::zero()
+        };
+        let start = camera.position - eye;
+        let end = start + camera.forward() * camera.far;
+        let trace = level.trace(settings, start.into(), end.into());
+        if trace.all_solid || trace.start_solid {
+            return info!("Can't teleport from inside of a wall");
+        }
+        match trace.normal {
+            Some(normal) => {
+                let hit = Point3::from(trace.end);
+                camera.position = hit + Vector3::from(normal) * TELEPORT_OFFSET + eye;
+                self.fall_speed = 0.0;
+            }
+            None => info!("Nothing to teleport to within the far plane"),
+        }
+    }
+}
+
+/// Moves the player's box by `motion`, sliding along whatever it hits. Returns the new position
+/// and whether the box stands on a floor.
+fn slide_move(
+    level: &Level,
+    settings: &RenderSettings,
+    mut position: Point3<f32>,
+    mut motion: Vector3<f32>,
+) -> (Point3<f32>, bool) {
+    let mut on_floor = false;
+    for _ in 0..SLIDE_ITERATIONS {
+        if motion.magnitude2() == 0.0 {
+            break;
+        }
+        let trace = level.trace(settings, position.into(), (position + motion).into());
+        if trace.all_solid {
+            // Stuck inside of a wall, only noclip helps then
+            return (position, true);
+        }
+        position = trace.end.into();
+        let normal = match trace.normal {
+            Some(normal) => Vector3::from(normal),
+            None => break,
+        };
+        if normal.y > FLOOR_NORMAL_Y {
+            on_floor = true;
+        }
+        // Keep the rest of the motion except for its part going into the plane
+        let remaining = motion * (1.0 - trace.fraction);
+        motion = remaining - normal * remaining.dot(normal);
+    }
+    (position, on_floor)
+}
+
+#[derive(Debug)]
+struct SkyFaceTransform {
+    side: usize,
+    transform: FaceTransform,
+}
+
+impl FromStr for SkyFaceTransform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (side, transform) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected `side=transform`, got `{}`", s))?;
+        let side = render::Cubemap::side_names()
+            .iter()
+            .position(|&name| name == side)
+            .ok_or_else(|| format!("Unknown skybox face `{}`", side))?;
+        let transform = transform.parse()?;
+        Ok(Self { side, transform })
+    }
 }
 
 fn main() {
     init_logger().unwrap();
     let opt = Opt::from_args();
-    start_window_loop(opt.bsp_path, &opt.wad_path, opt.skybox_path);
+    if !(opt.near > 0.0 && opt.near < opt.far) {
+        structopt::clap::Error::with_description(
+            &format!(
+                "Expected 0 < --near < --far, got near {} and far {}",
+                opt.near, opt.far
+            ),
+            structopt::clap::ErrorKind::InvalidValue,
+        )
+        .exit();
+    }
+    if opt.check_wads {
+        std::process::exit(run_check_wads(&opt));
+    }
+    if let Some(obj_path) = &opt.export_obj {
+        std::process::exit(run_export_obj(&opt.bsp_paths[0], obj_path));
+    }
+    let mut options = LevelOptions {
+        stream_wads: opt.stream_wads,
+        merge_faces: opt.merge_faces,
+        compare_bsp: opt.compare_bsp.clone(),
+        texture_dir: opt.texture_dir.clone(),
+        game_dir: opt.game_dir.clone(),
+        hull: opt.hull,
+        ..LevelOptions::default()
+    };
+    for t in &opt.sky_transforms {
+        options.sky_transforms[t.side] = t.transform;
+    }
+    if let Some(path) = &opt.lights_rad {
+        match std::fs::read_to_string(path) {
+            Ok(rad) => options.emissive.extend(
+                file::rad::parse_lights_rad(&rad)
+                    .into_iter()
+                    .map(|light| (light.name.to_ascii_lowercase(), 1.0)),
+            ),
+            Err(e) => warn!("Couldn't read lights.rad at {}: {}", path.display(), e),
+        }
+    }
+    if let Some(path) = &opt.emissive_list {
+        match std::fs::read_to_string(path) {
+            Ok(list) => options.emissive.extend(parse_emissive_list(&list)),
+            Err(e) => warn!("Couldn't read emissive list at {}: {}", path.display(), e),
+        }
+    }
+    let settings = RenderSettings {
+        skybox_only: opt.skybox_only,
+        world_rotate: opt.world_rotate,
+        world_offset: opt.world_offset,
+        rotate_skybox: opt.rotate_skybox,
+        reverse_z: opt.reverse_z,
+        texture_filter: opt.texture_filter,
+        anisotropy: opt.aniso,
+        texel_density: opt.texel_density,
+        no_animations: opt.no_animations,
+        gamma: opt.gamma.clamp(GAMMA_RANGE.0, GAMMA_RANGE.1),
+        srgb: opt.srgb,
+        fog: opt.fog,
+        clear_color: opt.clear_color,
+        void_checkerboard: opt.void_checkerboard,
+        water_tint: opt.water_tint,
+        pvs_blend_time: opt.pvs_blend.max(0.0),
+        density_ramp: DensityRamp {
+            min: opt.density_range.0,
+            max: opt.density_range.1,
+            low_color: opt.density_low_color,
+            high_color: opt.density_high_color,
+        },
+        ..RenderSettings::default()
+    };
+    if settings.texel_density {
+        log_density_legend(&settings.density_ramp);
+    }
+    let speed_fov = SpeedFov::new(opt.speed_fov, opt.speed_fov_scale, Deg(opt.speed_fov_max));
+    let mut camera = Camera::new(1024.0, 768.0, Deg(opt.fov), opt.near, opt.far);
+    camera.sensitivity = opt.sensitivity;
+    camera.reverse_z = opt.reverse_z;
+    let maps: Vec<_> = opt
+        .bsp_paths
+        .iter()
+        .map(|bsp_path| MapPaths {
+            bsp_path: bsp_path.clone(),
+            wad_paths: if opt.wad_path.is_empty() {
+                locate_wads(bsp_path, opt.wad_dir.as_deref())
+            } else {
+                opt.wad_path.clone()
+            },
+        })
+        .collect();
+    if let Some(frames) = opt.benchmark {
+        std::process::exit(run_benchmark(
+            &opt, &maps[0], &options, &settings, camera, frames,
+        ));
+    }
+    if let Err(e) = start_window_loop(
+        maps,
+        opt.skybox_path,
+        options,
+        settings,
+        camera,
+        speed_fov,
+        ContextOptions {
+            depth_bits: opt.depth_bits,
+            vsync: opt.vsync,
+            msaa: opt.msaa,
+        },
+    ) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_emissive_list(s: &str) -> Vec<(String, f32)> {
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(n, line)| {
+            let mut parts = line.split_whitespace();
+            let entry = parts
+                .next()
+                .zip(parts.next().and_then(|x| x.parse::<f32>().ok()));
+            if entry.is_none() {
+                warn!(
+                    "Skipping malformed emissive list line {}: `{}`",
+                    n + 1,
+                    line
+                );
+            }
+            entry.map(|(name, strength)| (name.to_ascii_lowercase(), strength))
+        })
+        .collect()
+}
+
+fn run_check_wads(opt: &Opt) -> i32 {
+    match check::check_wads(&opt.bsp_paths[0], &opt.wad_path, opt.wad_dir.as_deref()) {
+        Ok(report) => {
+            if opt.json {
+                println!("{}", report.to_json());
+            } else {
+                println!("{}", report.to_text());
+            }
+            if report.is_complete() {
+                0
+            } else {
+                1
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
+
+/// Wads listed in the map's `wad` key found in `wad_dir` or next to the map.
+fn locate_wads(bsp_path: &Path, wad_dir: Option<&Path>) -> Vec<PathBuf> {
+    let map_dir = check::map_dir(bsp_path);
+    let search_dirs = wad_dir
+        .into_iter()
+        .chain(map_dir.as_deref())
+        .collect::<Vec<_>>();
+    match check::locate_wads(bsp_path, &search_dirs) {
+        Ok((found, missing)) => {
+            for path in &found {
+                info!("Found wad of the map at {}", path.display());
+            }
+            for name in &missing {
+                warn!("Wad `{}` of the map isn't found", name);
+            }
+            found
+        }
+        // Loading the map will report it as well
+        Err(e) => {
+            debug!("Couldn't look for wads of the map: {}", e);
+            vec![]
+        }
+    }
+}
+
+fn run_benchmark(
+    opt: &Opt,
+    paths: &MapPaths,
+    options: &LevelOptions,
+    settings: &RenderSettings,
+    camera: Camera,
+    frames: usize,
+) -> i32 {
+    let stats = benchmark::run(
+        paths,
+        opt.skybox_path.as_ref(),
+        options,
+        settings,
+        camera,
+        frames,
+        opt.depth_bits,
+    );
+    match stats {
+        Ok(stats) => {
+            let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+            println!(
+                "{} frames: min {:.2} ms, avg {:.2} ms, max {:.2} ms",
+                stats.frames,
+                ms(stats.min),
+                ms(stats.avg),
+                ms(stats.max)
+            );
+            println!(
+                "percentiles: 50% {:.2} ms, 95% {:.2} ms, 99% {:.2} ms",
+                ms(stats.p50),
+                ms(stats.p95),
+                ms(stats.p99)
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn run_export_obj(bsp_path: &Path, obj_path: &Path) -> i32 {
+    let export = || -> Result<(), String> {
+        let bsp_file = file::container::read(bsp_path)
+            .map_err(|e| format!("Failed to read bsp at {}: {}", bsp_path.display(), e))?;
+        let map = RawMap::parse(&bsp_file)
+            .map_err(|e| format!("Failed to parse bsp at {}: {}", bsp_path.display(), e))?;
+        let mtl_path = obj_path.with_extension("mtl");
+        let mtl_name = mtl_path
+            .file_name()
+            .ok_or_else(|| format!("Invalid OBJ path {}", obj_path.display()))?
+            .to_string_lossy();
+        let create = |path: &Path| {
+            fs::File::create(path)
+                .map(io::BufWriter::new)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e))
+        };
+        let (mut obj, mut mtl) = (create(obj_path)?, create(&mtl_path)?);
+        file::export::write_obj(&map, &mtl_name, &mut obj, &mut mtl)
+            .and_then(|_| obj.flush())
+            .and_then(|_| mtl.flush())
+            .map_err(|e| format!("Failed to export OBJ to {}: {}", obj_path.display(), e))
+    };
+    match export() {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
 }
 
 fn get_window_center(window: &glutin::window::Window) -> glutin::dpi::PhysicalPosition<f64> {
-    let out_pos = window.outer_position().unwrap();
+    let out_pos = window
+        .outer_position()
+        .unwrap_or_else(|_| glutin::dpi::PhysicalPosition::new(0, 0));
     let out_size = window.outer_size();
     glutin::dpi::PhysicalPosition {
         x: f64::from(out_pos.x + out_size.width as i32 / 2),
@@ -53,44 +963,167 @@ fn get_window_center(window: &glutin::window::Window) -> glutin::dpi::PhysicalPo
 
 fn grab_cursor(window: &glutin::window::Window) {
     window.set_cursor_visible(false);
-    window.set_cursor_grab(true).unwrap();
-    window
-        .set_cursor_position(get_window_center(window))
-        .unwrap();
+    if let Err(e) = window.set_cursor_grab(true) {
+        warn!("Couldn't grab the cursor: {}", e);
+    }
+    center_cursor(window);
+}
+
+fn center_cursor(window: &glutin::window::Window) {
+    if let Err(e) = window.set_cursor_position(get_window_center(window)) {
+        debug!("Couldn't move the cursor: {}", e);
+    }
 }
 
 fn ungrab_cursor(window: &glutin::window::Window) {
     window.set_cursor_visible(true);
-    window.set_cursor_grab(false).unwrap();
+    if let Err(e) = window.set_cursor_grab(false) {
+        warn!("Couldn't release the cursor: {}", e);
+    }
 }
 
-fn start_window_loop<P: AsRef<Path>>(bsp_path: P, wad_path: &[P], skybox_path: Option<P>) {
-    let event_loop = glutin::event_loop::EventLoop::new();
-    let wb = glutin::window::WindowBuilder::new()
-        .with_title("hlbsp viewer")
-        .with_inner_size(glutin::dpi::LogicalSize::new(1024.0, 768.0));
-    let cb = glutin::ContextBuilder::new();
-
-    let mut camera = Camera::new(1024.0, 768.0, Deg(90.0), 1.0, 8192.0);
-    let display = glium::Display::new(wb, cb, &event_loop).unwrap();
-    grab_cursor(display.gl_window().window());
-
-    let level_render = Level::new(&display, bsp_path, wad_path, skybox_path);
-    if let Some((x, y, z)) = level_render.start_point() {
-        camera.set_position(x, y + CAMERA_OFFSET, z);
-    }
+/// Clears the surface and draws the level seen by the camera.
+fn draw_level<S: Surface>(
+    surface: &mut S,
+    level: &Level,
+    camera: &Camera,
+    time: f32,
+    draw_params: &glium::DrawParameters,
+    settings: &RenderSettings,
+) {
+    let far_depth = if settings.reverse_z { 0.0 } else { 1.0 };
+    surface.clear_color_and_depth(level.clear_color(settings), far_depth);
+    level.render(
+        surface,
+        camera.perspective(),
+        camera.view(),
+        camera.position.into(),
+        time,
+        draw_params,
+        settings,
+    );
+}
 
-    let draw_params = glium::DrawParameters {
+/// Parameters of drawing the level, the depth test follows `RenderSettings::reverse_z`.
+fn draw_parameters(settings: &RenderSettings) -> glium::DrawParameters<'static> {
+    glium::DrawParameters {
         blend: glium::Blend::alpha_blending(),
+        // Faces of bsp maps are wound clockwise looking at their fronts, the map keeps that
+        // and draws faces which have to be seen from both sides without culling
         backface_culling: glium::BackfaceCullingMode::CullCounterClockwise,
         depth: glium::Depth {
-            test: glium::DepthTest::IfLessOrEqual,
+            test: if settings.reverse_z {
+                glium::DepthTest::IfMoreOrEqual
+            } else {
+                glium::DepthTest::IfLessOrEqual
+            },
             write: true,
             ..glium::Depth::default()
         },
         ..glium::DrawParameters::default()
+    }
+}
+
+/// Requested properties of the GL context.
+struct ContextOptions {
+    depth_bits: u8,
+    vsync: bool,
+    /// Samples per pixel, no multisampling if 0.
+    msaa: u16,
+}
+
+/// Map given with `--bsp` and the wads it's loaded with.
+struct MapPaths {
+    bsp_path: PathBuf,
+    wad_paths: Vec<PathBuf>,
+}
+
+/// Loads the map of `paths`, putting the camera at the player start if there's one.
+fn load_level(
+    display: &glium::Display,
+    paths: &MapPaths,
+    skybox_path: Option<&PathBuf>,
+    options: &LevelOptions,
+    settings: &RenderSettings,
+    camera: &mut Camera,
+) -> Result<Level, String> {
+    let stage = Cell::new(None);
+    let log_stage = |current, _| {
+        if stage.replace(Some(current)) != Some(current) {
+            info!("Loading: {:?}", current);
+        }
     };
+    let level = Level::with_progress(
+        display,
+        &paths.bsp_path,
+        &paths.wad_paths.iter().collect::<Vec<_>>(),
+        skybox_path,
+        options,
+        Some(&log_stage),
+    )
+    .map_err(|e| e.to_string())?;
+    if let Some([x, y, z]) = level.start_point(settings) {
+        camera.set_position(x, y + CAMERA_OFFSET, z);
+    }
+    if let Some(direction) = level.start_direction(settings) {
+        camera.look_along(direction.into());
+    }
+    Ok(level)
+}
+
+fn watch_wads(paths: &MapPaths) -> Option<WadWatcher> {
+    WadWatcher::new(&paths.wad_paths)
+        .map_err(|e| warn!("Couldn't watch wads for changes: {}", e))
+        .ok()
+}
+
+fn start_window_loop(
+    maps: Vec<MapPaths>,
+    skybox_path: Option<PathBuf>,
+    options: LevelOptions,
+    mut settings: RenderSettings,
+    mut camera: Camera,
+    mut speed_fov: SpeedFov,
+    context: ContextOptions,
+) -> Result<(), String> {
+    let event_loop = glutin::event_loop::EventLoop::new();
+    let wb = glutin::window::WindowBuilder::new()
+        .with_title(WINDOW_TITLE)
+        .with_inner_size(glutin::dpi::LogicalSize::new(1024.0, 768.0));
+    let cb = glutin::ContextBuilder::new()
+        .with_depth_buffer(context.depth_bits)
+        .with_vsync(context.vsync)
+        .with_multisampling(context.msaa);
 
+    let display = glium::Display::new(wb, cb, &event_loop)
+        .map_err(|e| format!("failed to create window: {}", e))?;
+    grab_cursor(display.gl_window().window());
+    // The window may be of another size than requested, e.g. with HiDPI scaling or tiling
+    let (width, height) = display.get_framebuffer_dimensions();
+    camera.set_viewport(width, height);
+
+    let mut map_index = 0;
+    let bookmarks = Bookmarks::load(bookmarks_path(&maps[map_index].bsp_path));
+    restore_clip_planes(&mut camera, &bookmarks);
+    let mut level_render = load_level(
+        &display,
+        &maps[map_index],
+        skybox_path.as_ref(),
+        &options,
+        &settings,
+        &mut camera,
+    )?;
+    let mut wad_watcher = watch_wads(&maps[map_index]);
+
+    let draw_params = glium::DrawParameters {
+        // Enables GL_MULTISAMPLE, only has an effect with a multisampled framebuffer
+        multisampling: context.msaa > 0,
+        ..draw_parameters(&settings)
+    };
+
+    let overlay = TextOverlay::new(&display);
+    let underwater = Underwater::new(&display, context.msaa, context.depth_bits);
+    let mut state = AppState::new(bookmarks, options.hull);
     event_loop.run(move |event, _, control_flow| {
         let gl_window = display.gl_window();
         let window = gl_window.window();
@@ -98,17 +1131,127 @@ fn start_window_loop<P: AsRef<Path>>(bsp_path: P, wad_path: &[P], skybox_path: O
             glutin::event::Event::WindowEvent {
                 window_id: _,
                 event: wevent,
-            } => *control_flow = process_window(window, &wevent, &mut camera),
+            } => {
+                *control_flow = process_window(
+                    window,
+                    &wevent,
+                    &mut state,
+                    &mut camera,
+                    &mut settings,
+                    &mut speed_fov,
+                )
+            }
+            // Raw motion isn't accelerated or stopped at the edges of the screen like the cursor
+            glutin::event::Event::DeviceEvent {
+                event: glutin::event::DeviceEvent::MouseMotion { delta: (dx, dy) },
+                ..
+            } => state.look_around(&mut camera, dx as f32, dy as f32),
             glutin::event::Event::MainEventsCleared => window.request_redraw(),
             glutin::event::Event::RedrawRequested(_) => {
+                if state.teleport_requested {
+                    state.teleport_requested = false;
+                    state.teleport_to_surface(&mut camera, &level_render, &settings);
+                }
+                if state.pvs_lock_toggled {
+                    state.pvs_lock_toggled = false;
+                    state.toggle_pvs_lock(&camera, &level_render, &mut settings);
+                }
+                if state.reload_requested {
+                    state.reload_requested = false;
+                    // Leaves of the recompiled map are numbered anew
+                    settings.locked_leaf = None;
+                    let bsp_path = &maps[map_index].bsp_path;
+                    if let Err(e) = level_render.reload_map(&display, bsp_path, &options) {
+                        warn!("Couldn't reload the map, keeping the old one: {}", e);
+                    }
+                }
+                if let Some(step) = state.map_step.take() {
+                    if maps.len() > 1 {
+                        let next = (map_index as isize + step).rem_euclid(maps.len() as isize);
+                        let next = next as usize;
+                        info!("Switching to {}", maps[next].bsp_path.display());
+                        match load_level(
+                            &display,
+                            &maps[next],
+                            skybox_path.as_ref(),
+                            &options,
+                            &settings,
+                            &mut camera,
+                        ) {
+                            Ok(level) => {
+                                map_index = next;
+                                level_render = level;
+                                wad_watcher = watch_wads(&maps[map_index]);
+                                settings.locked_leaf = None;
+                                state.bookmarks =
+                                    Bookmarks::load(bookmarks_path(&maps[map_index].bsp_path));
+                                restore_clip_planes(&mut camera, &state.bookmarks);
+                                state.fall_speed = 0.0;
+                            }
+                            Err(e) => warn!("Couldn't switch maps: {}", e),
+                        }
+                    }
+                }
                 let mut target = display.draw();
 
-                let projection = camera.perspective();
-                let view = camera.view();
-
-                target.clear_color_and_depth((1.0, 1.0, 0.0, 1.0), 1.0);
-                level_render.render(&mut target, projection, view, &draw_params);
-                target.finish().unwrap();
+                if let Some(wad_watcher) = &wad_watcher {
+                    for path in wad_watcher.changed() {
+                        level_render.reload_wad(&display, path);
+                    }
+                }
+                state.update_movement(&mut camera, &level_render, &settings);
+                speed_fov.update(&mut camera);
+                let time = state.clock.seconds(Instant::now());
+                let in_water =
+                    level_render.contents_at(&settings, camera.position.into()) == Contents::Water;
+                let drawn = in_water
+                    && underwater.render(
+                        &display,
+                        &mut target,
+                        settings.water_tint,
+                        time,
+                        |framebuffer| {
+                            draw_level(
+                                framebuffer,
+                                &level_render,
+                                &camera,
+                                time,
+                                &draw_params,
+                                &settings,
+                            )
+                        },
+                    );
+                if !drawn {
+                    draw_level(
+                        &mut target,
+                        &level_render,
+                        &camera,
+                        time,
+                        &draw_params,
+                        &settings,
+                    );
+                }
+                let fps = state.fps.frame();
+                let lines = state.overlay_lines(
+                    fps,
+                    level_render.to_map_space(&settings, camera.position.into()),
+                    level_render.leaf_at(&settings, camera.position.into()),
+                    settings.locked_leaf,
+                );
+                overlay.render(&display, &mut target, &lines);
+                if state.show_crosshair {
+                    overlay.render_crosshair(&display, &mut target);
+                }
+                if let Err(e) = target.finish() {
+                    warn!("Couldn't swap buffers: {}", e);
+                }
+                if state.screenshot_requested {
+                    state.screenshot_requested = false;
+                    match save_screenshot(&display) {
+                        Ok(path) => info!("Screenshot saved to {}", path.display()),
+                        Err(e) => warn!("Couldn't save screenshot: {}", e),
+                    }
+                }
             }
             _ => {
                 let next_frame_time =
@@ -119,56 +1262,232 @@ fn start_window_loop<P: AsRef<Path>>(bsp_path: P, wad_path: &[P], skybox_path: O
     });
 }
 
+fn log_density_legend(ramp: &DensityRamp) {
+    info!(
+        "Texel density legend: {:?} at {} and less, {:?} at {} and more texels per unit",
+        ramp.low_color, ramp.min, ramp.high_color, ramp.max
+    );
+}
+
+fn log_clip_planes(camera: &Camera) {
+    info!("Clip planes: near={}, far={}", camera.near, camera.far);
+}
+
+/// Logs the clip planes changed with the keys and saves them for the map.
+fn clip_planes_changed(camera: &Camera, bookmarks: &mut Bookmarks) {
+    log_clip_planes(camera);
+    bookmarks.set_clip_planes([camera.near, camera.far]);
+}
+
+/// Uses the clip planes saved for the map instead of the current ones, if there are any.
+fn restore_clip_planes(camera: &mut Camera, bookmarks: &Bookmarks) {
+    if let Some([near, far]) = bookmarks.clip_planes() {
+        if near > 0.0 && near < far {
+            camera.near = near;
+            camera.far = far;
+            log_clip_planes(camera);
+        }
+    }
+}
+
+fn adjust_gamma(settings: &mut RenderSettings, delta: f32) {
+    settings.gamma = (settings.gamma + delta).clamp(GAMMA_RANGE.0, GAMMA_RANGE.1);
+    info!("Gamma: {:.1}", settings.gamma);
+}
+
+/// Slot of bookmarks for number keys 1 to 9.
+fn bookmark_slot(key: glutin::event::VirtualKeyCode) -> Option<u8> {
+    use glutin::event::VirtualKeyCode::*;
+
+    [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+        .iter()
+        .position(|&k| k == key)
+        .map(|i| i as u8 + 1)
+}
+
+/// Saves the camera to the slot with Shift held, otherwise moves it to the saved state.
+fn use_bookmark(state: &mut AppState, camera: &mut Camera, slot: u8) {
+    use glutin::event::VirtualKeyCode;
+
+    if state.is_held(VirtualKeyCode::LShift) || state.is_held(VirtualKeyCode::RShift) {
+        state.bookmarks.set(slot, camera.snapshot());
+    } else if let Some(camera_state) = state.bookmarks.get(slot) {
+        camera.restore(camera_state);
+        state.fall_speed = 0.0;
+    } else {
+        info!("Bookmark {} is empty, save it with Shift+{}", slot, slot);
+    }
+}
+
 fn process_window(
     window: &glutin::window::Window,
     wevent: &glutin::event::WindowEvent,
+    state: &mut AppState,
     camera: &mut Camera,
+    settings: &mut RenderSettings,
+    speed_fov: &mut SpeedFov,
 ) -> glutin::event_loop::ControlFlow {
     match wevent {
         glutin::event::WindowEvent::KeyboardInput { input, .. } => {
+            if let Some(virt_keycode) = input.virtual_keycode {
+                match input.state {
+                    glutin::event::ElementState::Pressed => state.held_keys.insert(virt_keycode),
+                    glutin::event::ElementState::Released => state.held_keys.remove(&virt_keycode),
+                };
+            }
             if input.state == glutin::event::ElementState::Pressed {
                 if let Some(virt_keycode) = input.virtual_keycode {
                     match virt_keycode {
-                        glutin::event::VirtualKeyCode::W => camera.move_forward(MOVE_SPEED),
-                        glutin::event::VirtualKeyCode::S => camera.move_back(MOVE_SPEED),
-                        glutin::event::VirtualKeyCode::A => camera.move_left(MOVE_SPEED),
-                        glutin::event::VirtualKeyCode::D => camera.move_right(MOVE_SPEED),
-                        glutin::event::VirtualKeyCode::G => unsafe {
-                            if MOUSE_GRABBED {
+                        glutin::event::VirtualKeyCode::N => {
+                            camera.scale_clip_planes(1.0 / CLIP_PLANE_STEP, 1.0);
+                            clip_planes_changed(camera, &mut state.bookmarks);
+                        }
+                        glutin::event::VirtualKeyCode::M => {
+                            camera.scale_clip_planes(CLIP_PLANE_STEP, 1.0);
+                            clip_planes_changed(camera, &mut state.bookmarks);
+                        }
+                        glutin::event::VirtualKeyCode::K => {
+                            camera.scale_clip_planes(1.0, 1.0 / CLIP_PLANE_STEP);
+                            clip_planes_changed(camera, &mut state.bookmarks);
+                        }
+                        glutin::event::VirtualKeyCode::L => {
+                            camera.scale_clip_planes(1.0, CLIP_PLANE_STEP);
+                            clip_planes_changed(camera, &mut state.bookmarks);
+                        }
+                        glutin::event::VirtualKeyCode::B => {
+                            settings.skybox_only = !settings.skybox_only;
+                        }
+                        glutin::event::VirtualKeyCode::V => {
+                            speed_fov.enabled = !speed_fov.enabled;
+                        }
+                        glutin::event::VirtualKeyCode::C => {
+                            settings.compare_swapped = !settings.compare_swapped;
+                            info!("Base map swapped: {}", settings.compare_swapped);
+                        }
+                        glutin::event::VirtualKeyCode::F => {
+                            settings.texture_filter = settings.texture_filter.next();
+                            state.filter_changed = Some((Instant::now(), settings.texture_filter));
+                            info!("Texture filter: {:?}", settings.texture_filter);
+                            window.set_title(&format!(
+                                "{} [{:?}]",
+                                WINDOW_TITLE, settings.texture_filter
+                            ));
+                        }
+                        glutin::event::VirtualKeyCode::T => {
+                            settings.texel_density = !settings.texel_density;
+                            if settings.texel_density {
+                                log_density_legend(&settings.density_ramp);
+                            }
+                        }
+                        glutin::event::VirtualKeyCode::F3 => {
+                            state.show_overlay = !state.show_overlay;
+                        }
+                        glutin::event::VirtualKeyCode::F4 => {
+                            settings.leaf_colors = !settings.leaf_colors;
+                        }
+                        glutin::event::VirtualKeyCode::E => {
+                            settings.entity_boxes = !settings.entity_boxes;
+                        }
+                        glutin::event::VirtualKeyCode::F5 => {
+                            settings.studio_models = !settings.studio_models;
+                        }
+                        glutin::event::VirtualKeyCode::F6 => {
+                            settings.sprites = !settings.sprites;
+                        }
+                        glutin::event::VirtualKeyCode::P => state.toggle_pause(),
+                        glutin::event::VirtualKeyCode::O => {
+                            state.paused_look = !state.paused_look;
+                            info!("Mouse look while paused: {}", state.paused_look);
+                        }
+                        glutin::event::VirtualKeyCode::H => {
+                            state.show_crosshair = !state.show_crosshair;
+                        }
+                        glutin::event::VirtualKeyCode::LBracket => {
+                            state.scale_move_speed(1.0 / MOVE_SPEED_STEP);
+                        }
+                        glutin::event::VirtualKeyCode::RBracket => {
+                            state.scale_move_speed(MOVE_SPEED_STEP);
+                        }
+                        glutin::event::VirtualKeyCode::F12 => {
+                            state.screenshot_requested = true;
+                        }
+                        glutin::event::VirtualKeyCode::R => state.teleport_requested = true,
+                        glutin::event::VirtualKeyCode::F9 => state.reload_requested = true,
+                        glutin::event::VirtualKeyCode::F7 => state.pvs_lock_toggled = true,
+                        glutin::event::VirtualKeyCode::F8 => {
+                            settings.pvs_blend = !settings.pvs_blend;
+                            info!("PVS blending: {}", settings.pvs_blend);
+                        }
+                        glutin::event::VirtualKeyCode::PageUp => state.map_step = Some(-1),
+                        glutin::event::VirtualKeyCode::PageDown => state.map_step = Some(1),
+                        glutin::event::VirtualKeyCode::F1 => {
+                            settings.wireframe = !settings.wireframe;
+                        }
+                        glutin::event::VirtualKeyCode::F2 => {
+                            settings.wireframe_overlay = !settings.wireframe_overlay;
+                        }
+                        glutin::event::VirtualKeyCode::G => {
+                            if state.mouse_grabbed {
                                 ungrab_cursor(window);
-                                MOUSE_GRABBED = false;
                             } else {
                                 grab_cursor(window);
-                                MOUSE_GRABBED = true;
                             }
-                        },
+                            state.mouse_grabbed = !state.mouse_grabbed;
+                        }
+                        glutin::event::VirtualKeyCode::Equals
+                        | glutin::event::VirtualKeyCode::Plus
+                        | glutin::event::VirtualKeyCode::NumpadAdd => {
+                            adjust_gamma(settings, GAMMA_STEP);
+                        }
+                        glutin::event::VirtualKeyCode::Minus
+                        | glutin::event::VirtualKeyCode::NumpadSubtract => {
+                            adjust_gamma(settings, -GAMMA_STEP);
+                        }
+                        glutin::event::VirtualKeyCode::Y => {
+                            settings.toggle_textures = !settings.toggle_textures;
+                        }
+                        glutin::event::VirtualKeyCode::X => {
+                            state.walk = !state.walk;
+                            state.fall_speed = 0.0;
+                            info!(
+                                "{}",
+                                if state.walk {
+                                    "Walk mode"
+                                } else {
+                                    "Noclip mode"
+                                }
+                            );
+                        }
                         glutin::event::VirtualKeyCode::Q => {
                             return glutin::event_loop::ControlFlow::Exit
                         }
-                        _ => (),
+                        key => {
+                            if let Some(slot) = bookmark_slot(key) {
+                                use_bookmark(state, camera, slot);
+                            }
+                        }
                     }
                 }
             }
             glutin::event_loop::ControlFlow::Poll
         }
-        glutin::event::WindowEvent::CursorMoved {
-            position: glutin::dpi::PhysicalPosition { x, y },
-            ..
-        } => {
-            unsafe {
-                if MOUSE_GRABBED {
-                    let mouse_pos = get_window_center(window);
-                    let (dx, dy) = (x - mouse_pos.x, y - mouse_pos.y);
-                    window
-                        .set_cursor_position(get_window_center(window))
-                        .unwrap();
-                    camera.rotate_by((-dy * 0.1) as f32, (dx * 0.1) as f32, 0.0);
-                }
-            }
+        glutin::event::WindowEvent::Resized(glutin::dpi::PhysicalSize { width, height }) => {
+            camera.set_viewport(*width, *height);
             glutin::event_loop::ControlFlow::Poll
         }
-        glutin::event::WindowEvent::Resized(glutin::dpi::PhysicalSize { width, height }) => {
-            camera.aspect_ratio = (*width as f32) / (*height as f32);
+        // The window is resized to `new_inner_size` after it, not every platform sends `Resized`
+        glutin::event::WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+            camera.set_viewport(new_inner_size.width, new_inner_size.height);
+            glutin::event_loop::ControlFlow::Poll
+        }
+        // Releases aren't delivered to unfocused windows
+        glutin::event::WindowEvent::Focused(false) => {
+            state.focused = false;
+            state.held_keys.clear();
+            glutin::event_loop::ControlFlow::Poll
+        }
+        glutin::event::WindowEvent::Focused(true) => {
+            state.focused = true;
             glutin::event_loop::ControlFlow::Poll
         }
         glutin::event::WindowEvent::CloseRequested => glutin::event_loop::ControlFlow::Exit,
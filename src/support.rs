@@ -1,14 +1,47 @@
 use cgmath::{perspective, vec3, Angle, Deg, Euler, InnerSpace, Matrix4, Point3, Rad, Vector3};
+use glium::{texture::RawImage2d, Display};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 pub type Scal = f32;
 
+/// Maximal pitch up or down in degrees, so the view never flips over.
+const MAX_PITCH: Scal = 89.0;
+/// Range of the vertical field of view in degrees.
+const FOV_RANGE: (Scal, Scal) = (30.0, 120.0);
+const DEFAULT_SENSITIVITY: Scal = 0.1;
+/// Vertical field of view of the fully zoomed camera in degrees.
+const ZOOM_FOV: Scal = 20.0;
+/// Part of the whole zoom passed in a second, so it takes a fifth of a second.
+const ZOOM_SPEED: Scal = 5.0;
+
+/// Position and orientation of a `Camera`, in the view space of the render settings it was taken
+/// with.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub position: [Scal; 3],
+    /// Pitch, yaw and roll in degrees.
+    pub rotation: [Scal; 3],
+}
+
 pub struct Camera {
     pub aspect_ratio: Scal,
     pub fov: Rad<Scal>,
+    /// Temporary widening added to `fov`, e.g. by `SpeedFov`.
+    pub fov_boost: Rad<Scal>,
     pub near: Scal,
     pub far: Scal,
     pub position: Point3<Scal>,
     pub rotation: Euler<Deg<Scal>>,
+    /// Degrees of rotation per unit passed to `rotate_by`, e.g. a pixel of mouse movement.
+    pub sensitivity: Scal,
+    /// Map the near plane to depth 1 and the far one to 0, see `RenderSettings::reverse_z`.
+    pub reverse_z: bool,
+    /// From 0 for the usual field of view to 1 for `ZOOM_FOV`, see `update_zoom`.
+    zoom: Scal,
 }
 
 impl Camera {
@@ -21,24 +54,33 @@ impl Camera {
     ) -> Self {
         Self {
             aspect_ratio: width / height,
-            fov: fov.into(),
+            fov: Deg(Deg::from(fov.into()).0.clamp(FOV_RANGE.0, FOV_RANGE.1)).into(),
+            fov_boost: Rad(0.0),
             near,
             far,
             position: Point3::new(0.0, 0.0, 0.0),
             rotation: Euler::new(Deg(0.0), Deg(0.0), Deg(0.0)),
+            sensitivity: DEFAULT_SENSITIVITY,
+            reverse_z: false,
+            zoom: 0.0,
         }
     }
 
-    pub fn rotate_by(&mut self, pitch: Scal, yaw: Scal, roll: Scal) {
-        self.rotation.x = Deg(self.rotation.x.0 + pitch);
-        // TODO : replace with clamp when it'll be stable
-        if self.rotation.x.0 > 89.9 {
-            self.rotation.x.0 = 89.9;
-        } else if self.rotation.x.0 < -89.9 {
-            self.rotation.x.0 = -89.9;
+    /// Fits the aspect ratio to a framebuffer of `width` x `height` pixels. Empty ones, e.g. of
+    /// minimized windows, keep the previous ratio.
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect_ratio = width as Scal / height as Scal;
         }
-        self.rotation.y += Deg(yaw);
-        self.rotation.z += Deg(roll);
+    }
+
+    /// Turns slower while zoomed, by the ratio of the zoomed field of view to the usual one.
+    pub fn rotate_by(&mut self, pitch: Scal, yaw: Scal, roll: Scal) {
+        let sensitivity = self.sensitivity * (self.zoomed_fov() / (self.fov + self.fov_boost));
+        let (pitch, yaw, roll) = (pitch * sensitivity, yaw * sensitivity, roll * sensitivity);
+        self.rotation.x = Deg((self.rotation.x.0 + pitch).clamp(-MAX_PITCH, MAX_PITCH));
+        self.rotation.y = (self.rotation.y + Deg(yaw)).normalize();
+        self.rotation.z = (self.rotation.z + Deg(roll)).normalize();
     }
 
     pub const fn up() -> Vector3<Scal> {
@@ -58,30 +100,80 @@ impl Camera {
         self.forward().cross(Self::up()).normalize()
     }
 
+    /// Turns the camera to look along the direction, without a roll.
+    pub fn look_along(&mut self, direction: Vector3<Scal>) {
+        let direction = direction.normalize();
+        self.rotation.x = Deg(Deg::from(Rad(direction.y.asin()))
+            .0
+            .clamp(-MAX_PITCH, MAX_PITCH));
+        self.rotation.y = Rad(direction.z.atan2(direction.x)).into();
+        self.rotation.z = Deg(0.0);
+    }
+
+    pub fn snapshot(&self) -> CameraState {
+        CameraState {
+            position: self.position.into(),
+            rotation: [self.rotation.x.0, self.rotation.y.0, self.rotation.z.0],
+        }
+    }
+
+    pub fn restore(&mut self, state: CameraState) {
+        let [x, y, z] = state.position;
+        self.set_position(x, y, z);
+        let [pitch, yaw, roll] = state.rotation;
+        self.rotation = Euler::new(Deg(pitch.clamp(-MAX_PITCH, MAX_PITCH)), Deg(yaw), Deg(roll));
+    }
+
     pub fn set_position(&mut self, x: Scal, y: Scal, z: Scal) {
         self.position.x = x;
         self.position.y = y;
         self.position.z = z;
     }
 
-    pub fn move_forward(&mut self, speed: Scal) {
-        self.position += self.forward() * speed;
+    /// Moves by `distance` along the sum of forward and right axes scaled by the factors,
+    /// so moving diagonally isn't faster.
+    pub fn move_relative(&mut self, forward: Scal, right: Scal, distance: Scal) {
+        let direction = self.forward() * forward + self.right() * right;
+        if direction.magnitude2() > 0.0 {
+            self.position += direction.normalize() * distance;
+        }
     }
 
-    pub fn move_back(&mut self, speed: Scal) {
-        self.move_forward(-speed);
+    pub fn scale_clip_planes(&mut self, near_factor: Scal, far_factor: Scal) {
+        let near = self.near * near_factor;
+        let far = self.far * far_factor;
+        if near > 0.0 && near < far {
+            self.near = near;
+            self.far = far;
+        }
     }
 
-    pub fn move_right(&mut self, speed: Scal) {
-        self.position += self.right() * speed;
+    /// Zooms in towards `ZOOM_FOV` while `zoomed`, otherwise back out, over `dt` seconds.
+    pub fn update_zoom(&mut self, zoomed: bool, dt: Scal) {
+        let step = dt * ZOOM_SPEED;
+        self.zoom = if zoomed {
+            (self.zoom + step).min(1.0)
+        } else {
+            (self.zoom - step).max(0.0)
+        };
     }
 
-    pub fn move_left(&mut self, speed: Scal) {
-        self.move_right(-speed);
+    /// Field of view with `fov_boost`, narrowed by the zoom. It's never widened by it.
+    fn zoomed_fov(&self) -> Rad<Scal> {
+        let fov = self.fov + self.fov_boost;
+        let zoomed = Rad(Rad::from(Deg(ZOOM_FOV)).0.min(fov.0));
+        fov + (zoomed - fov) * self.zoom
     }
 
+    /// Projection of the view space. With `reverse_z` its clip z is negated, so the near
+    /// plane gets depth 1 and the far one 0.
     pub fn perspective(&self) -> Matrix4<Scal> {
-        perspective(self.fov, self.aspect_ratio, self.near, self.far)
+        let projection = perspective(self.zoomed_fov(), self.aspect_ratio, self.near, self.far);
+        if self.reverse_z {
+            Matrix4::from_nonuniform_scale(1.0, 1.0, -1.0) * projection
+        } else {
+            projection
+        }
     }
 
     pub fn view(&self) -> Matrix4<Scal> {
@@ -89,6 +181,122 @@ impl Camera {
     }
 }
 
+/// Widens the field of view proportionally to camera speed.
+pub struct SpeedFov {
+    pub enabled: bool,
+    /// Degrees of extra fov per unit/second of speed.
+    pub scale: Scal,
+    pub max: Deg<Scal>,
+    last_position: Option<Point3<Scal>>,
+    last_update: Instant,
+}
+
+impl SpeedFov {
+    const RESPONSE: Scal = 5.0;
+
+    pub fn new(enabled: bool, scale: Scal, max: Deg<Scal>) -> Self {
+        Self {
+            enabled,
+            scale,
+            max,
+            last_position: None,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, camera: &mut Camera) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+
+        let target = match self.last_position {
+            Some(last) if self.enabled && dt > 0.0 => {
+                let speed = (camera.position - last).magnitude() / dt;
+                Deg((speed * self.scale).min(self.max.0))
+            }
+            _ => Deg(0.0),
+        };
+        self.last_position = Some(camera.position);
+
+        // Ease towards the target instead of jumping, movement comes in bursts
+        let current = Deg::from(camera.fov_boost);
+        let t = (dt * Self::RESPONSE).min(1.0);
+        camera.fov_boost = (current + (target - current) * t).into();
+    }
+}
+
+/// Time since a start which doesn't pass while paused, e.g. of animated textures.
+pub struct PausableClock {
+    started: Instant,
+    /// Time spent paused before the current pause.
+    paused_for: Duration,
+    paused_at: Option<Instant>,
+}
+
+impl PausableClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            paused_for: Duration::ZERO,
+            paused_at: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn set_paused(&mut self, paused: bool, now: Instant) {
+        match (self.paused_at, paused) {
+            (None, true) => self.paused_at = Some(now),
+            (Some(paused_at), false) => {
+                self.paused_for += now.saturating_duration_since(paused_at);
+                self.paused_at = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Seconds passed by `now` not counting pauses.
+    pub fn seconds(&self, now: Instant) -> Scal {
+        let now = self.paused_at.unwrap_or(now);
+        (now.saturating_duration_since(self.started))
+            .saturating_sub(self.paused_for)
+            .as_secs_f32()
+    }
+}
+
+/// Frames per second averaged over periods of `FpsCounter::PERIOD`.
+pub struct FpsCounter {
+    frames: u32,
+    period_start: Instant,
+    fps: Scal,
+}
+
+impl FpsCounter {
+    const PERIOD: Scal = 0.5;
+
+    pub fn new() -> Self {
+        Self {
+            frames: 0,
+            period_start: Instant::now(),
+            fps: 0.0,
+        }
+    }
+
+    /// Counts a drawn frame, returns the rate of the last full period.
+    pub fn frame(&mut self) -> Scal {
+        self.frames += 1;
+        let elapsed = self.period_start.elapsed().as_secs_f32();
+        if elapsed >= Self::PERIOD {
+            self.fps = self.frames as Scal / elapsed;
+            self.frames = 0;
+            self.period_start = Instant::now();
+        }
+        self.fps
+    }
+}
+
 use log::{LevelFilter, Metadata, Record, SetLoggerError};
 
 struct SimpleLogger;
@@ -110,3 +318,109 @@ impl log::Log for SimpleLogger {
 pub fn init_logger() -> Result<(), SetLoggerError> {
     log::set_boxed_logger(Box::new(SimpleLogger)).map(|()| log::set_max_level(LevelFilter::Debug))
 }
+
+/// Saves the last presented frame to `screenshot-<unix millis>.png` in the working directory.
+pub fn save_screenshot(display: &Display) -> Result<PathBuf, String> {
+    let frame: RawImage2d<u8> = display.read_front_buffer().map_err(|e| e.to_string())?;
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    let path = PathBuf::from(format!("screenshot-{}.png", millis));
+
+    let image = image::RgbaImage::from_raw(frame.width, frame.height, frame.data.into_owned())
+        .ok_or("framebuffer size mismatch")?;
+    // Rows are read bottom to top
+    image::imageops::flip_vertical(&image)
+        .save(&path)
+        .map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = Camera::new(1024.0, 768.0, Deg(90.0), 1.0, 8192.0);
+        for _ in 0..1000 {
+            camera.rotate_by(7.5, 13.0, 0.0);
+            assert!(camera.rotation.x.0.abs() <= MAX_PITCH);
+            assert!(camera.forward().y < 1.0);
+        }
+        for _ in 0..1000 {
+            camera.rotate_by(-7.5, 13.0, 0.0);
+            assert!(camera.rotation.x.0.abs() <= MAX_PITCH);
+        }
+        assert!((0.0..360.0).contains(&camera.rotation.y.0));
+    }
+
+    #[test]
+    fn zoom_narrows_fov_and_slows_turning() {
+        let mut camera = Camera::new(1024.0, 768.0, Deg(90.0), 1.0, 8192.0);
+        camera.update_zoom(true, 0.1);
+        let halfway = Deg::from(camera.zoomed_fov()).0;
+        assert!((halfway - 55.0).abs() < 1e-3);
+        camera.update_zoom(true, 1.0);
+        assert!((Deg::from(camera.zoomed_fov()).0 - ZOOM_FOV).abs() < 1e-3);
+
+        camera.rotate_by(0.0, 90.0, 0.0);
+        let yaw = 90.0 * DEFAULT_SENSITIVITY * ZOOM_FOV / 90.0;
+        assert!((camera.rotation.y.0 - yaw).abs() < 1e-3);
+
+        camera.update_zoom(false, 10.0);
+        assert_eq!(camera.zoomed_fov(), camera.fov);
+    }
+
+    #[test]
+    fn empty_viewport_keeps_aspect_ratio() {
+        let mut camera = Camera::new(1024.0, 768.0, Deg(90.0), 1.0, 8192.0);
+        camera.set_viewport(3440, 1440);
+        assert_eq!(camera.aspect_ratio, 3440.0 / 1440.0);
+        camera.set_viewport(3440, 0);
+        assert_eq!(camera.aspect_ratio, 3440.0 / 1440.0);
+    }
+
+    #[test]
+    fn clock_stops_while_paused() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut clock = PausableClock::new(start);
+        assert_eq!(clock.seconds(at(2)), 2.0);
+        clock.set_paused(true, at(2));
+        assert!(clock.is_paused());
+        assert_eq!(clock.seconds(at(5)), 2.0);
+        clock.set_paused(true, at(6));
+        clock.set_paused(false, at(7));
+        assert_eq!(clock.seconds(at(7)), 2.0);
+        assert_eq!(clock.seconds(at(10)), 5.0);
+    }
+
+    #[test]
+    fn look_along_matches_forward() {
+        let mut camera = Camera::new(1024.0, 768.0, Deg(90.0), 1.0, 8192.0);
+        for direction in [
+            vec3(-1.0, 0.0, 0.0),
+            vec3(0.0, 0.5, -1.0),
+            vec3(3.0, -1.0, 2.0),
+        ] {
+            camera.look_along(direction);
+            assert!((camera.forward() - direction.normalize()).magnitude() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn restore_returns_to_snapshot() {
+        let mut camera = Camera::new(1024.0, 768.0, Deg(90.0), 1.0, 8192.0);
+        camera.set_position(10.0, -20.0, 30.0);
+        camera.rotate_by(100.0, 450.0, 0.0);
+        let state = camera.snapshot();
+        let forward = camera.forward();
+
+        camera.set_position(0.0, 0.0, 0.0);
+        camera.look_along(vec3(1.0, 0.0, 0.0));
+        camera.restore(state);
+        assert_eq!(camera.position, Point3::new(10.0, -20.0, 30.0));
+        assert!((camera.forward() - forward).magnitude() < 1e-4);
+    }
+}
@@ -0,0 +1,63 @@
+use log::warn;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+};
+
+/// Watches wad files for changes. Directories of the files are watched instead of the files
+/// themselves, as editors often save by replacing them.
+pub struct WadWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// Absolute paths of watched files with the wad paths they were given as.
+    wads: Vec<(PathBuf, PathBuf)>,
+}
+
+/// File to watch for the wad path, the archive for ones inside of zip or pak.
+fn watched_file(path: &Path) -> PathBuf {
+    file::container::split_zip_path(path)
+        .or_else(|| file::container::split_pak_path(path))
+        .map_or_else(|| path.to_path_buf(), |(archive, _)| archive)
+}
+
+impl WadWatcher {
+    pub fn new(wad_paths: &[PathBuf]) -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+
+        let wads: Vec<_> = wad_paths
+            .iter()
+            .filter_map(|path| Some((watched_file(path).canonicalize().ok()?, path.clone())))
+            .collect();
+        let dirs: HashSet<_> = wads.iter().filter_map(|(file, _)| file.parent()).collect();
+        for dir in dirs {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                warn!("Couldn't watch {} for wad changes: {}", dir.display(), e);
+            }
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            wads,
+        })
+    }
+
+    /// Wad paths changed since the previous call, as they were passed to `new`.
+    pub fn changed(&self) -> Vec<&Path> {
+        let changed_files: HashSet<_> = self
+            .events
+            .try_iter()
+            .filter_map(Result::ok)
+            .filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+            .flat_map(|event| event.paths)
+            .collect();
+        self.wads
+            .iter()
+            .filter(|(file, _)| changed_files.contains(file))
+            .map(|(_, path)| path.as_path())
+            .collect()
+    }
+}